@@ -4,6 +4,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/APU_DMC
+#[derive(Clone)]
 pub struct DMCChannel {
     enabled: bool,
     even_latch: bool,
@@ -1,4 +1,5 @@
 // https://wiki.nesdev.com/w/index.php/APU_Envelope
+#[derive(Clone)]
 pub struct Envelope {
     pub constant_volume: bool,
     pub loop_flag: bool,
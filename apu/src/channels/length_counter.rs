@@ -1,4 +1,5 @@
 // https://wiki.nesdev.com/w/index.php/APU_Length_Counter
+#[derive(Clone)]
 pub struct LengthCounter {
     pub enabled: bool,
     pub counter: u8,
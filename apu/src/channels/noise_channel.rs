@@ -1,6 +1,7 @@
 use crate::channels::{envelope::Envelope, length_counter::LengthCounter};
 
 // https://wiki.nesdev.com/w/index.php/APU_Noise
+#[derive(Clone)]
 pub struct NoiseChannel {
     even_latch: bool,
     timer: u16,
@@ -1,6 +1,7 @@
 use crate::channels::{envelope::Envelope, length_counter::LengthCounter};
 
 // https://wiki.nesdev.com/w/index.php/APU_Pulse
+#[derive(Clone)]
 pub struct PulseChannel {
     is_pulse2: bool,
     even_latch: bool,
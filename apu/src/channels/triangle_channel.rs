@@ -1,6 +1,7 @@
 use crate::channels::length_counter::LengthCounter;
 
 // https://wiki.nesdev.com/w/index.php/APU_Triangle
+#[derive(Clone)]
 pub struct TriangleChannel {
     timer: u16,
     timer_period: u16,
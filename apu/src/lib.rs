@@ -11,6 +11,25 @@ use memory::Memory;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// Everything about the APU that changes as it runs, for save states.
+/// The output filters and samples that haven't been taken yet aren't included.
+#[derive(Clone)]
+pub struct APUState {
+    pulse1: PulseChannel,
+    pulse2: PulseChannel,
+    triangle: TriangleChannel,
+    noise: NoiseChannel,
+    dmc: DMCChannel,
+
+    frame_counter_cycle: u64,
+    frame_sequence_len: u8,
+    frame_sequence_step: u8,
+    bus_latch: u8,
+
+    irq_disable: bool,
+    frame_irq: bool,
+}
+
 // http://www.slack.net/~ant/nes-emu/apu_ref.txt
 pub struct APU {
     pulse1: PulseChannel,
@@ -98,6 +117,40 @@ impl APU {
         self.frame_irq = false;
     }
 
+    pub fn save_state(&self) -> APUState {
+        APUState {
+            pulse1: self.pulse1.clone(),
+            pulse2: self.pulse2.clone(),
+            triangle: self.triangle.clone(),
+            noise: self.noise.clone(),
+            dmc: self.dmc.clone(),
+
+            frame_counter_cycle: self.frame_counter_cycle,
+            frame_sequence_len: self.frame_sequence_len,
+            frame_sequence_step: self.frame_sequence_step,
+            bus_latch: self.bus_latch,
+
+            irq_disable: self.irq_disable,
+            frame_irq: self.frame_irq,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &APUState) {
+        self.pulse1 = state.pulse1.clone();
+        self.pulse2 = state.pulse2.clone();
+        self.triangle = state.triangle.clone();
+        self.noise = state.noise.clone();
+        self.dmc = state.dmc.clone();
+
+        self.frame_counter_cycle = state.frame_counter_cycle;
+        self.frame_sequence_len = state.frame_sequence_len;
+        self.frame_sequence_step = state.frame_sequence_step;
+        self.bus_latch = state.bus_latch;
+
+        self.irq_disable = state.irq_disable;
+        self.frame_irq = state.frame_irq;
+    }
+
     pub fn tick(&mut self) {
         // The APU is effectively clocked once per CPU cycle
         // The triangle is clocked by this signal directly
@@ -76,26 +76,32 @@ impl AddressingMode {
         }
 
         match self {
-            AddressingMode::ABS => format!(" = {:02X}", memory.peek(memory.peek_u16(pc + 1))),
-            AddressingMode::ZER => format!(" = {:02X}", memory.peek(memory.peek(pc + 1) as u16)),
+            AddressingMode::ABS => format!(
+                " = {:02X}",
+                memory.peek(memory.peek_u16(pc.wrapping_add(1)))
+            ),
+            AddressingMode::ZER => format!(
+                " = {:02X}",
+                memory.peek(memory.peek(pc.wrapping_add(1)) as u16)
+            ),
             AddressingMode::ZEX => {
-                let target = memory.peek(pc + 1).wrapping_add(x);
+                let target = memory.peek(pc.wrapping_add(1)).wrapping_add(x);
                 format!(" @ {:02X} = {:02X}", target, memory.peek(target as u16))
             }
             AddressingMode::ZEY => {
-                let target = memory.peek(pc + 1).wrapping_add(y);
+                let target = memory.peek(pc.wrapping_add(1)).wrapping_add(y);
                 format!(" @ {:02X} = {:02X}", target, memory.peek(target as u16))
             }
             AddressingMode::ABX => {
-                let target = memory.peek_u16(pc + 1).wrapping_add(x as u16);
+                let target = memory.peek_u16(pc.wrapping_add(1)).wrapping_add(x as u16);
                 format!(" @ {:04X} = {:02X}", target, memory.peek(target))
             }
             AddressingMode::ABY => {
-                let target = memory.peek_u16(pc + 1).wrapping_add(y as u16);
+                let target = memory.peek_u16(pc.wrapping_add(1)).wrapping_add(y as u16);
                 format!(" @ {:04X} = {:02X}", target, memory.peek(target))
             }
             AddressingMode::INX => {
-                let index = memory.peek(pc + 1).wrapping_add(x);
+                let index = memory.peek(pc.wrapping_add(1)).wrapping_add(x);
                 let lo = memory.peek(index as u16) as u16;
                 let hi = memory.peek(index.wrapping_add(1) as u16) as u16;
                 let target = (hi << 8) | lo;
@@ -107,7 +113,7 @@ impl AddressingMode {
                 )
             }
             AddressingMode::INY => {
-                let index = memory.peek(pc + 1);
+                let index = memory.peek(pc.wrapping_add(1));
                 let lo = memory.peek(index as u16) as u16;
                 let hi = memory.peek(index.wrapping_add(1) as u16) as u16;
                 let addr_base = (hi << 8) | lo;
@@ -120,7 +126,7 @@ impl AddressingMode {
                 )
             }
             AddressingMode::ABI => {
-                let addr = memory.peek_u16(pc + 1);
+                let addr = memory.peek_u16(pc.wrapping_add(1));
                 let hi = memory.peek(if addr & 0x00FF == 0x00FF {
                     addr & 0xFF00
                 } else {
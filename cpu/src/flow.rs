@@ -0,0 +1,81 @@
+/// The reason the CPU jumped to an interrupt handler
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interrupt {
+    Nmi,
+    Irq,
+    Brk,
+}
+
+/// A change in control flow that profilers and debuggers can follow. The stack pointer is
+/// the one from before a call pushes its return address, which a matching return restores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowEvent {
+    Call {
+        from: u16, // The address of the JSR
+        to: u16,
+        sp: u8,
+    },
+    Return {
+        sp: u8,
+    },
+    Interrupt {
+        interrupt: Interrupt,
+        from: u16, // The address the handler will return to
+        to: u16,
+        sp: u8,
+    },
+    InterruptReturn {
+        sp: u8,
+    },
+}
+
+/// A call or interrupt on the CPU's shadow call stack that hasn't returned yet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CallFrame {
+    pub interrupt: Option<Interrupt>, // None for a subroutine called with JSR
+    pub from: u16, // The address of the JSR, or the address the handler will return to
+    pub to: u16,
+    pub sp: u8, // The stack pointer that returning from this frame restores
+}
+
+impl CallFrame {
+    /// Update a call stack to follow a change in control flow
+    pub(crate) fn follow(stack: &mut Vec<CallFrame>, event: &FlowEvent) {
+        let (frame, sp) = match *event {
+            FlowEvent::Call { from, to, sp } => (
+                Some(CallFrame {
+                    interrupt: None,
+                    from,
+                    to,
+                    sp,
+                }),
+                sp,
+            ),
+            FlowEvent::Interrupt {
+                interrupt,
+                from,
+                to,
+                sp,
+            } => (
+                Some(CallFrame {
+                    interrupt: Some(interrupt),
+                    from,
+                    to,
+                    sp,
+                }),
+                sp,
+            ),
+            FlowEvent::Return { sp } | FlowEvent::InterruptReturn { sp } => (None, sp),
+        };
+
+        // Frames are matched by stack pointer rather than by count. A return pops every frame
+        // whose return address it skipped past, as when a routine pulls its own return address
+        // to exit to its caller's caller, but leaves frames alone if it returns through an
+        // address that was pushed by hand. A push drops frames whose return addresses it
+        // overwrites, as when the stack pointer is reset.
+        while stack.last().is_some_and(|top| top.sp <= sp) {
+            stack.pop();
+        }
+        stack.extend(frame);
+    }
+}
@@ -4,15 +4,21 @@ extern crate bitflags;
 extern crate lazy_static;
 
 mod addressing_mode;
+mod flow;
 mod instruction;
 mod status_register;
+mod trace;
 
 use addressing_mode::AddressingMode;
-use memory::Memory;
+pub use flow::{CallFrame, FlowEvent, Interrupt};
+use memory::{BusObserver, Memory};
 use status_register::StatusRegister;
+pub use trace::TraceEntry;
 
 use instruction::{Instruction, INSTRUCTIONS};
+use std::cell::RefCell;
 use std::ops;
+use std::rc::Rc;
 
 pub const NMI_VEC: u16 = 0xFFFA;
 pub const RST_VEC: u16 = 0xFFFC;
@@ -23,6 +29,41 @@ const STACK_INIT: u8 = 0xfd;
 
 const DECIMAL_ENABLED: bool = false;
 
+/// A copy of the programmer-visible registers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub s: u8,
+    pub pc: u16,
+}
+
+/// Everything about the CPU that changes as it runs, for save states
+#[derive(Clone, Debug)]
+pub struct CPUState {
+    registers: Registers,
+    wait_cycles: u32,
+    cycles: u64,
+    nmi_timer: u8,
+    instruction_pc: u16,
+    call_stack: Vec<CallFrame>,
+}
+
+/// Why the CPU stopped before executing an instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Halt {
+    Breakpoint(u16),
+    Interrupt(Interrupt), // Before the first instruction of the handler
+}
+
+/// Decides whether the CPU should halt where it would, e.g. by checking a breakpoint's condition,
+/// so that it carries straight on when the answer is no
+pub trait HaltFilter {
+    fn should_halt(&mut self, cpu: &CPU, halt: Halt) -> bool;
+}
+
 pub struct CPU {
     // Registers
     a: u8, // Accumulator
@@ -34,9 +75,21 @@ pub struct CPU {
     pc: u16, // Program counter
     wait_cycles: u32,
     cycles: u64,
-    pub log: bool,
+    tracing: bool,
+    recording_flow: bool,
+    flow_events: Vec<FlowEvent>,
+    call_stack: Vec<CallFrame>,
     pub nmi_timer: u8,
 
+    instruction_pc: u16, // The address of the last instruction to start executing
+    breakpoints: Vec<u16>,
+    halt_interrupts: Vec<Interrupt>,
+    pending_interrupt_halt: Option<Interrupt>,
+    halt: Option<Halt>,
+    halt_filter: Option<Rc<RefCell<dyn HaltFilter>>>,
+    halted: bool,   // Whether the last step halted rather than executing anything
+    resuming: bool, // Whether the next instruction is the one that was halted on
+
     memory: Box<dyn Memory>,
 }
 
@@ -51,8 +104,20 @@ impl CPU {
             pc: 0,
             wait_cycles: 0,
             cycles: 0,
-            log: false,
+            tracing: false,
+            recording_flow: false,
+            flow_events: Vec::new(),
+            call_stack: Vec::new(),
             nmi_timer: 0,
+
+            instruction_pc: 0,
+            breakpoints: Vec::new(),
+            halt_interrupts: Vec::new(),
+            pending_interrupt_halt: None,
+            halt: None,
+            halt_filter: None,
+            halted: false,
+            resuming: false,
             memory,
         }
     }
@@ -67,6 +132,9 @@ impl CPU {
         self.wait_cycles = 0;
         self.cycles = 7;
         self.nmi_timer = 0;
+        self.pending_interrupt_halt = None;
+        self.resuming = false;
+        self.call_stack.clear();
 
         self.pc = self.memory.read_u16(RST_VEC);
     }
@@ -75,31 +143,183 @@ impl CPU {
         self.wait_cycles += stall_cycles;
     }
 
-    pub fn tick(&mut self) -> Option<String> {
-        let log_option = if self.wait_cycles > 0 {
+    /// Enable or disable the creation of a TraceEntry before each instruction
+    pub fn set_tracing(&mut self, tracing: bool) {
+        self.tracing = tracing;
+    }
+
+    /// Enable or disable recording calls, returns and interrupts as FlowEvents
+    pub fn set_recording_flow(&mut self, recording_flow: bool) {
+        self.recording_flow = recording_flow;
+        self.flow_events.clear();
+    }
+
+    /// Take the FlowEvents recorded since the last call, oldest first
+    pub fn take_flow_events(&mut self) -> Vec<FlowEvent> {
+        std::mem::take(&mut self.flow_events)
+    }
+
+    /// The calls and interrupts that haven't returned yet, outermost first
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: self.p.bits(),
+            s: self.s,
+            pc: self.pc,
+        }
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.p = StatusRegister::from_bits_truncate(registers.p);
+        self.s = registers.s;
+        self.pc = registers.pc;
+    }
+
+    pub fn save_state(&self) -> CPUState {
+        CPUState {
+            registers: self.registers(),
+            wait_cycles: self.wait_cycles,
+            cycles: self.cycles,
+            nmi_timer: self.nmi_timer,
+            instruction_pc: self.instruction_pc,
+            call_stack: self.call_stack.clone(),
+        }
+    }
+
+    /// Restore a save state, dropping any halt or flow events from before it
+    pub fn load_state(&mut self, state: &CPUState) {
+        self.set_registers(state.registers);
+        self.wait_cycles = state.wait_cycles;
+        self.cycles = state.cycles;
+        self.nmi_timer = state.nmi_timer;
+        self.instruction_pc = state.instruction_pc;
+        self.call_stack = state.call_stack.clone();
+        self.flow_events.clear();
+        self.pending_interrupt_halt = None;
+        self.halt = None;
+        self.halted = false;
+        self.resuming = false;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn instruction_pc(&self) -> u16 {
+        self.instruction_pc
+    }
+
+    /// Decode the instruction at an address as if it were about to execute, or None for an unknown opcode
+    pub fn disassemble(&self, addr: u16) -> Option<TraceEntry> {
+        let op = INSTRUCTIONS.get(&self.memory.peek(addr))?;
+        Some(self.trace_entry(addr, op))
+    }
+
+    /// Whether the next tick will start an instruction, rather than finishing the last one
+    pub fn at_instruction_boundary(&self) -> bool {
+        self.wait_cycles == 0
+    }
+
+    /// Halt before executing the instruction at an address
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.push(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        if let Some(index) = self.breakpoints.iter().position(|&bp| bp == addr) {
+            self.breakpoints.remove(index);
+        }
+    }
+
+    /// Halt before the first instruction of an interrupt handler
+    pub fn set_halt_on_interrupt(&mut self, interrupt: Interrupt, halt: bool) {
+        self.halt_interrupts
+            .retain(|&existing| existing != interrupt);
+        if halt {
+            self.halt_interrupts.push(interrupt);
+        }
+    }
+
+    /// Check every halt with a filter first, or halt unconditionally if None
+    pub fn set_halt_filter(&mut self, filter: Option<Rc<RefCell<dyn HaltFilter>>>) {
+        self.halt_filter = filter;
+    }
+
+    /// Whether the last tick halted before an instruction, rather than running
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Take the reason for the last halt; ticking again resumes from the instruction halted on
+    pub fn take_halt(&mut self) -> Option<Halt> {
+        self.halt.take()
+    }
+
+    /// Run the instruction about to start without halting for a breakpoint on it, as after a halt,
+    /// so that stepping or continuing from a breakpoint's address moves past it
+    pub fn resume(&mut self) {
+        if self.at_instruction_boundary() {
+            self.resuming = true;
+        }
+    }
+
+    pub fn add_bus_observer(&mut self, observer: Rc<RefCell<dyn BusObserver>>) {
+        self.memory.add_observer(observer);
+    }
+
+    pub fn remove_bus_observer(&mut self, observer: &Rc<RefCell<dyn BusObserver>>) {
+        self.memory.remove_observer(observer);
+    }
+
+    pub fn tick(&mut self) -> Option<TraceEntry> {
+        let trace_option = if self.wait_cycles > 0 {
             self.wait_cycles -= 1;
             None
         } else {
-            self.step()
+            let trace_option = self.step();
+            if self.halted {
+                return None; // No time passes while halted
+            }
+            trace_option
         };
         self.cycles += 1;
-        log_option
+        trace_option
     }
 
-    pub fn step(&mut self) -> Option<String> {
+    pub fn step(&mut self) -> Option<TraceEntry> {
+        // Nothing moves on a step that halts, so stopping doesn't change when an NMI fires
+        self.halted = self.should_halt();
+        if self.halted {
+            return None;
+        }
+
         if self.nmi_timer > 0 {
             self.nmi_timer -= 1;
             if self.nmi_timer == 0 {
                 self.nmi();
+                self.halted = self.should_halt(); // Before the first instruction of the handler
+                if self.halted {
+                    return None;
+                }
             }
         }
+        self.instruction_pc = self.pc;
 
-        let opcode = self.memory.read(self.pc);
+        let opcode = self.memory.fetch(self.pc);
         let op = INSTRUCTIONS
             .get(&opcode)
             .expect(&format!("Unimplemented instruction: {:#02X}", opcode)[..]);
-        let log = if self.log {
-            Some(self.format_step(op))
+        let trace = if self.tracing {
+            Some(self.trace_entry(self.pc, op))
         } else {
             None
         };
@@ -119,10 +339,11 @@ impl CPU {
         }
         self.wait_cycles += op.cycles - 1;
 
-        log
+        trace
     }
 
     pub fn nmi(&mut self) {
+        let (from, sp) = (self.pc, self.s);
         self.stack_push_u16(self.pc);
         let mut status = self.p.clone();
         status.insert(StatusRegister::BREAK_HI);
@@ -130,6 +351,7 @@ impl CPU {
         self.stack_push(status.bits());
         self.p.insert(StatusRegister::IRQ_DISABLE);
         self.pc = self.memory.read_u16(NMI_VEC);
+        self.enter_interrupt(Interrupt::Nmi, from, sp);
     }
 
     pub fn irq(&mut self) {
@@ -137,6 +359,7 @@ impl CPU {
             return;
         }
 
+        let (from, sp) = (self.pc, self.s);
         self.stack_push_u16(self.pc);
         let mut status = self.p.clone();
         status.insert(StatusRegister::BREAK_HI);
@@ -144,46 +367,85 @@ impl CPU {
         self.stack_push(status.bits());
         self.p.insert(StatusRegister::IRQ_DISABLE);
         self.pc = self.memory.read_u16(IRQ_VEC);
+        self.enter_interrupt(Interrupt::Irq, from, sp);
+    }
+
+    fn enter_interrupt(&mut self, interrupt: Interrupt, from: u16, sp: u8) {
+        self.record_flow(FlowEvent::Interrupt {
+            interrupt,
+            from,
+            to: self.pc,
+            sp,
+        });
+        if self.halt_interrupts.contains(&interrupt) {
+            self.pending_interrupt_halt = Some(interrupt);
+        }
     }
 
-    fn format_step(&self, op: &Instruction) -> String {
-        format!("{:<48}{}", self.format_instr(op), self.format_state())
+    fn record_flow(&mut self, event: FlowEvent) {
+        CallFrame::follow(&mut self.call_stack, &event);
+        if self.recording_flow {
+            self.flow_events.push(event);
+        }
     }
 
-    fn format_instr(&self, op: &Instruction) -> String {
-        format!(
-            "{:04X}  {:02X} {} {} {:>4} {}{}",
-            self.pc,
-            self.memory.peek(self.pc),
-            if op.mode.operand_length() > 0 {
-                format!("{:02X}", self.memory.peek(self.pc + 1))
-            } else {
-                "  ".to_string()
-            },
-            if op.mode.operand_length() > 1 {
-                format!("{:02X}", self.memory.peek(self.pc + 2))
-            } else {
-                "  ".to_string()
+    fn should_halt(&mut self) -> bool {
+        let resuming = std::mem::take(&mut self.resuming);
+        if let Some(interrupt) = self.pending_interrupt_halt.take() {
+            if self.try_halt(Halt::Interrupt(interrupt)) {
+                return true;
+            }
+        } else if resuming {
+            return false;
+        }
+        self.breakpoints.contains(&self.pc) && self.try_halt(Halt::Breakpoint(self.pc))
+    }
+
+    /// Halt unless the filter turns it down
+    fn try_halt(&mut self, halt: Halt) -> bool {
+        if let Some(filter) = self.halt_filter.clone() {
+            if !filter.borrow_mut().should_halt(self, halt) {
+                return false;
+            }
+        }
+        self.halt = Some(halt);
+        self.resuming = true;
+        true
+    }
+
+    fn trace_entry(&self, addr: u16, op: &Instruction) -> TraceEntry {
+        let operand_length = op.mode.operand_length();
+        TraceEntry {
+            registers: Registers {
+                pc: addr,
+                ..self.registers()
             },
-            op.op_str,
-            op.mode.format(
-                self.pc,
-                match op.mode.operand_length() {
-                    1 => self.memory.peek(self.pc + 1) as u16,
-                    2 => self.memory.peek_u16(self.pc + 1),
+            cycles: self.cycles,
+            bytes: (0..=operand_length)
+                .map(|i| self.memory.peek(addr.wrapping_add(i)))
+                .collect(),
+            mnemonic: op.op_str,
+            operand: op.mode.format(
+                addr,
+                match operand_length {
+                    1 => self.memory.peek(addr.wrapping_add(1)) as u16,
+                    2 => self.memory.peek_u16(addr.wrapping_add(1)),
                     _ => 0x0,
-                }
+                },
             ),
-            op.mode
-                .format_data(self.pc, self.x, self.y, self.memory.as_ref())
-        )
+            annotation: op
+                .mode
+                .format_data(addr, self.x, self.y, self.memory.as_ref()),
+        }
     }
 
-    fn format_state(&self) -> String {
-        format!(
-            "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
-            self.a, self.x, self.y, self.p, self.s, self.cycles
-        )
+    /// Read the data an instruction operates on, which is part of the instruction itself in immediate mode
+    fn read_data(&mut self, addr: u16, mode: &AddressingMode) -> u8 {
+        if *mode == AddressingMode::IMM {
+            self.memory.fetch(addr)
+        } else {
+            self.memory.read(addr)
+        }
     }
 
     fn stack_push(&mut self, data: u8) {
@@ -212,22 +474,28 @@ impl CPU {
         use AddressingMode::*;
         match mode {
             IMM => (self.pc, false),
-            ABS => (self.memory.read_u16(self.pc), false),
-            ZER => (self.memory.read(self.pc) as u16, false),
-            ZEX => (self.memory.read(self.pc).wrapping_add(self.x) as u16, false),
-            ZEY => (self.memory.read(self.pc).wrapping_add(self.y) as u16, false),
+            ABS => (self.memory.fetch_u16(self.pc), false),
+            ZER => (self.memory.fetch(self.pc) as u16, false),
+            ZEX => (
+                self.memory.fetch(self.pc).wrapping_add(self.x) as u16,
+                false,
+            ),
+            ZEY => (
+                self.memory.fetch(self.pc).wrapping_add(self.y) as u16,
+                false,
+            ),
             ABX => {
-                let base = self.memory.read_u16(self.pc);
+                let base = self.memory.fetch_u16(self.pc);
                 let addr = base.wrapping_add(self.x as u16);
                 (addr, pages_differ(base, addr))
             }
             ABY => {
-                let base = self.memory.read_u16(self.pc);
+                let base = self.memory.fetch_u16(self.pc);
                 let addr = base.wrapping_add(self.y as u16);
                 (addr, pages_differ(base, addr))
             }
             REL => {
-                let offset = self.memory.read(self.pc) as i8;
+                let offset = self.memory.fetch(self.pc) as i8;
                 let dest = self.pc.wrapping_add(1).wrapping_add(offset as u16);
                 (
                     dest,
@@ -235,13 +503,13 @@ impl CPU {
                 )
             }
             INX => {
-                let index = self.memory.read(self.pc).wrapping_add(self.x);
+                let index = self.memory.fetch(self.pc).wrapping_add(self.x);
                 let lo = self.memory.read(index as u16) as u16;
                 let hi = self.memory.read(index.wrapping_add(1) as u16) as u16;
                 ((hi << 8) | lo, false)
             }
             INY => {
-                let index = self.memory.read(self.pc);
+                let index = self.memory.fetch(self.pc);
                 let lo = self.memory.read(index as u16) as u16;
                 let hi = self.memory.read(index.wrapping_add(1) as u16) as u16;
                 let addr_base = (hi << 8) | lo;
@@ -249,7 +517,7 @@ impl CPU {
                 (addr, pages_differ(addr_base, addr))
             }
             ABI => {
-                let addr = self.memory.read_u16(self.pc);
+                let addr = self.memory.fetch_u16(self.pc);
                 // 6502 indirect addressing bug at page boundaries
                 let hi = self.memory.read(if addr & 0x00FF == 0x00FF {
                     addr & 0xFF00
@@ -309,7 +577,10 @@ impl CPU {
             "ROL" => self.rotate_op(true, mode),
             "ROR" => self.rotate_op(false, mode),
             "RTI" => self.rti(),
-            "RTS" => self.pc = self.stack_pop_u16() + 1,
+            "RTS" => {
+                self.pc = self.stack_pop_u16() + 1;
+                self.record_flow(FlowEvent::Return { sp: self.s });
+            }
             "SBC" | "*SBC" => self.arithmetic_op(true, mode),
             "SEC" => self.p.insert(StatusRegister::CARRY),
             "SED" => self.p.insert(StatusRegister::DECIMAL),
@@ -364,7 +635,7 @@ impl CPU {
     /// Perform a binary logic operation (e.g. AND) between A and memory
     fn bit_op(&mut self, f: impl Fn(u8, u8) -> u8, mode: &AddressingMode) {
         let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         self.a = f(self.a, data);
         self.p.set(StatusRegister::ZERO, self.a == 0);
         self.p.set(StatusRegister::NEGATIVE, (self.a & 0x80) != 0);
@@ -385,7 +656,7 @@ impl CPU {
     /// Compare a register to memory, then set flags
     fn compare_op(&mut self, reg: u8, mode: &AddressingMode) {
         let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         let temp: i16 = reg as i16 - data as i16;
         self.p.set(StatusRegister::NEGATIVE, (temp & 0x80) != 0);
         self.p.set(StatusRegister::ZERO, temp == 0);
@@ -424,7 +695,7 @@ impl CPU {
     /// Load memory and return it to be put into a register
     fn load_op(&mut self, mode: &AddressingMode) -> u8 {
         let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         self.p.set(StatusRegister::NEGATIVE, (data & 0x80) != 0);
         self.p.set(StatusRegister::ZERO, data == 0);
         if page_cross {
@@ -456,7 +727,7 @@ impl CPU {
     /// Add/subtract data to/from A and set flags, possibly using decimal mode
     fn arithmetic_op(&mut self, subtract: bool, mode: &AddressingMode) {
         let (addr, page_cross) = self.get_operand_address(mode);
-        let mut data = self.memory.read(addr);
+        let mut data = self.read_data(addr, mode);
         if subtract {
             data = ((data as i8).wrapping_neg().wrapping_sub(1)) as u8
         }
@@ -550,10 +821,18 @@ impl CPU {
     }
 
     fn jump_op(&mut self, save_return: bool, mode: &AddressingMode) {
+        let (from, sp) = (self.pc - 1, self.s);
         if save_return {
             self.stack_push_u16(self.pc - 1 + 2);
         } // 2 ahead of opcode
         self.pc = self.get_operand_address(mode).0;
+        if save_return {
+            self.record_flow(FlowEvent::Call {
+                from,
+                to: self.pc,
+                sp,
+            });
+        }
     }
 
     // Unimplemented instructions that AND the high byte of the
@@ -595,10 +874,12 @@ impl CPU {
 
     /// Force a system interrupt
     fn brk(&mut self) {
+        let (from, sp) = (self.pc + 2, self.s);
         self.stack_push_u16(self.pc + 2);
         self.stack_push((self.p | StatusRegister::BREAK).bits());
         self.p.insert(StatusRegister::IRQ_DISABLE);
         self.pc = self.memory.read_u16(IRQ_VEC);
+        self.enter_interrupt(Interrupt::Brk, from, sp);
     }
 
     /// Pop from stack, update the value of A, and set flags
@@ -613,6 +894,7 @@ impl CPU {
         let val = self.stack_pop();
         self.p.set_from_stack(val);
         self.pc = self.stack_pop_u16();
+        self.record_flow(FlowEvent::InterruptReturn { sp: self.s });
     }
 
     /// Unofficial: Execute AND imm, setting flags slightly differently
@@ -627,7 +909,7 @@ impl CPU {
     /// Unofficial: Like AND followed by ROR, but setting flags in a different way to ROR
     fn arr(&mut self, mode: &AddressingMode) {
         let (addr, _) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         self.a &= data;
         self.execute_op("ROR", &AddressingMode::ACC);
         self.p.set(StatusRegister::NEGATIVE, (self.a & 0x80) != 0);
@@ -642,7 +924,7 @@ impl CPU {
     /// Unofficial: Set X = (A & X) - operand, setting flags
     fn axs(&mut self, mode: &AddressingMode) {
         let (addr, _) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         let a_and_x = self.a & self.x;
         self.x = a_and_x.wrapping_sub(data);
         self.p.set(StatusRegister::CARRY, data <= a_and_x);
@@ -669,7 +951,7 @@ impl CPU {
     /// Unofficial: http://visual6502.org/wiki/index.php?title=6502_Opcode_8B_%28XAA,_ANE%29
     fn xaa(&mut self, mode: &AddressingMode) {
         let (addr, _) = self.get_operand_address(mode);
-        let data = self.memory.read(addr);
+        let data = self.read_data(addr, mode);
         self.a = self.a & self.x & data;
         self.p.set(StatusRegister::NEGATIVE, (self.a & 0x80) != 0);
         self.p.set(StatusRegister::ZERO, self.a == 0);
@@ -688,6 +970,10 @@ impl Memory for CPU {
     fn write(&mut self, addr: u16, data: u8) {
         self.memory.write(addr, data);
     }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.memory.fetch(addr)
+    }
 }
 
 /// Translates a binary integer to a "Binary Coded Decimal"
@@ -0,0 +1,62 @@
+use crate::Registers;
+use std::fmt;
+
+/// The state of the CPU just before it executes an instruction
+pub struct TraceEntry {
+    pub registers: Registers,
+    pub cycles: u64,
+    pub bytes: Vec<u8>, // The opcode followed by its operands
+    pub mnemonic: &'static str,
+    pub operand: String,
+    pub annotation: String, // Addresses and data touched by the operand, e.g. " @ 0300 = 1F"
+}
+
+impl TraceEntry {
+    /// The instruction as it is written in assembly, e.g. "LDA $0300,X"
+    pub fn disassembly(&self) -> String {
+        format!("{} {}", self.mnemonic, self.operand)
+            .trim_end()
+            .to_string()
+    }
+
+    /// The opcode and operand bytes in hex, padded to the width of three bytes
+    pub fn format_bytes(&self) -> String {
+        (0..3)
+            .map(|i| match self.bytes.get(i) {
+                Some(byte) => format!("{:02X}", byte),
+                None => "  ".to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The left half of a nestest-style log line, e.g. "C000  4C F5 C5  JMP $C5F5"
+    pub fn format_instruction(&self) -> String {
+        format!(
+            "{:04X}  {} {:>4} {}{}",
+            self.registers.pc,
+            self.format_bytes(),
+            self.mnemonic,
+            self.operand,
+            self.annotation
+        )
+    }
+}
+
+// https://www.qmtpro.com/~nes/misc/nestest.log (without the PPU column)
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let regs = &self.registers;
+        write!(
+            f,
+            "{:<48}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+            self.format_instruction(),
+            regs.a,
+            regs.x,
+            regs.y,
+            regs.p,
+            regs.s,
+            self.cycles
+        )
+    }
+}
@@ -0,0 +1,433 @@
+use nes::debugger::{Breakpoint, BreakpointHit, Interrupt};
+use nes::trace::format_flags;
+use nes::NES;
+
+pub const HELP: &str = "\
+step (s) [count]            execute instructions
+next (n)                    step, running subroutine calls until they return
+out (o)                     run until the current subroutine or interrupt handler returns
+sl                          step to the next source line, following calls
+nl                          step to the next source line, running subroutine calls
+to <addr>                   run until the instruction at an address
+continue (c) [frames]       run until a breakpoint, or for a number of frames
+stack (bt)                  list the calls and interrupts being run, innermost first
+regs (r) [reg=value ...]    show or set A, X, Y, P, S and PC
+mem (m) <addr> [length]     dump CPU memory
+vmem (vm) <addr> [length]   dump PPU memory
+poke <addr> <byte ...>      write CPU memory
+vpoke <addr> <byte ...>     write PPU memory
+dis (d) [addr] [count]      disassemble, from PC by default
+break (b) <addr>            break before executing an address
+watch [r|w|rw] <addr>[-end] break after the CPU accesses memory
+vwatch [r|w|rw] <addr>[-end] break after the CPU accesses PPU memory through PPUDATA
+catch nmi|irq|brk           break before the first instruction of an interrupt handler
+cond <id> [expression]      only break when an expression is true, e.g. a == $FF && [$0300] > 3
+breaks (bl)                 list breakpoints
+delete (bd) <id>            remove a breakpoint
+reset                       reset the NES
+quit (q)
+Addresses and bytes are in hex, or labels from loaded symbols, and counts are in decimal.
+next, out, to and continue without a frame count can't be interrupted, and only return
+once they get where they're going or a breakpoint hits.";
+
+const DEFAULT_DUMP_LENGTH: usize = 64;
+const DEFAULT_DISASSEMBLY_COUNT: usize = 10;
+
+/// A monitor-style command interpreter that drives an NES without a GUI
+pub struct Console {
+    nes: NES,
+    quit: bool,
+}
+
+impl Console {
+    /// Take control of an NES, which stays paused except while commands run it
+    pub fn new(mut nes: NES) -> Self {
+        nes.paused = true;
+        Self { nes, quit: false }
+    }
+
+    pub fn nes(&self) -> &NES {
+        &self.nes
+    }
+
+    pub fn nes_mut(&mut self) -> &mut NES {
+        &mut self.nes
+    }
+
+    /// Whether the quit command has run
+    pub fn is_finished(&self) -> bool {
+        self.quit
+    }
+
+    /// Run one line of input, returning what it printed
+    pub fn execute(&mut self, line: &str) -> Result<String, &'static str> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(String::new()),
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "s" | "step" => {
+                let count = parse_count(args.first(), 1)?;
+                let mut hit = None;
+                for _ in 0..count {
+                    self.nes.step_instruction();
+                    hit = self.nes.take_break_hit();
+                    if hit.is_some() {
+                        break;
+                    }
+                }
+                Ok(self.report(hit))
+            }
+            "n" | "next" => {
+                self.nes.step_over();
+                Ok(self.stop_report())
+            }
+            "o" | "out" => {
+                self.nes.step_out();
+                Ok(self.stop_report())
+            }
+            "sl" => {
+                self.nes.step_line();
+                Ok(self.stop_report())
+            }
+            "nl" => {
+                self.nes.step_line_over();
+                Ok(self.stop_report())
+            }
+            "to" => {
+                let addr = self.parse_addr(args.first().ok_or("missing address")?)?;
+                self.nes.run_to(addr);
+                Ok(self.stop_report())
+            }
+            "c" | "continue" => {
+                let frames = match args.first() {
+                    Some(frames) => Some(
+                        frames
+                            .parse::<u64>()
+                            .ok()
+                            .filter(|&frames| frames > 0)
+                            .ok_or("invalid count")?,
+                    ),
+                    None => None,
+                };
+                self.run(frames);
+                Ok(self.stop_report())
+            }
+            "bt" | "stack" => Ok(self
+                .nes
+                .call_stack()
+                .iter()
+                .rev()
+                .map(|frame| {
+                    let kind = match frame.interrupt {
+                        None => "JSR",
+                        Some(Interrupt::Nmi) => "NMI",
+                        Some(Interrupt::Irq) => "IRQ",
+                        Some(Interrupt::Brk) => "BRK",
+                    };
+                    format!("{} ${:04X} from ${:04X}\n", kind, frame.to, frame.from)
+                })
+                .collect()),
+            "r" | "regs" => {
+                for arg in args {
+                    self.set_register(arg)?;
+                }
+                Ok(self.format_registers())
+            }
+            "m" | "mem" | "vm" | "vmem" => {
+                let addr = self.parse_addr(args.first().ok_or("missing address")?)?;
+                let length = parse_count(args.get(1), DEFAULT_DUMP_LENGTH)?;
+                Ok(self.dump(addr, length, command.starts_with('v')))
+            }
+            "poke" | "vpoke" => {
+                let addr = self.parse_addr(args.first().ok_or("missing address")?)?;
+                if args.len() < 2 {
+                    return Err("missing data");
+                }
+                for (i, byte) in args[1..].iter().enumerate() {
+                    let byte = parse_byte(byte)?;
+                    let addr = addr.wrapping_add(i as u16);
+                    if command == "vpoke" {
+                        self.nes.write_ppu_memory(addr, byte);
+                    } else {
+                        self.nes.write_cpu_memory(addr, byte);
+                    }
+                }
+                Ok(String::new())
+            }
+            "d" | "dis" => {
+                let addr = match args.first() {
+                    Some(addr) => self.parse_addr(addr)?,
+                    None => self.nes.cpu_registers().pc,
+                };
+                let count = parse_count(args.get(1), DEFAULT_DISASSEMBLY_COUNT)?;
+                Ok(self.disassemble(addr, count))
+            }
+            "b" | "break" => {
+                let addr = self.parse_addr(args.first().ok_or("missing address")?)?;
+                Ok(self.add_breakpoint(Breakpoint::Execute(addr)))
+            }
+            "watch" | "vwatch" => {
+                let (access, range) = match args.as_slice() {
+                    [range] => ("w", *range),
+                    [access, range] => (*access, *range),
+                    _ => return Err("expected an access type and address range"),
+                };
+                let (start, end) = self.parse_range(range)?;
+                let vram = command == "vwatch";
+                let breakpoints = match (access, vram) {
+                    ("r", false) => vec![Breakpoint::Read(start, end)],
+                    ("w", false) => vec![Breakpoint::Write(start, end)],
+                    ("rw", false) => {
+                        vec![Breakpoint::Read(start, end), Breakpoint::Write(start, end)]
+                    }
+                    ("r", true) => vec![Breakpoint::VramRead(start, end)],
+                    ("w", true) => vec![Breakpoint::VramWrite(start, end)],
+                    ("rw", true) => vec![
+                        Breakpoint::VramRead(start, end),
+                        Breakpoint::VramWrite(start, end),
+                    ],
+                    _ => return Err("access type must be r, w or rw"),
+                };
+                Ok(breakpoints
+                    .into_iter()
+                    .map(|breakpoint| self.add_breakpoint(breakpoint))
+                    .collect())
+            }
+            "catch" => {
+                let interrupt = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+                    Some("nmi") => Interrupt::Nmi,
+                    Some("irq") => Interrupt::Irq,
+                    Some("brk") => Interrupt::Brk,
+                    _ => return Err("expected nmi, irq or brk"),
+                };
+                Ok(self.add_breakpoint(Breakpoint::Interrupt(interrupt)))
+            }
+            "bl" | "breaks" => Ok(self
+                .nes
+                .breakpoints()
+                .iter()
+                .map(|entry| {
+                    let mut line = format!("{}: {}", entry.id, entry.breakpoint);
+                    if let Some(condition) = &entry.condition {
+                        line += &format!(" if {}", condition);
+                    }
+                    format!("{}, hit {} times\n", line, entry.hits)
+                })
+                .collect()),
+            "cond" => {
+                let id = args
+                    .first()
+                    .and_then(|id| id.parse().ok())
+                    .ok_or("expected a breakpoint ID")?;
+                // The rest of the line is the condition, which may contain spaces
+                let after_command = line.trim_start()[command.len()..].trim_start();
+                let source = after_command[args[0].len()..].trim();
+                let condition = if source.is_empty() {
+                    None
+                } else {
+                    Some(source.parse()?)
+                };
+                if self.nes.set_breakpoint_condition(id, condition) {
+                    Ok(String::new())
+                } else {
+                    Err("no breakpoint has that ID")
+                }
+            }
+            "bd" | "delete" => {
+                let id = args
+                    .first()
+                    .and_then(|id| id.parse().ok())
+                    .ok_or("expected a breakpoint ID")?;
+                if self.nes.remove_breakpoint(id) {
+                    Ok(String::new())
+                } else {
+                    Err("no breakpoint has that ID")
+                }
+            }
+            "reset" => {
+                self.nes.reset();
+                Ok(self.stop_report())
+            }
+            "h" | "help" => Ok(format!("{}\n", HELP)),
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            _ => Err("unknown command, try help"),
+        }
+    }
+
+    /// Run until a breakpoint, or for a number of frames (at least 1) if given
+    fn run(&mut self, frames: Option<u64>) {
+        self.nes.take_break_hit();
+        self.nes.resume();
+        let mut frames_left = frames;
+        while !self.nes.paused {
+            self.nes.tick();
+            if self.nes.get_new_frame().is_some() {
+                if let Some(frames_left) = &mut frames_left {
+                    *frames_left -= 1;
+                    if *frames_left == 0 {
+                        // Finish the instruction in progress, so that commands see a consistent state
+                        self.nes.step_instruction();
+                        break;
+                    }
+                }
+            }
+        }
+        self.nes.paused = true;
+    }
+
+    fn stop_report(&mut self) -> String {
+        let hit = self.nes.take_break_hit();
+        self.report(hit)
+    }
+
+    /// Report the breakpoint that stopped the NES, if any, then the source line and instruction it stopped before
+    fn report(&self, hit: Option<BreakpointHit>) -> String {
+        let mut report = String::new();
+        if let Some(hit) = hit {
+            report += &format!("{}\n", hit);
+        }
+        let pc = self.nes.cpu_registers().pc;
+        if let Some(line) = self.nes.source_line_at(pc) {
+            report += &format!("{}:{}\n", line.file, line.line);
+        }
+        report += &self.disassemble(pc, 1);
+        report
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> String {
+        let id = self.nes.add_breakpoint(breakpoint);
+        format!("{}: {}\n", id, breakpoint)
+    }
+
+    fn set_register(&mut self, assignment: &str) -> Result<(), &'static str> {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or("expected register=value")?;
+        let mut registers = self.nes.cpu_registers();
+        match &name.to_lowercase()[..] {
+            "a" => registers.a = parse_byte(value)?,
+            "x" => registers.x = parse_byte(value)?,
+            "y" => registers.y = parse_byte(value)?,
+            "p" => registers.p = parse_byte(value)?,
+            "s" | "sp" => registers.s = parse_byte(value)?,
+            "pc" => registers.pc = self.parse_addr(value)?,
+            _ => return Err("unknown register"),
+        }
+        self.nes.set_cpu_registers(registers);
+        Ok(())
+    }
+
+    fn format_registers(&self) -> String {
+        let registers = self.nes.cpu_registers();
+        format!(
+            "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} ({}) SP:{:02X} PC:{:04X}\n",
+            registers.a,
+            registers.x,
+            registers.y,
+            registers.p,
+            format_flags(registers.p),
+            registers.s,
+            registers.pc
+        )
+    }
+
+    /// Hex dump memory in rows of 16 bytes, e.g. "0200: 00 01 02 ..."
+    fn dump(&self, addr: u16, length: usize, vram: bool) -> String {
+        let mut out = String::new();
+        for row in (0..length).step_by(16) {
+            let row_addr = addr.wrapping_add(row as u16);
+            out += &format!("{:04X}:", row_addr);
+            for i in row..(row + 16).min(length) {
+                let addr = addr.wrapping_add(i as u16);
+                let byte = if vram {
+                    self.nes.peek_ppu_memory(addr)
+                } else {
+                    self.nes.peek_cpu_memory(addr)
+                };
+                out += &format!(" {:02X}", byte);
+            }
+            out += "\n";
+        }
+        out
+    }
+
+    /// List instructions from an address under their labels, marking the one at PC
+    fn disassemble(&self, addr: u16, count: usize) -> String {
+        let pc = self.nes.cpu_registers().pc;
+        let mut out = String::new();
+        let mut addr = addr;
+        for _ in 0..count {
+            let marker = if addr == pc { ">" } else { " " };
+            if let Some(label) = self.nes.label_at(addr) {
+                out += &format!("{}:\n", label);
+            }
+            match self.nes.disassemble(addr) {
+                Some(entry) => {
+                    out += &format!("{} {}\n", marker, entry.format_instruction().trim_end());
+                    addr = addr.wrapping_add(entry.bytes.len() as u16);
+                }
+                None => {
+                    out += &format!(
+                        "{} {:04X}  {:02X}        ???\n",
+                        marker,
+                        addr,
+                        self.nes.peek_cpu_memory(addr)
+                    );
+                    addr = addr.wrapping_add(1);
+                }
+            }
+        }
+        out
+    }
+    /// Parse a label, or an address in hex
+    fn parse_addr(&self, s: &str) -> Result<u16, &'static str> {
+        match self.nes.address_of_label(s) {
+            Some(addr) => Ok(addr),
+            None => parse_hex(s).map_err(|_| "unknown label or invalid address"),
+        }
+    }
+
+    /// Parse an address or an inclusive range like "2000-2007"
+    fn parse_range(&self, s: &str) -> Result<(u16, u16), &'static str> {
+        match s.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (self.parse_addr(start)?, self.parse_addr(end)?);
+                if end < start {
+                    return Err("range ends before it starts");
+                }
+                Ok((start, end))
+            }
+            None => {
+                let addr = self.parse_addr(s)?;
+                Ok((addr, addr))
+            }
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Result<u16, &'static str> {
+    let digits = s.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| "invalid hex number")
+}
+
+fn parse_byte(s: &str) -> Result<u8, &'static str> {
+    let value = parse_hex(s)?;
+    if value > 0xFF {
+        return Err("value doesn't fit in a byte");
+    }
+    Ok(value as u8)
+}
+
+fn parse_count(arg: Option<&&str>, default: usize) -> Result<usize, &'static str> {
+    match arg {
+        Some(count) => count.parse().map_err(|_| "invalid count"),
+        None => Ok(default),
+    }
+}
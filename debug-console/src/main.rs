@@ -0,0 +1,99 @@
+use debug_console::Console;
+use nes::symbols::SymbolTable;
+use nes::NES;
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "usage: {} [--pc <hex address>] [--symbols <.dbg or .nl file> ...] <NES ROM file>",
+        program
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut rom_path = None;
+    let mut start_pc = None;
+    let mut symbol_paths = Vec::new();
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match &arg[..] {
+            "--pc" => {
+                let pc = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                let pc = pc.trim_start_matches('$');
+                start_pc = Some(u16::from_str_radix(pc, 16).unwrap_or_else(|_| usage(&args[0])));
+            }
+            "--symbols" => {
+                symbol_paths.push(arg_iter.next().unwrap_or_else(|| usage(&args[0])));
+            }
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => usage(&args[0]),
+        }
+    }
+    let rom_path = rom_path.unwrap_or_else(|| usage(&args[0]));
+
+    let file = File::open(rom_path).unwrap_or_else(|err| {
+        eprintln!("failed to read file: {}", err);
+        process::exit(1);
+    });
+
+    let mut nes = NES::new();
+    nes.load_rom(file).unwrap_or_else(|err| {
+        eprintln!("failed to load ROM: {}", err);
+        process::exit(1);
+    });
+    if !symbol_paths.is_empty() {
+        let mut symbols = SymbolTable::new();
+        for path in symbol_paths {
+            symbols.load_file(Path::new(path)).unwrap_or_else(|err| {
+                eprintln!("failed to load symbols from {}: {}", path, err);
+                process::exit(1);
+            });
+        }
+        nes.set_symbols(Some(symbols));
+    }
+    if let Some(pc) = start_pc {
+        let mut registers = nes.cpu_registers();
+        registers.pc = pc;
+        nes.set_cpu_registers(registers);
+    }
+
+    let mut console = Console::new(nes);
+    print!("{}", console.execute("dis").unwrap());
+
+    let stdin = io::stdin();
+    let mut last_line = String::new();
+    while !console.is_finished() {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break, // End of input
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("failed to read input: {}", err);
+                process::exit(1);
+            }
+        }
+        // Like most monitors, an empty line repeats the last command, to make stepping quicker
+        if line.trim().is_empty() {
+            line = last_line.clone();
+        } else {
+            last_line = line.clone();
+        }
+
+        match console.execute(&line) {
+            Ok(output) => print!("{}", output),
+            Err(err) => println!("error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use debug_console::Console;
+use nes::NES;
+
+use std::fs::File;
+use std::path::PathBuf;
+
+fn nestest_console() -> Console {
+    let nestest_path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "..",
+        "nes",
+        "resources",
+        "nestest.nes",
+    ]
+    .iter()
+    .collect();
+    let mut nes = NES::new();
+    nes.load_rom(File::open(nestest_path).unwrap()).unwrap();
+    let mut console = Console::new(nes);
+    console.execute("r pc=C000").unwrap();
+    console
+}
+
+#[test]
+fn console_nestest() {
+    let mut console = nestest_console();
+    assert_eq!(
+        console.execute("r").unwrap(),
+        "A:00 X:00 Y:00 P:24 (nvUbdIzc) SP:FD PC:C000\n"
+    );
+    assert!(console
+        .execute("d C000 1")
+        .unwrap()
+        .starts_with("> C000  4C F5 C5  JMP $C5F5"));
+
+    // C5FD  20 2D C7  JSR $C72D, which returns to C600
+    console.execute("to C5FD").unwrap();
+    assert!(console.execute("n").unwrap().starts_with("> C600"));
+    assert_eq!(console.nes().cpu_registers().s, 0xFD);
+
+    // Break inside the subroutine, then run back out of it
+    console.execute("b C72D").unwrap();
+    console.execute("r pc=C5FD").unwrap();
+    assert!(console
+        .execute("c")
+        .unwrap()
+        .starts_with("breakpoint 1 (execute $C72D) hit at $C72D"));
+    assert_eq!(console.execute("bt").unwrap(), "JSR $C72D from $C5FD\n");
+    console.execute("bd 1").unwrap();
+    assert!(console.execute("o").unwrap().starts_with("> C600"));
+
+    console.execute("poke 200 12 34").unwrap();
+    assert_eq!(console.execute("m 200 2").unwrap(), "0200: 12 34\n");
+    console.execute("vpoke 2000 56").unwrap();
+    assert_eq!(console.execute("vm 2000 1").unwrap(), "2000: 56\n");
+
+    // Zero frames would never finish
+    assert!(console.execute("c 0").is_err());
+    console.execute("c 1").unwrap();
+    assert_eq!(console.nes().scanline(), 241);
+
+    assert!(console.execute("bogus").is_err());
+    console.execute("q").unwrap();
+    assert!(console.is_finished());
+}
+
+#[test]
+fn console_step_across_breakpoint() {
+    let mut console = nestest_console();
+    console.execute("b C5F7").unwrap();
+
+    // C000 jumps to C5F5, which lands on the breakpoint, and the third step runs past it
+    assert!(console.execute("s 3").unwrap().starts_with("> C5F9"));
+
+    // Each command lets through the breakpoint it starts on, but not the next time around
+    console.execute("r pc=C5F5").unwrap();
+    assert!(console.execute("s").unwrap().starts_with("> C5F7"));
+    assert!(console.execute("to C5F9").unwrap().starts_with("> C5F9"));
+    console.execute("r pc=C5F7").unwrap();
+    assert!(console.execute("n").unwrap().starts_with("> C5F9"));
+    console.execute("r pc=C5F5").unwrap();
+    assert!(console
+        .execute("c")
+        .unwrap()
+        .starts_with("breakpoint 1 (execute $C5F7) hit at $C5F7"));
+    console.execute("r pc=C5F5").unwrap();
+    console.execute("s").unwrap();
+    assert!(!console.execute("c 1").unwrap().starts_with("breakpoint"));
+}
@@ -2,11 +2,30 @@ pub mod mmu;
 pub mod ram;
 pub mod rom;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Watches the reads and writes made through a bus, e.g. for breakpoints
+pub trait BusObserver {
+    fn on_read(&mut self, addr: u16, data: u8);
+    fn on_write(&mut self, addr: u16, data: u8);
+}
+
 pub trait Memory {
     fn read(&mut self, addr: u16) -> u8;
     fn peek(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, data: u8);
 
+    /// Read a byte that is being executed or drawn, rather than used as data,
+    /// which only matters to memory that records how it is used
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    /// Notify an observer of each read and write, if this memory is a bus that supports it
+    fn add_observer(&mut self, _observer: Rc<RefCell<dyn BusObserver>>) {}
+    fn remove_observer(&mut self, _observer: &Rc<RefCell<dyn BusObserver>>) {}
+
     fn read_u16(&mut self, addr: u16) -> u16 {
         let lo = self.read(addr) as u16;
         let hi = self.read(addr + 1) as u16;
@@ -19,6 +38,12 @@ pub trait Memory {
         (hi << 8) | lo
     }
 
+    fn fetch_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.fetch(addr) as u16;
+        let hi = self.fetch(addr + 1) as u16;
+        (hi << 8) | lo
+    }
+
     fn write_u16(&mut self, addr: u16, data: u16) {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xff) as u8;
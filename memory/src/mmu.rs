@@ -1,5 +1,5 @@
 use crate::ram::RAM;
-use crate::Memory;
+use crate::{BusObserver, Memory};
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -23,11 +23,15 @@ impl MapRange {
 
 pub struct MMU {
     ranges: Vec<MapRange>,
+    observers: Vec<Rc<RefCell<dyn BusObserver>>>,
 }
 
 impl MMU {
     pub fn new() -> Self {
-        Self { ranges: Vec::new() }
+        Self {
+            ranges: Vec::new(),
+            observers: Vec::new(),
+        }
     }
 
     pub fn map_mirrored(
@@ -73,7 +77,12 @@ impl MMU {
 impl Memory for MMU {
     fn read(&mut self, addr: u16) -> u8 {
         if let Ok(result) = self.access(addr) {
-            result.1.borrow_mut().read(result.0)
+            let data = result.1.borrow_mut().read(result.0);
+            // Observers see the address after mirroring, so e.g. $2008 is reported as $2000
+            for observer in &self.observers {
+                observer.borrow_mut().on_read(result.0, data);
+            }
+            data
         } else {
             0
         }
@@ -89,7 +98,27 @@ impl Memory for MMU {
 
     fn write(&mut self, addr: u16, data: u8) {
         if let Ok(result) = self.access(addr) {
-            result.1.borrow_mut().write(result.0, data)
+            result.1.borrow_mut().write(result.0, data);
+            for observer in &self.observers {
+                observer.borrow_mut().on_write(result.0, data);
+            }
         }
     }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        if let Ok(result) = self.access(addr) {
+            result.1.borrow_mut().fetch(result.0)
+        } else {
+            0
+        }
+    }
+
+    fn add_observer(&mut self, observer: Rc<RefCell<dyn BusObserver>>) {
+        self.observers.push(observer);
+    }
+
+    fn remove_observer(&mut self, observer: &Rc<RefCell<dyn BusObserver>>) {
+        self.observers
+            .retain(|existing| !Rc::ptr_eq(existing, observer));
+    }
 }
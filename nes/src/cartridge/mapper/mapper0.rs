@@ -1,28 +1,60 @@
 use crate::cartridge::Mapper;
 use memory::Memory;
+use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/NROM
+#[derive(Clone)]
 pub struct Mapper0 {
     n_prg_banks: u16,
-    prg_rom: Vec<u8>,
-    chr_mem: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
+    chr_mem: Rc<Vec<u8>>,
     chr_mem_is_ram: bool,
 }
 
-impl Mapper for Mapper0 {}
+impl Mapper for Mapper0 {
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr >= 0x8000 {
+            Some((addr as usize - 0x8000) % (self.n_prg_banks as usize * 0x4000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr <= 0x1FFF && !self.chr_mem_is_ram {
+            Some(addr as usize % self.chr_mem.len())
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_mem
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        match self.prg_rom_offset(addr) {
+            Some(offset) => {
+                Rc::make_mut(&mut self.prg_rom)[offset] = data;
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 impl Mapper0 {
     pub fn new(n_prg_banks: u16, n_chr_banks: u16, prg_data: Vec<u8>, chr_data: Vec<u8>) -> Self {
         Self {
             n_prg_banks,
-            chr_mem: if n_chr_banks == 0 {
+            chr_mem: Rc::new(if n_chr_banks == 0 {
                 // TODO: RAM sizes
                 vec![0; 0x2000]
             } else {
                 chr_data
-            },
+            }),
             chr_mem_is_ram: n_chr_banks == 0,
-            prg_rom: prg_data,
+            prg_rom: Rc::new(prg_data),
         }
     }
 }
@@ -35,8 +67,8 @@ impl Memory for Mapper0 {
     fn peek(&self, addr: u16) -> u8 {
         if addr <= 0x1FFF {
             self.chr_mem[addr as usize % self.chr_mem.len()]
-        } else if addr >= 0x8000 {
-            self.prg_rom[((addr as usize - 0x8000) % (self.n_prg_banks as usize * 0x4000))]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0
         }
@@ -46,10 +78,11 @@ impl Memory for Mapper0 {
         if addr <= 0x1FFF {
             if self.chr_mem_is_ram {
                 let len = self.chr_mem.len();
-                self.chr_mem[addr as usize % len] = data;
+                Rc::make_mut(&mut self.chr_mem)[addr as usize % len] = data;
             }
         } else if addr >= 0x8000 {
-            self.prg_rom[((addr as usize - 0x8000) % (self.n_prg_banks as usize * 0x4000))] = data;
+            let offset = (addr as usize - 0x8000) % (self.n_prg_banks as usize * 0x4000);
+            Rc::make_mut(&mut self.prg_rom)[offset] = data;
         }
     }
 }
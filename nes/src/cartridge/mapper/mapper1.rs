@@ -1,13 +1,15 @@
 use crate::cartridge::Mapper;
 use crate::cartridge::Mirroring;
 use memory::Memory;
+use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/MMC1
+#[derive(Clone)]
 pub struct Mapper1 {
     n_prg_banks: u16,
-    prg_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
     prg_ram: Vec<u8>,
-    chr_mem: Vec<u8>,
+    chr_mem: Rc<Vec<u8>>,
     chr_mem_is_ram: bool,
 
     last_write_timer: u8,
@@ -23,14 +25,14 @@ impl Mapper1 {
     pub fn new(n_prg_banks: u16, n_chr_banks: u16, prg_data: Vec<u8>, chr_data: Vec<u8>) -> Self {
         Self {
             n_prg_banks,
-            prg_rom: prg_data,
+            prg_rom: Rc::new(prg_data),
             prg_ram: vec![0; 0x2000],
-            chr_mem: if n_chr_banks == 0 {
+            chr_mem: Rc::new(if n_chr_banks == 0 {
                 // TODO: RAM sizes
                 vec![0; 0x2000]
             } else {
                 chr_data
-            },
+            }),
             chr_mem_is_ram: n_chr_banks == 0,
 
             last_write_timer: 0,
@@ -42,6 +44,23 @@ impl Mapper1 {
             prg_bank: 0b10000,
         }
     }
+
+    fn map_chr(&self, addr: u16) -> usize {
+        let chr_bank_mode = self.control_register >> 4;
+        let base = if addr <= 0x0FFF {
+            if chr_bank_mode == 0 {
+                0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
+            } else {
+                0x1000 * (self.chr_bank_0 as usize)
+            }
+        } else if chr_bank_mode == 0 {
+            0x1000 + 0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
+        } else {
+            0x1000 * (self.chr_bank_1 as usize)
+        };
+
+        (base + ((addr as usize) % 0x1000)) % self.chr_mem.len()
+    }
 }
 
 impl Mapper for Mapper1 {
@@ -54,6 +73,57 @@ impl Mapper for Mapper1 {
         })
     }
 
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        let prg_bank_mode = (self.control_register & 0b1100) >> 2;
+        let len = self.prg_rom.len();
+        if 0x8000 <= addr && addr <= 0xBFFF {
+            let base = if prg_bank_mode == 0 || prg_bank_mode == 1 {
+                0x8000 * ((self.prg_bank as usize) & 0b1110)
+            } else if prg_bank_mode == 2 {
+                0x0000
+            } else {
+                0x4000 * ((self.prg_bank as usize) & 0b1111)
+            };
+
+            Some((base + ((addr as usize) - 0x8000)) % len)
+        } else if 0xC000 <= addr {
+            let base = if prg_bank_mode == 0 || prg_bank_mode == 1 {
+                0x4000 + 0x8000 * ((self.prg_bank as usize) & 0b1110)
+            } else if prg_bank_mode == 2 {
+                0x4000 * ((self.prg_bank as usize) & 0b1111)
+            } else {
+                0x4000 * ((self.n_prg_banks as usize) - 1)
+            };
+
+            Some((base + ((addr as usize) - 0xC000)) % len)
+        } else {
+            None
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr <= 0x1FFF && !self.chr_mem_is_ram {
+            Some(self.map_chr(addr))
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_mem
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        if 0x6000 <= addr && addr <= 0x7FFF {
+            self.prg_ram[(addr as usize) - 0x6000] = data;
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            Rc::make_mut(&mut self.prg_rom)[offset] = data;
+        } else {
+            return false;
+        }
+        true
+    }
+
     fn cycle(&mut self) {
         if self.last_write_timer > 0 {
             self.last_write_timer -= 1;
@@ -71,7 +141,7 @@ impl Mapper for Mapper1 {
 
         // TODO: Different RAM sizes
         if self.chr_mem_is_ram {
-            self.chr_mem = vec![0; 0x2000];
+            self.chr_mem = Rc::new(vec![0; 0x2000]);
         }
         self.prg_ram = vec![0; 0x2000];
     }
@@ -87,77 +157,19 @@ impl Memory for Mapper1 {
             return self.prg_ram[(addr as usize) - 0x6000];
         }
 
-        if addr <= 0x0FFF {
-            let chr_bank_mode = self.control_register >> 4;
-            let base = if chr_bank_mode == 0 {
-                0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
-            } else {
-                0x1000 * (self.chr_bank_0 as usize)
-            };
-
-            let len = self.chr_mem.len();
-            self.chr_mem[(base + (addr as usize)) % len]
-        } else if 0x1000 <= addr && addr <= 0x1FFF {
-            let chr_bank_mode = self.control_register >> 4;
-            let base = if chr_bank_mode == 0 {
-                0x1000 + 0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
-            } else {
-                0x1000 * (self.chr_bank_1 as usize)
-            };
-
-            let len = self.chr_mem.len();
-            self.chr_mem[(base + ((addr as usize) - 0x1000)) % len]
-        } else if 0x8000 <= addr && addr <= 0xBFFF {
-            let prg_bank_mode = (self.control_register & 0b1100) >> 2;
-            let base = if prg_bank_mode == 0 || prg_bank_mode == 1 {
-                0x8000 * ((self.prg_bank as usize) & 0b1110)
-            } else if prg_bank_mode == 2 {
-                0x0000
-            } else {
-                0x4000 * ((self.prg_bank as usize) & 0b1111)
-            };
-
-            let len = self.prg_rom.len();
-            self.prg_rom[(base + ((addr as usize) - 0x8000)) % len]
-        } else if 0xC000 <= addr {
-            let prg_bank_mode = (self.control_register & 0b1100) >> 2;
-            let base = if prg_bank_mode == 0 || prg_bank_mode == 1 {
-                0x4000 + 0x8000 * ((self.prg_bank as usize) & 0b1110)
-            } else if prg_bank_mode == 2 {
-                0x4000 * ((self.prg_bank as usize) & 0b1111)
-            } else {
-                0x4000 * ((self.n_prg_banks as usize) - 1)
-            };
-
-            let len = self.prg_rom.len();
-            self.prg_rom[(base + ((addr as usize) - 0xC000)) % len]
+        if addr <= 0x1FFF {
+            self.chr_mem[self.map_chr(addr)]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0
         }
     }
 
     fn write(&mut self, addr: u16, data: u8) {
-        if addr <= 0x0FFF && self.chr_mem_is_ram {
-            let chr_bank_mode = self.control_register >> 4;
-            let base = if chr_bank_mode == 0 {
-                0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
-            } else {
-                0x1000 * (self.chr_bank_0 as usize)
-            };
-
-            let len = self.chr_mem.len();
-            self.chr_mem[(base + (addr as usize)) % len] = data;
-            return;
-        } else if 0x1000 <= addr && addr <= 0x1FFF && self.chr_mem_is_ram {
-            let chr_bank_mode = self.control_register >> 4;
-            let base = if chr_bank_mode == 0 {
-                0x1000 + 0x2000 * ((self.chr_bank_0 as usize) & 0b11110)
-            } else {
-                0x1000 * (self.chr_bank_1 as usize)
-            };
-
-            let len = self.chr_mem.len();
-            self.chr_mem[(base + ((addr as usize) - 0x1000)) % len] = data;
+        if addr <= 0x1FFF && self.chr_mem_is_ram {
+            let mapped_addr = self.map_chr(addr);
+            Rc::make_mut(&mut self.chr_mem)[mapped_addr] = data;
             return;
         } else if 0x4020 <= addr && addr <= 0x5FFF {
             return;
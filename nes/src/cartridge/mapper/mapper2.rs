@@ -1,22 +1,48 @@
 use crate::cartridge::Mapper;
 use memory::Memory;
+use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/UxROM
+#[derive(Clone)]
 pub struct Mapper2 {
     n_prg_banks: u16,
-    prg_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
     chr_mem: Vec<u8>,
     prg_bank: u8,
 }
 
-impl Mapper for Mapper2 {}
+impl Mapper for Mapper2 {
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if 0x8000 <= addr && addr <= 0xBFFF {
+            Some(self.prg_bank as usize * 0x4000 + (addr as usize - 0x8000))
+        } else if 0xC000 <= addr {
+            Some((self.n_prg_banks as usize - 1) * 0x4000 + (addr as usize - 0xC000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_mem
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        match self.prg_rom_offset(addr) {
+            Some(offset) => {
+                Rc::make_mut(&mut self.prg_rom)[offset] = data;
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 impl Mapper2 {
     pub fn new(n_prg_banks: u16, prg_data: Vec<u8>) -> Self {
         Self {
             n_prg_banks,
             chr_mem: vec![0; 0x2000],
-            prg_rom: prg_data,
+            prg_rom: Rc::new(prg_data),
             prg_bank: 0,
         }
     }
@@ -30,10 +56,8 @@ impl Memory for Mapper2 {
     fn peek(&self, addr: u16) -> u8 {
         if addr <= 0x1FFF {
             self.chr_mem[addr as usize % self.chr_mem.len()]
-        } else if 0x8000 <= addr && addr <= 0xBFFF {
-            self.prg_rom[self.prg_bank as usize * 0x4000 + (addr as usize - 0x8000)]
-        } else if 0xC000 <= addr {
-            self.prg_rom[(self.n_prg_banks as usize - 1) * 0x4000 + (addr as usize - 0xC000)]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0x0
         }
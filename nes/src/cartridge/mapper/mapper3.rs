@@ -1,22 +1,54 @@
 use crate::cartridge::Mapper;
 use memory::Memory;
+use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/INES_Mapper_003
+#[derive(Clone)]
 pub struct Mapper3 {
     n_chr_banks: u16,
-    prg_rom: Vec<u8>,
-    chr_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
+    chr_rom: Rc<Vec<u8>>,
     chr_bank: u8,
 }
 
-impl Mapper for Mapper3 {}
+impl Mapper for Mapper3 {
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if 0x8000 <= addr {
+            Some((addr as usize - 0x8000) % self.prg_rom.len())
+        } else {
+            None
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr <= 0x1FFF {
+            Some((self.chr_bank as usize * 0x2000) + addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_rom
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        match self.prg_rom_offset(addr) {
+            Some(offset) => {
+                Rc::make_mut(&mut self.prg_rom)[offset] = data;
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 impl Mapper3 {
     pub fn new(n_chr_banks: u16, prg_data: Vec<u8>, chr_data: Vec<u8>) -> Self {
         Self {
             n_chr_banks: n_chr_banks,
-            prg_rom: prg_data,
-            chr_rom: chr_data,
+            prg_rom: Rc::new(prg_data),
+            chr_rom: Rc::new(chr_data),
             chr_bank: 0,
         }
     }
@@ -28,11 +60,10 @@ impl Memory for Mapper3 {
     }
 
     fn peek(&self, addr: u16) -> u8 {
-        if addr <= 0x1FFF {
-            self.chr_rom[(self.chr_bank as usize * 0x2000) + addr as usize]
-        } else if 0x8000 <= addr {
-            let len = self.prg_rom.len();
-            self.prg_rom[(addr as usize - 0x8000) % len]
+        if let Some(offset) = self.chr_rom_offset(addr) {
+            self.chr_rom[offset]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0x0
         }
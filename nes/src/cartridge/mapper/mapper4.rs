@@ -1,14 +1,21 @@
 use crate::cartridge::Mapper;
 use crate::cartridge::Mirroring;
 use memory::Memory;
+use std::rc::Rc;
+
+// A12 rising only clocks the IRQ counter once it has been low for about 3 CPU cycles,
+// which ignores the short lows between sprite pattern fetches
+// https://wiki.nesdev.com/w/index.php/MMC3#IRQ_Specifics
+const A12_FILTER_DOTS: u8 = 9;
 
 // https://wiki.nesdev.com/w/index.php/MMC3
+#[derive(Clone)]
 pub struct Mapper4 {
     n_prg_banks: u16,
     n_chr_banks: u16,
-    prg_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
     prg_ram: Vec<u8>,
-    chr_mem: Vec<u8>,
+    chr_mem: Rc<Vec<u8>>,
     chr_mem_is_ram: bool,
 
     // Memory mapping ($8000-$9FFF, $A000-$BFFF)
@@ -25,7 +32,7 @@ pub struct Mapper4 {
     irq_latch: u8,
     schedule_irq_reload: bool,
     irq_enable: bool,
-    previous_a12: u16,
+    a12_low_dots: u8, // How long A12 has been low, up to the filter's length
     trigger_irq: bool,
 
     // A submapper with PRG ram and write protection
@@ -47,6 +54,57 @@ impl Mapper for Mapper4 {
         self.trigger_irq = false;
         ret
     }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr >= 0x8000 {
+            Some(self.map_prg(addr) + (addr as usize % 0x2000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr <= 0x1FFF && !self.chr_mem_is_ram {
+            Some(self.map_chr(addr) + (addr as usize % 0x400))
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_mem
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        if 0x6000 <= addr && addr <= 0x7FFF {
+            // Whether or not the RAM is enabled or protected
+            let offset = if self.is_mmc6 {
+                if addr < 0x7000 {
+                    return false;
+                }
+                (addr as usize - 0x7000) % 0x400
+            } else {
+                addr as usize - 0x6000
+            };
+            self.prg_ram[offset] = data;
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            Rc::make_mut(&mut self.prg_rom)[offset] = data;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn ppu_dot(&mut self, addr: u16, _read: bool) {
+        if addr & 0x1000 == 0 {
+            self.a12_low_dots = std::cmp::min(self.a12_low_dots + 1, A12_FILTER_DOTS);
+        } else {
+            if self.a12_low_dots == A12_FILTER_DOTS {
+                self.clock_irq_counter();
+            }
+            self.a12_low_dots = 0;
+        }
+    }
 }
 
 impl Mapper4 {
@@ -60,14 +118,14 @@ impl Mapper4 {
         Self {
             n_prg_banks,
             n_chr_banks: std::cmp::max(n_chr_banks, 1),
-            prg_rom: prg_data,
+            prg_rom: Rc::new(prg_data),
             prg_ram: vec![0; 0x2000],
-            chr_mem: if n_chr_banks == 0 {
+            chr_mem: Rc::new(if n_chr_banks == 0 {
                 // TODO: RAM sizes
                 vec![0; 0x2000]
             } else {
                 chr_data
-            },
+            }),
             chr_mem_is_ram: n_chr_banks == 0,
 
             select_bank_register: 0,
@@ -82,7 +140,7 @@ impl Mapper4 {
             irq_latch: 0,
             schedule_irq_reload: false,
             irq_enable: false,
-            previous_a12: 0,
+            a12_low_dots: 0,
             trigger_irq: false,
 
             is_mmc6,
@@ -94,6 +152,21 @@ impl Mapper4 {
         }
     }
 
+    fn clock_irq_counter(&mut self) {
+        if self.schedule_irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.schedule_irq_reload = false;
+        } else if self.irq_counter == 0 {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enable {
+            self.trigger_irq = true;
+        }
+    }
+
     fn map_chr(&self, addr: u16) -> usize {
         if (self.chr_bank_mode && addr >= 0x1000) || (!self.chr_bank_mode && addr < 0x1000) {
             let bank = self.bank_registers[((addr % 0x1000) >= 0x800) as usize];
@@ -122,26 +195,6 @@ impl Mapper4 {
 
 impl Memory for Mapper4 {
     fn read(&mut self, addr: u16) -> u8 {
-        // IRQ counter is a side-effect of reading
-        if addr <= 0x1FFF {
-            let a12 = (addr >> 12) & 1;
-            if (self.previous_a12 == 0) && (a12 == 1) {
-                if self.schedule_irq_reload {
-                    self.irq_counter = self.irq_latch;
-                    self.schedule_irq_reload = false;
-                } else if self.irq_counter == 0 {
-                    self.irq_counter = self.irq_latch;
-                } else {
-                    self.irq_counter -= 1;
-                }
-
-                if self.irq_counter == 0 && self.irq_enable {
-                    self.trigger_irq = true;
-                }
-            }
-            self.previous_a12 = a12;
-        }
-
         self.peek(addr)
     }
 
@@ -169,8 +222,8 @@ impl Memory for Mapper4 {
                     0
                 }
             }
-        } else if addr >= 0x8000 {
-            self.prg_rom[self.map_prg(addr) + (addr as usize % 0x2000)]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0
         }
@@ -181,7 +234,7 @@ impl Memory for Mapper4 {
         if addr <= 0x1FFF {
             if self.chr_mem_is_ram {
                 let mapped_addr = self.map_chr(addr) + (addr as usize % 0x400);
-                self.chr_mem[mapped_addr] = data;
+                Rc::make_mut(&mut self.chr_mem)[mapped_addr] = data;
             }
         } else if 0x6000 <= addr && addr <= 0x7FFF {
             if self.is_mmc6 {
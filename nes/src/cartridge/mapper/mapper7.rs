@@ -1,11 +1,13 @@
 use crate::cartridge::Mapper;
 use crate::cartridge::Mirroring;
 use memory::Memory;
+use std::rc::Rc;
 
 // https://wiki.nesdev.com/w/index.php/AxROM
+#[derive(Clone)]
 pub struct Mapper7 {
     n_prg_banks: u16,
-    prg_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
     chr_ram: Vec<u8>,
     prg_bank: u8,
     mirroring: Mirroring,
@@ -15,13 +17,35 @@ impl Mapper for Mapper7 {
     fn get_nametable_mirroring(&self) -> Option<Mirroring> {
         Some(self.mirroring)
     }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if 0x8000 <= addr {
+            Some(0x8000 * self.prg_bank as usize + (addr as usize - 0x8000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_ram
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        match self.prg_rom_offset(addr) {
+            Some(offset) => {
+                Rc::make_mut(&mut self.prg_rom)[offset] = data;
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Mapper7 {
     pub fn new(n_prg_banks: u16, prg_data: Vec<u8>) -> Self {
         Self {
             n_prg_banks: n_prg_banks,
-            prg_rom: prg_data,
+            prg_rom: Rc::new(prg_data),
             chr_ram: vec![0; 0x2000],
             prg_bank: 0,
             mirroring: Mirroring::SingleScreenLower,
@@ -37,8 +61,8 @@ impl Memory for Mapper7 {
     fn peek(&self, addr: u16) -> u8 {
         if addr <= 0x1FFF {
             self.chr_ram[addr as usize]
-        } else if 0x8000 <= addr {
-            self.prg_rom[0x8000 * self.prg_bank as usize + (addr as usize - 0x8000)]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0x0
         }
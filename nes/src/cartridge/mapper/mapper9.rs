@@ -1,12 +1,16 @@
 use crate::cartridge::Mapper;
 use crate::cartridge::Mirroring;
 use memory::Memory;
+use std::rc::Rc;
 
-// https://wiki.nesdev.com/w/index.php/INES_Mapper_003
+// https://wiki.nesdev.com/w/index.php/MMC2
+// https://wiki.nesdev.com/w/index.php/MMC4
+#[derive(Clone)]
 pub struct Mapper9 {
     n_prg_banks: u16,
-    prg_rom: Vec<u8>,
-    chr_rom: Vec<u8>,
+    prg_rom: Rc<Vec<u8>>,
+    prg_ram: Vec<u8>,
+    chr_rom: Rc<Vec<u8>>,
 
     prg_bank: u8,
     chr_fd_bank_lo: u8,
@@ -17,20 +21,94 @@ pub struct Mapper9 {
     chr_latch_1: bool,
 
     mirroring: Mirroring,
+
+    // MMC4 switches 16 KB of PRG ROM rather than 8 KB, has PRG RAM,
+    // and latches on any row of tiles $FD and $FE in the left pattern table
+    is_mmc4: bool,
 }
 
 impl Mapper for Mapper9 {
     fn get_nametable_mirroring(&self) -> Option<Mirroring> {
         Some(self.mirroring)
     }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if self.is_mmc4 {
+            if (0x8000..=0xBFFF).contains(&addr) {
+                Some((self.prg_bank as usize * 0x4000) + (addr as usize - 0x8000))
+            } else if 0xC000 <= addr {
+                Some(((self.n_prg_banks as usize - 1) * 0x4000) + (addr as usize - 0xC000))
+            } else {
+                None
+            }
+        } else if 0x8000 <= addr && addr <= 0x9FFF {
+            Some((self.prg_bank as usize * 0x2000) + (addr as usize - 0x8000))
+        } else if 0xA000 <= addr {
+            Some(((self.n_prg_banks as usize * 2 - 3) * 0x2000) + (addr as usize - 0xA000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if addr <= 0x0FFF {
+            let bank = if self.chr_latch_0 {
+                self.chr_fe_bank_lo
+            } else {
+                self.chr_fd_bank_lo
+            };
+            Some((bank as usize * 0x1000) + addr as usize)
+        } else if 0x1000 <= addr && addr <= 0x1FFF {
+            let bank = if self.chr_latch_1 {
+                self.chr_fe_bank_hi
+            } else {
+                self.chr_fd_bank_hi
+            };
+            Some((bank as usize * 0x1000) + (addr as usize - 0x1000))
+        } else {
+            None
+        }
+    }
+
+    fn chr_data(&self) -> &[u8] {
+        &self.chr_rom
+    }
+
+    fn poke(&mut self, addr: u16, data: u8) -> bool {
+        if self.is_mmc4 && (0x6000..=0x7FFF).contains(&addr) {
+            self.prg_ram[addr as usize - 0x6000] = data;
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            Rc::make_mut(&mut self.prg_rom)[offset] = data;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn ppu_dot(&mut self, addr: u16, read: bool) {
+        // Latch changes go into effect only after the read
+        if !read {
+            return;
+        }
+        // MMC2 only latches on the first of the left pattern table's addresses
+        let last_row = if self.is_mmc4 { 7 } else { 0 };
+        match addr {
+            0x0FD8..=0x0FDF if addr - 0x0FD8 <= last_row => self.chr_latch_0 = false,
+            0x0FE8..=0x0FEF if addr - 0x0FE8 <= last_row => self.chr_latch_0 = true,
+            0x1FD8..=0x1FDF => self.chr_latch_1 = false,
+            0x1FE8..=0x1FEF => self.chr_latch_1 = true,
+            _ => {}
+        }
+    }
 }
 
 impl Mapper9 {
-    pub fn new(n_prg_banks: u16, prg_data: Vec<u8>, chr_data: Vec<u8>) -> Self {
+    pub fn new(n_prg_banks: u16, prg_data: Vec<u8>, chr_data: Vec<u8>, is_mmc4: bool) -> Self {
         Self {
             n_prg_banks,
-            prg_rom: prg_data,
-            chr_rom: chr_data,
+            prg_rom: Rc::new(prg_data),
+            prg_ram: vec![0; 0x2000],
+            chr_rom: Rc::new(chr_data),
 
             prg_bank: 0,
             chr_fd_bank_lo: 0,
@@ -41,53 +119,38 @@ impl Mapper9 {
             chr_latch_1: false,
 
             mirroring: Mirroring::Vertical,
+
+            is_mmc4,
         }
     }
 }
 
 impl Memory for Mapper9 {
     fn read(&mut self, addr: u16) -> u8 {
-        // Latch changes go into effect only on the next read
-        let data = self.peek(addr);
-
-        if addr == 0x0FD8 {
-            self.chr_latch_0 = false;
-        } else if addr == 0x0FE8 {
-            self.chr_latch_0 = true;
-        } else if 0x1FD8 <= addr && addr <= 0x1FDF {
-            self.chr_latch_1 = false;
-        } else if 0x1FE8 <= addr && addr <= 0x1FEF {
-            self.chr_latch_1 = true;
-        }
-
-        data
+        self.peek(addr)
     }
 
     fn peek(&self, addr: u16) -> u8 {
-        if addr <= 0x0FFF {
-            if self.chr_latch_0 {
-                self.chr_rom[(self.chr_fe_bank_lo as usize * 0x1000) + addr as usize]
-            } else {
-                self.chr_rom[(self.chr_fd_bank_lo as usize * 0x1000) + addr as usize]
-            }
-        } else if 0x1000 <= addr && addr <= 0x1FFF {
-            if self.chr_latch_1 {
-                self.chr_rom[(self.chr_fe_bank_hi as usize * 0x1000) + (addr as usize - 0x1000)]
-            } else {
-                self.chr_rom[(self.chr_fd_bank_hi as usize * 0x1000) + (addr as usize - 0x1000)]
-            }
-        } else if 0x8000 <= addr && addr <= 0x9FFF {
-            self.prg_rom[(self.prg_bank as usize * 0x2000) + (addr as usize - 0x8000)]
-        } else if 0xA000 <= addr {
-            self.prg_rom[((self.n_prg_banks as usize * 2 - 3) * 0x2000) + (addr as usize - 0xA000)]
+        if let Some(offset) = self.chr_rom_offset(addr) {
+            self.chr_rom[offset]
+        } else if self.is_mmc4 && (0x6000..=0x7FFF).contains(&addr) {
+            self.prg_ram[addr as usize - 0x6000]
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            self.prg_rom[offset]
         } else {
             0
         }
     }
 
     fn write(&mut self, addr: u16, data: u8) {
-        if 0xA000 <= addr && addr <= 0xAFFF {
-            self.prg_bank = (data & 0b1111) % (self.n_prg_banks as u8 * 2);
+        if self.is_mmc4 && (0x6000..=0x7FFF).contains(&addr) {
+            self.prg_ram[addr as usize - 0x6000] = data;
+        } else if 0xA000 <= addr && addr <= 0xAFFF {
+            self.prg_bank = if self.is_mmc4 {
+                (data & 0b1111) % (self.n_prg_banks as u8)
+            } else {
+                (data & 0b1111) % (self.n_prg_banks as u8 * 2)
+            };
         } else if 0xB000 <= addr && addr <= 0xBFFF {
             self.chr_fd_bank_lo = data & 0b11111;
         } else if 0xC000 <= addr && addr <= 0xCFFF {
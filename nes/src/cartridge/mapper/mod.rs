@@ -20,7 +20,7 @@ pub use self::mapper71::Mapper71;
 pub use self::mapper9::Mapper9;
 
 // TODO: Add reset to more mappers so NES::reset works
-pub trait Mapper: Memory {
+pub trait Mapper: Memory + CloneMapper {
     fn get_nametable_mirroring(&self) -> Option<Mirroring> {
         None // Unless otherwise specified, mirroring is hard-wired
     }
@@ -29,6 +29,53 @@ pub trait Mapper: Memory {
         false
     }
 
+    /// The offset into PRG ROM that a CPU address currently maps to, if any
+    fn prg_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// The offset into CHR ROM that a PPU address currently maps to, if any (None for CHR RAM)
+    fn chr_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// All of the CHR ROM or RAM, whichever banks are switched in, for viewers
+    fn chr_data(&self) -> &[u8];
+
+    /// Change the PRG ROM or RAM that a CPU address currently maps to, without side effects
+    /// on the mapper's registers, for debuggers, returning whether anything is mapped there
+    fn poke(&mut self, _addr: u16, _data: u8) -> bool {
+        false
+    }
+
+    /// Called once per CPU cycle
     fn cycle(&mut self) {}
+
+    /// Called on every PPU dot with the address on the PPU's bus and whether the PPU read it.
+    /// While rendering, that's each fetch, including the garbage nametable fetches and those
+    /// for empty sprite slots, and otherwise it's the VRAM address. This is how MMC3 counts
+    /// scanlines by A12 and MMC2 and MMC4 switch banks after tiles $FD and $FE. It's also
+    /// enough for an MMC5 to find scanlines by the nametable fetches repeated at the end of
+    /// each one, though there's no MMC5 mapper yet.
+    fn ppu_dot(&mut self, _addr: u16, _read: bool) {}
+
     fn reset(&mut self) {}
 }
+
+/// Copies a boxed mapper, with its banks and RAM, for save states.
+/// Mappers keep ROM in an Rc, so that copies share it rather than each holding their own.
+pub trait CloneMapper {
+    fn clone_mapper(&self) -> Box<dyn Mapper>;
+}
+
+impl<T: Mapper + Clone + 'static> CloneMapper for T {
+    fn clone_mapper(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.clone_mapper()
+    }
+}
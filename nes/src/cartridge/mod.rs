@@ -1,5 +1,9 @@
+use crate::code_data_log::*;
 use memory::Memory;
+use ppu::{render_pattern_table, PPUBusObserver, RgbImage};
+use std::collections::hash_map::DefaultHasher;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
 
 mod cartridge_metadata;
@@ -9,9 +13,23 @@ pub use cartridge_metadata::Mirroring;
 mod mapper;
 use mapper::*;
 
+// Debuggers conventionally number PRG banks in 16 KB units, regardless of the mapper
+pub const PRG_BANK_SIZE: usize = 0x4000;
+// CHR ROM or RAM is viewed 4 KB at a time, the size of a pattern table
+pub const CHR_BANK_SIZE: usize = 0x1000;
+
+/// The mapper's registers and memory, for save states
+#[derive(Clone)]
+pub struct CartridgeState {
+    mapper: Option<Box<dyn Mapper>>,
+    rom_hash: u64, // Which ROM the state is from
+}
+
 pub struct Cartridge {
     meta: Option<CartridgeMetadata>,
     mapper: Option<Box<dyn Mapper>>,
+    code_data_log: Option<CodeDataLog>,
+    rom_hash: u64,
 }
 
 impl Cartridge {
@@ -19,6 +37,8 @@ impl Cartridge {
         Self {
             meta: None,
             mapper: None,
+            code_data_log: None,
+            rom_hash: 0,
         }
     }
 
@@ -31,16 +51,19 @@ impl Cartridge {
         let meta = CartridgeMetadata::from_header(header)?;
 
         let prg_size = 0x4000 * (meta.n_prg_banks as usize);
-        let prg_data = match bytes.by_ref().take(prg_size).collect() {
+        let prg_data: Vec<u8> = match bytes.by_ref().take(prg_size).collect() {
             Ok(data) => data,
             Err(_) => return Err("hit EOF while reading program ROM"),
         };
         let chr_size = 0x2000 * (meta.n_chr_banks as usize);
-        let chr_data = match bytes.by_ref().take(chr_size).collect() {
+        let chr_data: Vec<u8> = match bytes.by_ref().take(chr_size).collect() {
             Ok(data) => data,
             Err(_) => return Err("hit EOF while reading character ROM"),
         };
 
+        let mut hasher = DefaultHasher::new();
+        prg_data.hash(&mut hasher);
+        chr_data.hash(&mut hasher);
         let (n_prg_banks, n_chr_banks) = (meta.n_prg_banks, meta.n_chr_banks);
         let mapper: Box<dyn Mapper> = match meta.mapper_num {
             0 => Box::from(Mapper0::new(n_prg_banks, n_chr_banks, prg_data, chr_data)),
@@ -55,7 +78,8 @@ impl Cartridge {
                 meta.submapper_num == Some(1),
             )),
             7 => Box::from(Mapper7::new(n_prg_banks, prg_data)),
-            9 => Box::from(Mapper9::new(n_prg_banks, prg_data, chr_data)),
+            9 => Box::from(Mapper9::new(n_prg_banks, prg_data, chr_data, false)),
+            10 => Box::from(Mapper9::new(n_prg_banks, prg_data, chr_data, true)),
             71 => Box::from(Mapper71::new(n_prg_banks, prg_data)),
             _ => return Err("unsupported mapper"),
         };
@@ -63,6 +87,8 @@ impl Cartridge {
         Ok(Self {
             meta: Some(meta),
             mapper: Some(mapper),
+            code_data_log: None,
+            rom_hash: hasher.finish(),
         })
     }
 
@@ -93,12 +119,150 @@ impl Cartridge {
         }
     }
 
+    /// The offset into PRG ROM that a CPU address currently maps to, if any
+    pub fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        if let Some(some_mapper) = &self.mapper {
+            some_mapper.prg_rom_offset(addr)
+        } else {
+            None
+        }
+    }
+
+    /// The offset into CHR ROM that a PPU address currently maps to, if any
+    pub fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        if let Some(some_mapper) = &self.mapper {
+            some_mapper.chr_rom_offset(addr)
+        } else {
+            None
+        }
+    }
+
+    /// The 16 KB PRG bank that a CPU address currently maps to, if any
+    pub fn prg_bank(&self, addr: u16) -> Option<usize> {
+        self.prg_rom_offset(addr).map(|offset| offset / PRG_BANK_SIZE)
+    }
+
+    pub fn prg_rom_size(&self) -> usize {
+        self.meta
+            .as_ref()
+            .map_or(0, |meta| 0x4000 * (meta.n_prg_banks as usize))
+    }
+
+    pub fn chr_rom_size(&self) -> usize {
+        self.meta
+            .as_ref()
+            .map_or(0, |meta| 0x2000 * (meta.n_chr_banks as usize))
+    }
+
+    /// All of the CHR ROM or RAM, whichever banks are switched in
+    fn chr_data(&self) -> &[u8] {
+        match &self.mapper {
+            Some(some_mapper) => some_mapper.chr_data(),
+            None => &[],
+        }
+    }
+
+    pub fn chr_bank_count(&self) -> usize {
+        self.chr_data().len() / CHR_BANK_SIZE
+    }
+
+    /// Draw a 4 KB bank of CHR ROM or RAM like a pattern table, if there is such a bank
+    pub fn render_chr_bank(
+        &self,
+        bank: usize,
+        palette: &[u8; 4],
+        colors: &[u32],
+    ) -> Option<RgbImage> {
+        self.chr_data()
+            .chunks_exact(CHR_BANK_SIZE)
+            .nth(bank)
+            .map(|data| render_pattern_table(data, palette, colors))
+    }
+
+    /// Start recording how each byte of ROM is used, or stop if None
+    pub fn set_code_data_log(&mut self, log: Option<CodeDataLog>) -> Result<(), &'static str> {
+        if let Some(some_log) = &log {
+            if some_log.prg_rom_size() != self.prg_rom_size()
+                || some_log.chr_rom_size() != self.chr_rom_size()
+            {
+                return Err("code/data log doesn't match the size of the ROM");
+            }
+        }
+        self.code_data_log = log;
+        Ok(())
+    }
+
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.code_data_log.as_ref()
+    }
+
+    pub fn take_code_data_log(&mut self) -> Option<CodeDataLog> {
+        self.code_data_log.take()
+    }
+
+    /// Mark a byte as part of a DMC sample, which the DMC then reads over the CPU's bus
+    pub fn log_sample(&mut self, addr: u16) {
+        self.log_access(addr, PRG_PCM, 0);
+    }
+
+    /// Record an access in the code/data log, before it can change the mapper's banks
+    fn log_access(&mut self, addr: u16, prg_flags: u8, chr_flags: u8) {
+        if self.code_data_log.is_none() {
+            return;
+        }
+
+        if addr <= 0x1FFF {
+            if let Some(offset) = self.chr_rom_offset(addr) {
+                if let Some(log) = &mut self.code_data_log {
+                    log.log_chr(offset, chr_flags);
+                }
+            }
+        } else if let Some(offset) = self.prg_rom_offset(addr) {
+            if let Some(log) = &mut self.code_data_log {
+                log.log_prg(offset, addr, prg_flags);
+            }
+        }
+    }
+
+    fn read_mapper(&mut self, addr: u16) -> u8 {
+        if let Some(some_mapper) = &mut self.mapper {
+            some_mapper.read(addr)
+        } else {
+            0x00
+        }
+    }
+
+    /// Change PRG ROM or RAM without side effects, returning whether anything is mapped there
+    pub fn poke(&mut self, addr: u16, data: u8) -> bool {
+        if let Some(some_mapper) = &mut self.mapper {
+            some_mapper.poke(addr, data)
+        } else {
+            false
+        }
+    }
+
     pub fn cycle(&mut self) {
         if let Some(some_mapper) = &mut self.mapper {
             some_mapper.cycle();
         }
     }
 
+    pub fn save_state(&self) -> CartridgeState {
+        CartridgeState {
+            mapper: self.mapper.clone(),
+            rom_hash: self.rom_hash,
+        }
+    }
+
+    /// Whether a save state is from the ROM that is loaded now
+    pub fn matches_state(&self, state: &CartridgeState) -> bool {
+        state.rom_hash == self.rom_hash
+    }
+
+    pub fn load_state(&mut self, state: &CartridgeState) {
+        self.mapper = state.mapper.clone();
+    }
+
     pub fn reset(&mut self) {
         if let Some(some_mapper) = &mut self.mapper {
             some_mapper.reset();
@@ -106,14 +270,24 @@ impl Cartridge {
     }
 }
 
-impl Memory for Cartridge {
-    fn read(&mut self, addr: u16) -> u8 {
+impl PPUBusObserver for Cartridge {
+    fn on_dot(&mut self, addr: u16, read: bool) {
         if let Some(some_mapper) = &mut self.mapper {
-            some_mapper.read(addr)
-        } else {
-            0x00
+            some_mapper.ppu_dot(addr, read);
         }
     }
+}
+
+impl Memory for Cartridge {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.log_access(addr, PRG_DATA, CHR_READ);
+        self.read_mapper(addr)
+    }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.log_access(addr, PRG_CODE, CHR_DRAWN);
+        self.read_mapper(addr)
+    }
 
     fn peek(&self, addr: u16) -> u8 {
         if let Some(some_mapper) = &self.mapper {
@@ -0,0 +1,101 @@
+use std::io;
+use std::io::prelude::*;
+
+// http://fceux.com/web/help/CodeDataLogger.html
+// PRG bytes are laid out as xPdcAADC, where AA is the 8 KB window of CPU memory the byte was last accessed through
+pub const PRG_CODE: u8 = 0x01;
+pub const PRG_DATA: u8 = 0x02;
+pub const PRG_PCM: u8 = 0x40; // Played as a DMC sample
+const PRG_WINDOW_SHIFT: u8 = 2;
+const PRG_WINDOW_MASK: u8 = 0b0000_1100;
+
+pub const CHR_DRAWN: u8 = 0x01;
+pub const CHR_READ: u8 = 0x02; // Read by the CPU through PPUDATA
+
+/// Records how each byte of a cartridge's ROM has been used, in the FCEUX .cdl format
+#[derive(Clone)]
+pub struct CodeDataLog {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl CodeDataLog {
+    pub fn new(prg_rom_size: usize, chr_rom_size: usize) -> Self {
+        Self {
+            prg: vec![0; prg_rom_size],
+            chr: vec![0; chr_rom_size],
+        }
+    }
+
+    /// Read an existing log, which must be for a ROM with the given sizes
+    pub fn load(
+        reader: impl Read,
+        prg_rom_size: usize,
+        chr_rom_size: usize,
+    ) -> Result<Self, &'static str> {
+        let mut bytes = Vec::new();
+        if reader
+            .take((prg_rom_size + chr_rom_size + 1) as u64)
+            .read_to_end(&mut bytes)
+            .is_err()
+        {
+            return Err("failed to read code/data log");
+        }
+        if bytes.len() != prg_rom_size + chr_rom_size {
+            return Err("code/data log doesn't match the size of the ROM");
+        }
+
+        let chr = bytes.split_off(prg_rom_size);
+        Ok(Self { prg: bytes, chr })
+    }
+
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&self.prg)?;
+        writer.write_all(&self.chr)
+    }
+
+    /// The flags for each byte of PRG ROM
+    pub fn prg(&self) -> &[u8] {
+        &self.prg
+    }
+
+    /// The flags for each byte of CHR ROM
+    pub fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    pub fn prg_rom_size(&self) -> usize {
+        self.prg.len()
+    }
+
+    pub fn chr_rom_size(&self) -> usize {
+        self.chr.len()
+    }
+
+    /// Mark a byte of PRG ROM, given the CPU address it was accessed through
+    pub fn log_prg(&mut self, offset: usize, addr: u16, flags: u8) {
+        if let Some(entry) = self.prg.get_mut(offset) {
+            let window = (((addr >> 13) & 0b11) as u8) << PRG_WINDOW_SHIFT;
+            *entry = (*entry & !PRG_WINDOW_MASK) | window | flags;
+        }
+    }
+
+    pub fn log_chr(&mut self, offset: usize, flags: u8) {
+        if let Some(entry) = self.chr.get_mut(offset) {
+            *entry |= flags;
+        }
+    }
+
+    /// The number of PRG ROM bytes that have been used in any way
+    pub fn prg_bytes_logged(&self) -> usize {
+        self.prg
+            .iter()
+            .filter(|&&flags| flags & (PRG_CODE | PRG_DATA | PRG_PCM) != 0)
+            .count()
+    }
+
+    /// The number of CHR ROM bytes that have been used in any way
+    pub fn chr_bytes_logged(&self) -> usize {
+        self.chr.iter().filter(|&&flags| flags != 0).count()
+    }
+}
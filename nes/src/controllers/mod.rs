@@ -3,10 +3,19 @@ mod standard_controller;
 
 use memory::Memory;
 
+/// The buttons latched into a controller's shift register, and its strobe, for save states
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ControllerState {
+    state_shift: u8,
+    shift_strobe: bool,
+}
+
 pub trait Controller: Memory {
     fn is_controller_1(&self) -> bool;
     fn get_shift_strobe(&self) -> bool;
     fn set_state_shift(&mut self, val: u8);
+    fn save_state(&self) -> ControllerState;
+    fn load_state(&mut self, state: &ControllerState);
 }
 
 pub use self::no_controller::NoController;
@@ -1,4 +1,4 @@
-use crate::controllers::Controller;
+use crate::controllers::{Controller, ControllerState};
 use memory::Memory;
 
 pub struct NoController {}
@@ -19,6 +19,12 @@ impl Controller for NoController {
     }
 
     fn set_state_shift(&mut self, _: u8) {}
+
+    fn save_state(&self) -> ControllerState {
+        ControllerState::default()
+    }
+
+    fn load_state(&mut self, _: &ControllerState) {}
 }
 
 impl Memory for NoController {
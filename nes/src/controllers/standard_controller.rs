@@ -1,4 +1,4 @@
-use crate::controllers::Controller;
+use crate::controllers::{Controller, ControllerState};
 use memory::Memory;
 
 // https://wiki.nesdev.com/w/index.php/Standard_controller
@@ -30,6 +30,18 @@ impl Controller for StandardController {
     fn set_state_shift(&mut self, val: u8) {
         self.state_shift = val;
     }
+
+    fn save_state(&self) -> ControllerState {
+        ControllerState {
+            state_shift: self.state_shift,
+            shift_strobe: self.shift_strobe,
+        }
+    }
+
+    fn load_state(&mut self, state: &ControllerState) {
+        self.state_shift = state.state_shift;
+        self.shift_strobe = state.shift_strobe;
+    }
 }
 
 impl Memory for StandardController {
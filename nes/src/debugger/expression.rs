@@ -0,0 +1,381 @@
+use crate::Registers;
+use memory::Memory;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The state of the NES that an expression is evaluated against
+pub struct ExpressionContext<'a> {
+    pub registers: Registers,
+    pub scanline: u16,
+    pub dot: u16,
+    pub frame: u64,
+    pub access: Option<(u16, u8)>, // The address and data of the read or write being checked
+    pub memory: &'a dyn Memory,    // The CPU address space, read without side effects
+}
+
+/// An expression over the state of the NES, e.g. "A == $FF && [$0300] > 3 && scanline < 20".
+///
+/// Numbers are decimal, or hex with a $ or 0x prefix, or binary with a 0b prefix.
+/// `[addr]` reads a byte of CPU memory and `{addr}` reads a little-endian word.
+/// The variables are the registers `a`, `x`, `y`, `p`, `s` (or `sp`) and `pc`, the flags
+/// `n`, `v`, `d`, `i`, `z` and `c`, `scanline`, `dot`, `frame`, and the `address` and
+/// `value` of the memory access being checked, which are 0 for other breakpoints.
+/// The operators are those of C, so `%` is the remainder, and comparisons and logical
+/// operators give 1 or 0.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn evaluate(&self, context: &ExpressionContext) -> i64 {
+        self.root.evaluate(context)
+    }
+
+    /// Whether the expression evaluates to anything but 0
+    pub fn is_true(&self, context: &ExpressionContext) -> bool {
+        self.evaluate(context) != 0
+    }
+}
+
+impl FromStr for Expression {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let root = parser.parse_binary(0)?;
+        if parser.position != tokens.len() {
+            return Err("unexpected token in expression");
+        }
+        Ok(Self {
+            source: s.trim().to_string(),
+            root,
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Variable {
+    A,
+    X,
+    Y,
+    P,
+    S,
+    PC,
+    Flag(u8), // The mask of a bit of P
+    Scanline,
+    Dot,
+    Frame,
+    Address,
+    Value,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match &name.to_lowercase()[..] {
+            "a" => Variable::A,
+            "x" => Variable::X,
+            "y" => Variable::Y,
+            "p" => Variable::P,
+            "s" | "sp" => Variable::S,
+            "pc" => Variable::PC,
+            "n" => Variable::Flag(0x80),
+            "v" => Variable::Flag(0x40),
+            "d" => Variable::Flag(0x08),
+            "i" => Variable::Flag(0x04),
+            "z" => Variable::Flag(0x02),
+            "c" => Variable::Flag(0x01),
+            "scanline" => Variable::Scanline,
+            "dot" => Variable::Dot,
+            "frame" => Variable::Frame,
+            "address" => Variable::Address,
+            "value" => Variable::Value,
+            _ => return None,
+        })
+    }
+
+    fn evaluate(&self, context: &ExpressionContext) -> i64 {
+        let registers = &context.registers;
+        match *self {
+            Variable::A => registers.a as i64,
+            Variable::X => registers.x as i64,
+            Variable::Y => registers.y as i64,
+            Variable::P => registers.p as i64,
+            Variable::S => registers.s as i64,
+            Variable::PC => registers.pc as i64,
+            Variable::Flag(mask) => (registers.p & mask != 0) as i64,
+            Variable::Scanline => context.scanline as i64,
+            Variable::Dot => context.dot as i64,
+            Variable::Frame => context.frame as i64,
+            Variable::Address => context.access.map_or(0, |(addr, _)| addr as i64),
+            Variable::Value => context.access.map_or(0, |(_, data)| data as i64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOp {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        Some(match symbol {
+            "||" => BinaryOp::Or,
+            "&&" => BinaryOp::And,
+            "|" => BinaryOp::BitOr,
+            "^" => BinaryOp::BitXor,
+            "&" => BinaryOp::BitAnd,
+            "==" => BinaryOp::Equal,
+            "!=" => BinaryOp::NotEqual,
+            "<" => BinaryOp::Less,
+            "<=" => BinaryOp::LessEqual,
+            ">" => BinaryOp::Greater,
+            ">=" => BinaryOp::GreaterEqual,
+            "<<" => BinaryOp::ShiftLeft,
+            ">>" => BinaryOp::ShiftRight,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Subtract,
+            "*" => BinaryOp::Multiply,
+            "/" => BinaryOp::Divide,
+            "%" => BinaryOp::Remainder,
+            _ => return None,
+        })
+    }
+
+    // Higher binds tighter, as in C
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 0,
+            BinaryOp::And => 1,
+            BinaryOp::BitOr => 2,
+            BinaryOp::BitXor => 3,
+            BinaryOp::BitAnd => 4,
+            BinaryOp::Equal | BinaryOp::NotEqual => 5,
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => 6,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 7,
+            BinaryOp::Add | BinaryOp::Subtract => 8,
+            BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Remainder => 9,
+        }
+    }
+
+    fn apply(&self, left: i64, right: i64) -> i64 {
+        match self {
+            BinaryOp::Or => (left != 0 || right != 0) as i64,
+            BinaryOp::And => (left != 0 && right != 0) as i64,
+            BinaryOp::BitOr => left | right,
+            BinaryOp::BitXor => left ^ right,
+            BinaryOp::BitAnd => left & right,
+            BinaryOp::Equal => (left == right) as i64,
+            BinaryOp::NotEqual => (left != right) as i64,
+            BinaryOp::Less => (left < right) as i64,
+            BinaryOp::LessEqual => (left <= right) as i64,
+            BinaryOp::Greater => (left > right) as i64,
+            BinaryOp::GreaterEqual => (left >= right) as i64,
+            BinaryOp::ShiftLeft => left.wrapping_shl(right as u32),
+            BinaryOp::ShiftRight => left.wrapping_shr(right as u32),
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Subtract => left.wrapping_sub(right),
+            BinaryOp::Multiply => left.wrapping_mul(right),
+            // Dividing by zero gives 0, rather than stopping emulation
+            BinaryOp::Divide => left.checked_div(right).unwrap_or(0),
+            BinaryOp::Remainder => left.checked_rem(right).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Number(i64),
+    Variable(Variable),
+    Byte(Box<Node>), // [addr]
+    Word(Box<Node>), // {addr}
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, context: &ExpressionContext) -> i64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Variable(variable) => variable.evaluate(context),
+            Node::Byte(addr) => context.memory.peek(addr.evaluate(context) as u16) as i64,
+            Node::Word(addr) => {
+                let addr = addr.evaluate(context) as u16;
+                let lo = context.memory.peek(addr) as i64;
+                let hi = context.memory.peek(addr.wrapping_add(1)) as i64;
+                (hi << 8) | lo
+            }
+            Node::Unary(op, operand) => {
+                let operand = operand.evaluate(context);
+                match op {
+                    UnaryOp::Negate => operand.wrapping_neg(),
+                    UnaryOp::Not => (operand == 0) as i64,
+                    UnaryOp::Complement => !operand,
+                }
+            }
+            Node::Binary(op, left, right) => {
+                op.apply(left.evaluate(context), right.evaluate(context))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+// Longer symbols come first, so that e.g. "<=" isn't read as "<" then "="
+const SYMBOLS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~", "(", ")", "[", "]", "{", "}",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let (radix, prefix_len) = if rest.starts_with('$') {
+            (16, 1)
+        } else if rest.starts_with("0x") || rest.starts_with("0X") {
+            (16, 2)
+        } else if rest.starts_with("0b") || rest.starts_with("0B") {
+            (2, 2)
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            (10, 0)
+        } else {
+            (0, 0)
+        };
+
+        if radix != 0 {
+            let digits = &rest[prefix_len..];
+            let len = digits
+                .find(|c: char| !c.is_digit(radix))
+                .unwrap_or(digits.len());
+            let value = i64::from_str_radix(&digits[..len], radix).map_err(|_| "invalid number")?;
+            tokens.push(Token::Number(value));
+            rest = &digits[len..];
+        } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or("unexpected character in expression")?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// A precedence climbing parser over a list of tokens
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Node, &'static str> {
+        let mut left = self.parse_unary()?;
+        while let Some(Token::Symbol(symbol)) = self.tokens.get(self.position) {
+            let op = match BinaryOp::from_symbol(symbol) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.parse_binary(op.precedence() + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, &'static str> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or("unexpected end of expression")?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(Node::Number(*value)),
+            Token::Name(name) => Variable::from_name(name)
+                .map(Node::Variable)
+                .ok_or("unknown variable in expression"),
+            Token::Symbol("-") => Ok(Node::Unary(UnaryOp::Negate, Box::new(self.parse_unary()?))),
+            Token::Symbol("!") => Ok(Node::Unary(UnaryOp::Not, Box::new(self.parse_unary()?))),
+            Token::Symbol("~") => Ok(Node::Unary(
+                UnaryOp::Complement,
+                Box::new(self.parse_unary()?),
+            )),
+            Token::Symbol("(") => {
+                let inner = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Symbol("[") => {
+                let addr = self.parse_binary(0)?;
+                self.expect("]")?;
+                Ok(Node::Byte(Box::new(addr)))
+            }
+            Token::Symbol("{") => {
+                let addr = self.parse_binary(0)?;
+                self.expect("}")?;
+                Ok(Node::Word(Box::new(addr)))
+            }
+            Token::Symbol(_) => Err("unexpected operator in expression"),
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), &'static str> {
+        if self.tokens.get(self.position) == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err("unbalanced brackets in expression")
+        }
+    }
+}
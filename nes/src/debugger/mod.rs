@@ -0,0 +1,369 @@
+mod expression;
+
+pub use cpu::Interrupt;
+pub use expression::{Expression, ExpressionContext};
+
+use cpu::{Halt, HaltFilter, CPU};
+use memory::BusObserver;
+use ppu::PPU;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A condition that pauses the NES
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Breakpoint {
+    Execute(u16),
+    Read(u16, u16), // CPU reads from this (inclusive) range, after mirroring, e.g. $2000-$2007
+    Write(u16, u16),
+    VramRead(u16, u16), // PPU memory accessed through PPUDATA
+    VramWrite(u16, u16),
+    Interrupt(Interrupt),
+}
+
+impl Breakpoint {
+    fn matches_access(&self, addr: u16, is_write: bool) -> bool {
+        match *self {
+            Breakpoint::Read(start, end) | Breakpoint::VramRead(start, end) if !is_write => {
+                (start..=end).contains(&addr)
+            }
+            Breakpoint::Write(start, end) | Breakpoint::VramWrite(start, end) if is_write => {
+                (start..=end).contains(&addr)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Execute(addr) => write!(f, "execute ${:04X}", addr),
+            Breakpoint::Read(start, end) => write!(f, "read ${:04X}-${:04X}", start, end),
+            Breakpoint::Write(start, end) => write!(f, "write ${:04X}-${:04X}", start, end),
+            Breakpoint::VramRead(start, end) => {
+                write!(f, "VRAM read ${:04X}-${:04X}", start, end)
+            }
+            Breakpoint::VramWrite(start, end) => {
+                write!(f, "VRAM write ${:04X}-${:04X}", start, end)
+            }
+            Breakpoint::Interrupt(interrupt) => write!(f, "{:?}", interrupt),
+        }
+    }
+}
+
+/// A breakpoint set on the NES, which only pauses it if its condition is true
+#[derive(Clone, Debug)]
+pub struct BreakpointEntry {
+    pub id: usize,
+    pub breakpoint: Breakpoint,
+    pub condition: Option<Expression>,
+    pub hits: u64, // The number of times it has paused the NES
+}
+
+impl BreakpointEntry {
+    fn passes(&self, context: &ExpressionContext) -> bool {
+        self.condition
+            .as_ref()
+            .is_none_or(|condition| condition.is_true(context))
+    }
+}
+
+/// The breakpoint that paused the NES, and what triggered it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BreakpointHit {
+    pub id: usize,
+    pub breakpoint: Breakpoint,
+    pub pc: u16, // The instruction that made the access or ran during DMA, or is about to execute
+    pub access: Option<(u16, u8)>, // The address and data of a read or write
+}
+
+impl fmt::Display for BreakpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "breakpoint {} ({}) hit at ${:04X}",
+            self.id, self.breakpoint, self.pc
+        )?;
+        if let Some((addr, data)) = self.access {
+            write!(f, ": ${:04X} = ${:02X}", addr, data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the accesses on one bus against the read and write breakpoints for that bus
+struct AccessWatcher {
+    breakpoints: Vec<(usize, Breakpoint)>,
+    hits: Vec<BreakpointHit>, // Every match in the current instruction, whose conditions are checked after it
+}
+
+impl AccessWatcher {
+    fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            hits: Vec::new(),
+        }
+    }
+
+    fn check(&mut self, addr: u16, data: u8, is_write: bool) {
+        for &(id, breakpoint) in &self.breakpoints {
+            if breakpoint.matches_access(addr, is_write) {
+                self.hits.push(BreakpointHit {
+                    id,
+                    breakpoint,
+                    pc: 0, // Filled in once the instruction is known
+                    access: Some((addr, data)),
+                });
+            }
+        }
+    }
+}
+
+impl BusObserver for AccessWatcher {
+    fn on_read(&mut self, addr: u16, data: u8) {
+        self.check(addr, data, false);
+    }
+
+    fn on_write(&mut self, addr: u16, data: u8) {
+        self.check(addr, data, true);
+    }
+}
+
+/// Checks the conditions of execute and interrupt breakpoints as the CPU reaches them,
+/// so that it only halts for those whose condition is true
+struct HaltChecker {
+    breakpoints: Vec<(usize, Breakpoint, Option<Expression>)>,
+    ppu: Rc<RefCell<PPU>>,
+    hit: Option<usize>, // The breakpoint the CPU last halted for
+}
+
+impl HaltChecker {
+    fn new(ppu: Rc<RefCell<PPU>>) -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            ppu,
+            hit: None,
+        }
+    }
+}
+
+impl HaltFilter for HaltChecker {
+    fn should_halt(&mut self, cpu: &CPU, halt: Halt) -> bool {
+        let breakpoint = match halt {
+            Halt::Breakpoint(addr) => Breakpoint::Execute(addr),
+            Halt::Interrupt(interrupt) => Breakpoint::Interrupt(interrupt),
+        };
+        let ppu = self.ppu.borrow();
+        let context = ExpressionContext {
+            registers: cpu.registers(),
+            scanline: ppu.scanline(),
+            dot: ppu.dot(),
+            frame: ppu.frame(),
+            access: None,
+            memory: cpu,
+        };
+        self.hit = self
+            .breakpoints
+            .iter()
+            .find(|(_, other, condition)| {
+                *other == breakpoint
+                    && condition
+                        .as_ref()
+                        .is_none_or(|condition| condition.is_true(&context))
+            })
+            .map(|&(id, ..)| id);
+        self.hit.is_some()
+    }
+}
+
+/// Keeps the breakpoints set on the NES in sync with the CPU and the buses,
+/// which are only watched while they have breakpoints
+pub(crate) struct Debugger {
+    next_id: usize,
+    breakpoints: Vec<BreakpointEntry>,
+    cpu_watcher: Rc<RefCell<AccessWatcher>>,
+    vram_watcher: Rc<RefCell<AccessWatcher>>,
+    halt_checker: Rc<RefCell<HaltChecker>>,
+}
+
+impl Debugger {
+    pub fn new(ppu: Rc<RefCell<PPU>>) -> Self {
+        Self {
+            next_id: 1,
+            breakpoints: Vec::new(),
+            cpu_watcher: Rc::new(RefCell::new(AccessWatcher::new())),
+            vram_watcher: Rc::new(RefCell::new(AccessWatcher::new())),
+            halt_checker: Rc::new(RefCell::new(HaltChecker::new(ppu))),
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[BreakpointEntry] {
+        &self.breakpoints
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint, cpu: &mut CPU, ppu: &mut PPU) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(BreakpointEntry {
+            id,
+            breakpoint,
+            condition: None,
+            hits: 0,
+        });
+
+        match breakpoint {
+            Breakpoint::Execute(addr) => {
+                self.check_halts(id, breakpoint, cpu);
+                cpu.add_breakpoint(addr);
+            }
+            Breakpoint::Interrupt(interrupt) => {
+                self.check_halts(id, breakpoint, cpu);
+                cpu.set_halt_on_interrupt(interrupt, true);
+            }
+            Breakpoint::Read(..) | Breakpoint::Write(..) => {
+                let mut watcher = self.cpu_watcher.borrow_mut();
+                if watcher.breakpoints.is_empty() {
+                    cpu.add_bus_observer(self.cpu_watcher.clone());
+                }
+                watcher.breakpoints.push((id, breakpoint));
+            }
+            Breakpoint::VramRead(..) | Breakpoint::VramWrite(..) => {
+                let mut watcher = self.vram_watcher.borrow_mut();
+                if watcher.breakpoints.is_empty() {
+                    ppu.add_vram_observer(self.vram_watcher.clone());
+                }
+                watcher.breakpoints.push((id, breakpoint));
+            }
+        }
+        id
+    }
+
+    /// Have the CPU check a breakpoint's condition before it halts for it
+    fn check_halts(&self, id: usize, breakpoint: Breakpoint, cpu: &mut CPU) {
+        let mut checker = self.halt_checker.borrow_mut();
+        if checker.breakpoints.is_empty() {
+            cpu.set_halt_filter(Some(self.halt_checker.clone()));
+        }
+        checker.breakpoints.push((id, breakpoint, None));
+    }
+
+    pub fn remove(&mut self, id: usize, cpu: &mut CPU, ppu: &mut PPU) -> bool {
+        let index = match self.breakpoints.iter().position(|entry| entry.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        let breakpoint = self.breakpoints.remove(index).breakpoint;
+
+        if let Breakpoint::Execute(..) | Breakpoint::Interrupt(..) = breakpoint {
+            let mut checker = self.halt_checker.borrow_mut();
+            checker.breakpoints.retain(|&(bp_id, ..)| bp_id != id);
+            if checker.breakpoints.is_empty() {
+                cpu.set_halt_filter(None);
+            }
+        }
+        match breakpoint {
+            Breakpoint::Execute(addr) => cpu.remove_breakpoint(addr),
+            Breakpoint::Interrupt(interrupt) => {
+                let still_set = self
+                    .breakpoints
+                    .iter()
+                    .any(|entry| entry.breakpoint == breakpoint);
+                cpu.set_halt_on_interrupt(interrupt, still_set);
+            }
+            Breakpoint::Read(..) | Breakpoint::Write(..) => {
+                let mut watcher = self.cpu_watcher.borrow_mut();
+                watcher.breakpoints.retain(|&(bp_id, _)| bp_id != id);
+                if watcher.breakpoints.is_empty() {
+                    let observer: Rc<RefCell<dyn BusObserver>> = self.cpu_watcher.clone();
+                    cpu.remove_bus_observer(&observer);
+                }
+            }
+            Breakpoint::VramRead(..) | Breakpoint::VramWrite(..) => {
+                let mut watcher = self.vram_watcher.borrow_mut();
+                watcher.breakpoints.retain(|&(bp_id, _)| bp_id != id);
+                if watcher.breakpoints.is_empty() {
+                    let observer: Rc<RefCell<dyn BusObserver>> = self.vram_watcher.clone();
+                    ppu.remove_vram_observer(&observer);
+                }
+            }
+        }
+        true
+    }
+
+    /// Only pause for a breakpoint when a condition is true, or always if None
+    pub fn set_condition(&mut self, id: usize, condition: Option<Expression>) -> bool {
+        match self.breakpoints.iter_mut().find(|entry| entry.id == id) {
+            Some(entry) => {
+                let mut checker = self.halt_checker.borrow_mut();
+                if let Some(checked) = checker
+                    .breakpoints
+                    .iter_mut()
+                    .find(|(bp_id, ..)| *bp_id == id)
+                {
+                    checked.2 = condition.clone();
+                }
+                entry.condition = condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget accesses made outside of emulation, like a debugger editing memory
+    pub fn discard_access_hits(&mut self) {
+        self.cpu_watcher.borrow_mut().hits.clear();
+        self.vram_watcher.borrow_mut().hits.clear();
+    }
+
+    /// Find the breakpoint, if any, hit by the CPU's last tick whose condition is true,
+    /// including reads by OAM DMA and the DMC during it.
+    /// Conditions on reads and writes see the registers as they are after the instruction,
+    /// while the CPU checks the others itself, before it halts.
+    pub fn take_hit(&mut self, cpu: &mut CPU, ppu: &PPU) -> Option<BreakpointHit> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+
+        let halt = cpu.take_halt();
+        let mut context = ExpressionContext {
+            registers: cpu.registers(),
+            scanline: ppu.scanline(),
+            dot: ppu.dot(),
+            frame: ppu.frame(),
+            access: None,
+            memory: &*cpu,
+        };
+
+        if halt.is_some() {
+            let id = self.halt_checker.borrow_mut().hit.take()?;
+            let entry = self.breakpoints.iter_mut().find(|entry| entry.id == id)?;
+            entry.hits += 1;
+            return Some(BreakpointHit {
+                id,
+                breakpoint: entry.breakpoint,
+                pc: context.registers.pc,
+                access: None,
+            });
+        }
+
+        let mut access_hits = std::mem::take(&mut self.cpu_watcher.borrow_mut().hits);
+        access_hits.append(&mut self.vram_watcher.borrow_mut().hits);
+        for hit in access_hits {
+            context.access = hit.access;
+            if let Some(entry) = self
+                .breakpoints
+                .iter_mut()
+                .find(|entry| entry.id == hit.id && entry.passes(&context))
+            {
+                entry.hits += 1;
+                return Some(BreakpointHit {
+                    pc: cpu.instruction_pc(),
+                    ..hit
+                });
+            }
+        }
+        None
+    }
+}
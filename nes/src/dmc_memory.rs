@@ -0,0 +1,31 @@
+use crate::cartridge::Cartridge;
+use cpu::CPU;
+use memory::Memory;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The DMC's view of memory, which reads samples over the CPU's bus, so observers see them,
+/// and tells the cartridge which bytes are samples
+pub struct DMCMemory {
+    cpu: Rc<RefCell<CPU>>,
+    cart: Rc<RefCell<Cartridge>>,
+}
+
+impl DMCMemory {
+    pub fn new(cpu: Rc<RefCell<CPU>>, cart: Rc<RefCell<Cartridge>>) -> Self {
+        Self { cpu, cart }
+    }
+}
+
+impl Memory for DMCMemory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.cart.borrow_mut().log_sample(addr);
+        self.cpu.borrow_mut().read(addr)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.cpu.borrow().peek(addr)
+    }
+
+    fn write(&mut self, _addr: u16, _data: u8) {}
+}
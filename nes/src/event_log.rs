@@ -0,0 +1,112 @@
+use memory::BusObserver;
+use ppu::PPU;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The event viewer grid has a cell for every PPU dot in a frame
+// https://wiki.nesdev.com/w/index.php/PPU_rendering
+pub const DOTS_PER_SCANLINE: usize = 341;
+pub const SCANLINES_PER_FRAME: usize = 262;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    PpuRegisterWrite,    // $2000-$2007, and OAMDMA at $4014
+    ApuRegisterWrite,    // $4000-$4017, except OAMDMA and the controller strobe
+    MapperRegisterWrite, // $4020-$5FFF and $8000-$FFFF
+    PpuStatusRead,       // $2002, which acknowledges vblank and resets the write latch
+}
+
+impl EventKind {
+    /// The kind of event an access is, if it's one that gets recorded
+    fn of_access(addr: u16, write: bool) -> Option<Self> {
+        match (addr, write) {
+            (0x2002, false) => Some(EventKind::PpuStatusRead),
+            (_, false) => None,
+            (0x2000..=0x2007, _) | (0x4014, _) => Some(EventKind::PpuRegisterWrite),
+            (0x4016, _) => None,
+            (0x4000..=0x4017, _) => Some(EventKind::ApuRegisterWrite),
+            (0x4020..=0x5FFF, _) | (0x8000..=0xFFFF, _) => Some(EventKind::MapperRegisterWrite),
+            _ => None,
+        }
+    }
+
+    /// The color that an event viewer draws this kind of event in, as 0xRRGGBB
+    pub fn color(self) -> u32 {
+        match self {
+            EventKind::PpuRegisterWrite => 0xFF5050,
+            EventKind::ApuRegisterWrite => 0x50C0FF,
+            EventKind::MapperRegisterWrite => 0x50FF50,
+            EventKind::PpuStatusRead => 0xFFD040,
+        }
+    }
+}
+
+/// A CPU access to a register, and the PPU position when it happened
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub addr: u16,
+    pub value: u8,
+    pub scanline: u16,
+    pub dot: u16,
+}
+
+/// Records register accesses on the CPU bus, one frame at a time,
+/// with frames ending as vblank starts, like NES::get_new_frame
+pub struct EventLog {
+    ppu: Rc<RefCell<PPU>>,
+    events: Vec<Event>,
+    last_frame: Vec<Event>,
+}
+
+impl EventLog {
+    pub(crate) fn new(ppu: Rc<RefCell<PPU>>) -> Self {
+        Self {
+            ppu,
+            events: Vec::new(),
+            last_frame: Vec::new(),
+        }
+    }
+
+    /// The events so far in the frame being drawn
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// The events of the last complete frame
+    pub fn last_frame(&self) -> &[Event] {
+        &self.last_frame
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.last_frame = std::mem::take(&mut self.events);
+    }
+
+    fn record(&mut self, addr: u16, value: u8, write: bool) {
+        let kind = match EventKind::of_access(addr, write) {
+            Some(kind) => kind,
+            None => return,
+        };
+        // The PPU is only busy during OAM DMA, whose reads aren't recorded anyway
+        if let Ok(ppu) = self.ppu.try_borrow() {
+            self.events.push(Event {
+                kind,
+                addr,
+                value,
+                scanline: ppu.scanline(),
+                dot: ppu.dot(),
+            });
+        }
+    }
+}
+
+impl BusObserver for EventLog {
+    fn on_read(&mut self, addr: u16, data: u8) {
+        self.record(addr, data, false);
+    }
+
+    fn on_write(&mut self, addr: u16, data: u8) {
+        self.record(addr, data, true);
+    }
+}
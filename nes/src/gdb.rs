@@ -0,0 +1,554 @@
+use crate::debugger::{Breakpoint, BreakpointHit};
+use crate::{Registers, NES};
+
+use std::io;
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+// https://sourceware.org/gdb/current/onlinedocs/gdb/Remote-Protocol.html
+const PACKET_SIZE: usize = 0x1000;
+const INTERRUPT: u8 = 0x03;
+
+// Signals in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+// GDB has no built-in 6502 target, so frontends learn the registers from this description
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.kindnes.mos6502">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="s" bitsize="8" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// A breakpoint or watchpoint set by the client, and the NES breakpoints that implement it
+struct GdbBreakpoint {
+    kind: u8, // The Z packet type: 0 software, 1 hardware, 2 write, 3 read, 4 access
+    addr: u16,
+    len: u16,
+    ids: Vec<usize>,
+}
+
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    no_ack: bool,
+}
+
+/// A GDB remote serial protocol server that debugs an NES over TCP
+pub struct GdbServer {
+    listener: TcpListener,
+    connection: Option<Connection>,
+    breakpoints: Vec<GdbBreakpoint>,
+    running: bool, // Whether the client is waiting for the NES to stop
+    detached: bool,
+}
+
+impl GdbServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            connection: None,
+            breakpoints: Vec::new(),
+            running: false,
+            detached: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Whether a client connected and has since detached or killed the session
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    /// Accept a client and handle any packets it sent, without blocking.
+    /// The NES is paused while the client is attached, unless the client continues it.
+    pub fn poll(&mut self, nes: &mut NES) -> io::Result<()> {
+        if self.connection.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.connection = Some(Connection {
+                        stream,
+                        buffer: Vec::new(),
+                        no_ack: false,
+                    });
+                    self.running = false;
+                    self.detached = false;
+                    nes.paused = true;
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+
+        if self.running {
+            if let Some(hit) = nes.take_break_hit() {
+                self.running = false;
+                let reply = self.stop_reply(&hit);
+                self.send(&reply)?;
+            }
+        }
+
+        if !self.receive()? {
+            self.disconnect(nes);
+            return Ok(());
+        }
+        while let Some(packet) = self.next_packet()? {
+            match packet {
+                Packet::Interrupt => {
+                    nes.paused = true;
+                    if self.running {
+                        self.running = false;
+                        self.send(&format!("S{:02x}", SIGINT))?;
+                    }
+                }
+                Packet::Command(command) => {
+                    if let Some(reply) = self.handle(&command, nes) {
+                        self.send(&reply)?;
+                    }
+                    if self.connection.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for a client and serve it, running the NES while it's continued, until the client leaves
+    pub fn run(&mut self, nes: &mut NES) -> io::Result<()> {
+        while !self.detached {
+            self.poll(nes)?;
+            if nes.paused || !self.is_connected() {
+                thread::sleep(Duration::from_millis(1));
+            } else {
+                for _ in 0..1000 {
+                    nes.tick();
+                    if nes.paused {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, command: &str, nes: &mut NES) -> Option<String> {
+        let mut chars = command.chars();
+        let kind = match chars.next() {
+            Some(kind) => kind,
+            None => return Some(String::new()), // An empty packet isn't a command we support
+        };
+        let args = chars.as_str();
+        let reply = match kind {
+            '?' => format!("S{:02x}", SIGTRAP),
+            'g' => encode_registers(&nes.cpu_registers()),
+            'G' => match decode_registers(args) {
+                Some(registers) => {
+                    nes.set_cpu_registers(registers);
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            },
+            'p' => match usize::from_str_radix(args, 16) {
+                Ok(n) if n <= 5 => {
+                    let width = if n == 5 { 4 } else { 2 };
+                    encode_registers(&nes.cpu_registers())[2 * n..2 * n + width].to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            'P' => self.write_register(args, nes).unwrap_or("E01").to_string(),
+            'm' => match parse_addr_len(args) {
+                Some((addr, len)) if len <= PACKET_SIZE / 2 => (0..len)
+                    .map(|i| format!("{:02x}", nes.peek_cpu_memory(addr.wrapping_add(i as u16))))
+                    .collect(),
+                _ => "E01".to_string(),
+            },
+            'M' => self.write_memory(args, nes).unwrap_or("E01").to_string(),
+            'c' | 's' => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(pc) => {
+                            let mut registers = nes.cpu_registers();
+                            registers.pc = pc;
+                            nes.set_cpu_registers(registers);
+                        }
+                        Err(_) => return Some("E01".to_string()),
+                    }
+                }
+                nes.take_break_hit();
+                if kind == 'c' {
+                    self.running = true;
+                    nes.resume();
+                    return None; // The reply is sent when the NES stops
+                }
+                nes.step_instruction();
+                match nes.take_break_hit() {
+                    Some(hit) if hit.access.is_some() => self.stop_reply(&hit),
+                    _ => format!("S{:02x}", SIGTRAP),
+                }
+            }
+            'Z' | 'z' => self.toggle_breakpoint(kind == 'Z', args, nes).to_string(),
+            'H' | 'T' => "OK".to_string(), // There's only one thread
+            'D' => {
+                self.running = false;
+                self.clear_breakpoints(nes);
+                nes.paused = false;
+                let _ = self.send("OK");
+                self.disconnect(nes);
+                return None;
+            }
+            'k' => {
+                self.clear_breakpoints(nes);
+                self.disconnect(nes);
+                return None;
+            }
+            'q' | 'Q' => self.query(command),
+            _ => String::new(), // An empty reply means the command isn't supported
+        };
+        Some(reply)
+    }
+
+    fn query(&self, command: &str) -> String {
+        if command.starts_with("qSupported") {
+            format!(
+                "PacketSize={:x};QStartNoAckMode+;qXfer:features:read+;hwbreak+",
+                PACKET_SIZE
+            )
+        } else if command == "QStartNoAckMode" {
+            "OK".to_string()
+        } else if command == "qAttached" {
+            "1".to_string()
+        } else if command == "qC" {
+            "QC1".to_string()
+        } else if command == "qfThreadInfo" {
+            "m1".to_string()
+        } else if command == "qsThreadInfo" {
+            "l".to_string()
+        } else if let Some(args) = command.strip_prefix("qXfer:features:read:target.xml:") {
+            match parse_addr_len(args) {
+                Some((offset, _)) if offset as usize > TARGET_XML.len() => "E00".to_string(),
+                Some((offset, len)) => {
+                    let offset = offset as usize;
+                    let end = offset.saturating_add(len).min(TARGET_XML.len());
+                    let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+                    format!("{}{}", prefix, &TARGET_XML[offset..end])
+                }
+                None => "E01".to_string(),
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    fn write_register(&self, args: &str, nes: &mut NES) -> Option<&'static str> {
+        let (n, value) = args.split_once('=')?;
+        let n = usize::from_str_radix(n, 16).ok()?;
+        let mut encoded = encode_registers(&nes.cpu_registers());
+        let width = if n == 5 { 4 } else { 2 };
+        if n > 5 || value.len() != width {
+            return None;
+        }
+        encoded.replace_range(2 * n..2 * n + width, value);
+        nes.set_cpu_registers(decode_registers(&encoded)?);
+        Some("OK")
+    }
+
+    fn write_memory(&self, args: &str, nes: &mut NES) -> Option<&'static str> {
+        let (addr_len, data) = args.split_once(':')?;
+        let (addr, len) = parse_addr_len(addr_len)?;
+        let bytes = decode_hex(data)?;
+        if bytes.len() != len {
+            return None;
+        }
+        // Registers can't be written without side effects, so only memory can be changed
+        let mut written = true;
+        for (i, byte) in bytes.into_iter().enumerate() {
+            written &= nes.poke_cpu_memory(addr.wrapping_add(i as u16), byte);
+        }
+        if written {
+            Some("OK")
+        } else {
+            None
+        }
+    }
+
+    fn toggle_breakpoint(&mut self, insert: bool, args: &str, nes: &mut NES) -> &'static str {
+        let mut parts = args.split(',');
+        let (kind, addr, len) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(kind), Some(addr), Some(len)) => {
+                match (
+                    kind.parse::<u8>(),
+                    u16::from_str_radix(addr, 16),
+                    u16::from_str_radix(len, 16),
+                ) {
+                    (Ok(kind), Ok(addr), Ok(len)) => (kind, addr, len.max(1)),
+                    _ => return "E01",
+                }
+            }
+            _ => return "E01",
+        };
+
+        if !insert {
+            if let Some(index) = self
+                .breakpoints
+                .iter()
+                .position(|bp| bp.kind == kind && bp.addr == addr && bp.len == len)
+            {
+                for id in self.breakpoints.remove(index).ids {
+                    nes.remove_breakpoint(id);
+                }
+            }
+            return "OK";
+        }
+
+        let end = addr.saturating_add(len - 1);
+        let nes_breakpoints = match kind {
+            0 | 1 => vec![Breakpoint::Execute(addr)],
+            2 => vec![Breakpoint::Write(addr, end)],
+            3 => vec![Breakpoint::Read(addr, end)],
+            4 => vec![Breakpoint::Read(addr, end), Breakpoint::Write(addr, end)],
+            _ => return "", // Unsupported
+        };
+        let ids = nes_breakpoints
+            .into_iter()
+            .map(|breakpoint| nes.add_breakpoint(breakpoint))
+            .collect();
+        self.breakpoints.push(GdbBreakpoint {
+            kind,
+            addr,
+            len,
+            ids,
+        });
+        "OK"
+    }
+
+    fn stop_reply(&self, hit: &BreakpointHit) -> String {
+        let watchpoint = self
+            .breakpoints
+            .iter()
+            .find(|bp| bp.kind >= 2 && bp.ids.contains(&hit.id));
+        match (watchpoint, hit.access) {
+            (Some(watchpoint), Some((addr, _))) => {
+                let name = match watchpoint.kind {
+                    2 => "watch",
+                    3 => "rwatch",
+                    _ => "awatch",
+                };
+                format!("T{:02x}{}:{:04x};", SIGTRAP, name, addr)
+            }
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn clear_breakpoints(&mut self, nes: &mut NES) {
+        for breakpoint in self.breakpoints.drain(..) {
+            for id in breakpoint.ids {
+                nes.remove_breakpoint(id);
+            }
+        }
+    }
+
+    fn disconnect(&mut self, nes: &mut NES) {
+        self.clear_breakpoints(nes);
+        self.connection = None;
+        self.running = false;
+        self.detached = true;
+    }
+
+    /// Read whatever the client has sent, returning false if it hung up
+    fn receive(&mut self) -> io::Result<bool> {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => return Ok(false),
+        };
+        let mut chunk = [0; 1024];
+        loop {
+            match connection.stream.read(&mut chunk) {
+                Ok(0) => return Ok(false),
+                Ok(n) => connection.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+                Err(err) if err.kind() == io::ErrorKind::ConnectionReset => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Take the next complete packet from the buffer, acknowledging it
+    fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => return Ok(None),
+        };
+        loop {
+            match connection.buffer.first() {
+                None => return Ok(None),
+                Some(&INTERRUPT) => {
+                    connection.buffer.remove(0);
+                    return Ok(Some(Packet::Interrupt));
+                }
+                Some(b'$') => break,
+                Some(_) => {
+                    // Acknowledgements, and noise between packets
+                    connection.buffer.remove(0);
+                }
+            }
+        }
+
+        let end = match connection.buffer.iter().position(|&byte| byte == b'#') {
+            Some(end) if connection.buffer.len() >= end + 3 => end,
+            _ => return Ok(None), // Incomplete
+        };
+        let packet: Vec<u8> = connection.buffer.drain(..end + 3).collect();
+        let data = unescape(&packet[1..end]);
+        let checksum = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        let valid = checksum == Some(packet[1..end].iter().fold(0u8, |a, &b| a.wrapping_add(b)));
+
+        if !connection.no_ack {
+            connection
+                .stream
+                .write_all(if valid { b"+" } else { b"-" })?;
+        }
+        if !valid {
+            return Ok(None);
+        }
+        let command = String::from_utf8_lossy(&data).into_owned();
+        if command == "QStartNoAckMode" {
+            // The reply to this packet is the last one acknowledged
+            connection.no_ack = true;
+        }
+        Ok(Some(Packet::Command(command)))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        if let Some(connection) = &mut self.connection {
+            let escaped = escape(data.as_bytes());
+            let checksum = escaped.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+            let mut packet = Vec::with_capacity(escaped.len() + 4);
+            packet.push(b'$');
+            packet.extend_from_slice(&escaped);
+            packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+
+            // The stream is non-blocking, but replies are small enough that waiting is rare
+            let mut written = 0;
+            while written < packet.len() {
+                match connection.stream.write(&packet[written..]) {
+                    Ok(n) => written += n,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Packet {
+    Interrupt,
+    Command(String),
+}
+
+// Registers are sent as A, X, Y, P, S, then PC in little-endian order
+fn encode_registers(registers: &Registers) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        registers.a,
+        registers.x,
+        registers.y,
+        registers.p,
+        registers.s,
+        registers.pc & 0xFF,
+        registers.pc >> 8
+    )
+}
+
+fn decode_registers(hex: &str) -> Option<Registers> {
+    let bytes = decode_hex(hex)?;
+    if bytes.len() != 7 {
+        return None;
+    }
+    Some(Registers {
+        a: bytes[0],
+        x: bytes[1],
+        y: bytes[2],
+        p: bytes[3],
+        s: bytes[4],
+        pc: ((bytes[6] as u16) << 8) | (bytes[5] as u16),
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Some(((hex_digit(*high)? << 4) | hex_digit(*low)?) as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(digit: u8) -> Option<u32> {
+    (digit as char).to_digit(16)
+}
+
+/// Parse "addr,length" in hex
+fn parse_addr_len(args: &str) -> Option<(u16, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = u32::from_str_radix(addr, 16).ok()?;
+    if addr > 0xFFFF {
+        return None;
+    }
+    Some((addr as u16, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        if let b'#' | b'$' | b'}' | b'*' = byte {
+            escaped.push(b'}');
+            escaped.push(byte ^ 0x20);
+        } else {
+            escaped.push(byte);
+        }
+    }
+    escaped
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'}' {
+            if let Some(&next) = bytes.next() {
+                unescaped.push(next ^ 0x20);
+            }
+        } else {
+            unescaped.push(byte);
+        }
+    }
+    unescaped
+}
@@ -1,23 +1,52 @@
 mod cartridge;
+pub mod code_data_log;
 mod controllers;
 mod cpu_mapped_registers;
+pub mod debugger;
+mod dmc_memory;
+pub mod event_log;
+pub mod gdb;
 mod nametable_memory;
 mod palette_ram;
+pub mod profiler;
+mod save_state;
+pub mod script;
+pub mod symbols;
+pub mod trace;
+pub mod video;
 
 use crate::cartridge::Cartridge;
+use crate::code_data_log::CodeDataLog;
 use crate::controllers::Controller;
 use crate::controllers::NoController;
 use crate::controllers::StandardController;
 use crate::cpu_mapped_registers::CPUMappedRegisters;
+use crate::debugger::{Breakpoint, BreakpointEntry, BreakpointHit, Debugger, Expression};
+use crate::dmc_memory::DMCMemory;
+use crate::event_log::EventLog;
 use crate::nametable_memory::NametableMemory;
 use crate::palette_ram::PaletteRAM;
+use crate::profiler::Profiler;
+pub use crate::save_state::SaveState;
+use crate::symbols::{SourceLine, SymbolTable};
+use crate::trace::{TraceContext, TraceLogger};
+use crate::video::{Palette, PixelFormat};
+
+pub use cpu::{CallFrame, Registers, TraceEntry};
+pub use ppu::{
+    pixel_to_rgb, Layers, RgbImage, SpriteInfo, NAMETABLE_VIEW_HEIGHT, NAMETABLE_VIEW_WIDTH,
+    PATTERN_TABLE_VIEW_SIZE, SPRITE_VIEW_HEIGHT, SPRITE_VIEW_WIDTH,
+};
 
 use apu::APU;
-use cpu::CPU;
+use cpu::{Interrupt, CPU};
 use memory::mmu::MMU;
+use memory::ram::RAM;
+use memory::{BusObserver, Memory};
 use ppu::PPU;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::fs::File;
+use std::io;
 use std::rc::Rc;
 
 pub struct NES {
@@ -25,8 +54,19 @@ pub struct NES {
     ppu: Rc<RefCell<PPU>>,
     apu: Rc<RefCell<APU>>,
     cart: Rc<RefCell<Cartridge>>,
+    ram: Rc<RefCell<RAM>>,
+    nametables: Rc<RefCell<NametableMemory>>,
+    palette_ram: Rc<RefCell<PaletteRAM>>,
     joy1: Rc<RefCell<dyn Controller>>,
     joy2: Rc<RefCell<dyn Controller>>,
+    trace_logger: Option<TraceLogger>,
+    profiler: Option<Profiler>,
+    event_log: Option<Rc<RefCell<EventLog>>>,
+    symbols: Option<SymbolTable>,
+    debugger: Debugger,
+    break_hit: Option<BreakpointHit>,
+    palette: Palette,
+    last_frame: Box<[[u16; 256]; 240]>, // The PPU draws over its framebuffer as soon as it's done
 
     pub paused: bool,
 }
@@ -38,15 +78,14 @@ impl NES {
         let joy2 = Rc::new(RefCell::new(NoController::new()));
 
         // https://wiki.nesdev.com/w/index.php/PPU_memory_map
+        let nametables = Rc::new(RefCell::new(NametableMemory::new(cart.clone())));
+        let palette_ram = Rc::new(RefCell::new(PaletteRAM::new()));
         let mut ppu_mmu = MMU::new();
         ppu_mmu.map(0x0000, 0x1FFF, cart.clone()); // Pattern tables
-        ppu_mmu.map(
-            0x2000,
-            0x3EFF,
-            Rc::new(RefCell::new(NametableMemory::new(cart.clone()))),
-        );
-        ppu_mmu.map(0x3F00, 0x3FFF, Rc::new(RefCell::new(PaletteRAM::new()))); // Palette RAM indices
+        ppu_mmu.map(0x2000, 0x3EFF, nametables.clone());
+        ppu_mmu.map(0x3F00, 0x3FFF, palette_ram.clone()); // Palette RAM indices
         let ppu = Rc::new(RefCell::new(PPU::new(Box::from(ppu_mmu))));
+        ppu.borrow_mut().set_bus_observer(cart.clone()); // Mappers watch every PPU dot
 
         let apu = Rc::new(RefCell::new(APU::new()));
 
@@ -58,8 +97,9 @@ impl NES {
         )));
 
         // https://wiki.nesdev.com/w/index.php/CPU_memory_map
+        let ram = Rc::new(RefCell::new(RAM::new(0x0800, 0x0000)));
         let mut cpu_mmu = MMU::new();
-        cpu_mmu.map_ram_mirrored(0x0000, 0x1FFF, 0x0800);
+        cpu_mmu.map_mirrored(0x0000, 0x1FFF, 0x0800, ram.clone());
         cpu_mmu.map_mirrored(0x2000, 0x3FFF, 0x0008, ppu.clone()); // PPU registers
         cpu_mmu.map(0x4000, 0x401F, cpu_mapped_registers.clone()); // NES APU and I/O registers
         cpu_mmu.map(0x4020, 0xFFFF, cart.clone());
@@ -67,15 +107,29 @@ impl NES {
         cpu.borrow_mut().reset();
 
         ppu.borrow_mut().set_dma(cpu.clone());
-        apu.borrow_mut().set_dma(cpu.clone());
+        let dmc_memory = DMCMemory::new(cpu.clone(), cart.clone());
+        apu.borrow_mut().set_dma(Rc::new(RefCell::new(dmc_memory)));
+
+        let debugger = Debugger::new(ppu.clone());
 
         Self {
             cpu,
             ppu,
             apu,
             cart,
+            ram,
+            nametables,
+            palette_ram,
             joy1,
             joy2,
+            trace_logger: None,
+            profiler: None,
+            event_log: None,
+            symbols: None,
+            debugger,
+            break_hit: None,
+            palette: Palette::new(),
+            last_frame: Box::new([[0; 256]; 240]),
             paused: false,
         }
     }
@@ -112,8 +166,29 @@ impl NES {
         }
 
         self.ppu.borrow_mut().frame_ready = false;
-        if let Some(log) = self.cpu.borrow_mut().tick() {
-            println!("{}", log);
+        let trace_option = self.cpu.borrow_mut().tick();
+        if self.cpu.borrow().is_halted() {
+            self.check_breakpoints();
+            return; // The CPU halted before the instruction, so nothing else moves this tick
+        }
+        if let (Some(mut entry), Some(logger)) = (trace_option, &mut self.trace_logger) {
+            if let Some(symbols) = self.symbols.as_ref().filter(|_| logger.labels) {
+                let cart = self.cart.borrow();
+                symbols.label_operand(&mut entry, |addr| cart.prg_bank(addr));
+            }
+            let ppu = self.ppu.borrow();
+            let context = TraceContext {
+                scanline: ppu.scanline(),
+                dot: ppu.dot(),
+                frame: ppu.frame(),
+                prg_bank: self.cart.borrow().prg_bank(entry.registers.pc),
+            };
+            logger.log(&entry, &context);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            let cpu = self.cpu.borrow();
+            let cart = self.cart.borrow();
+            profiler.update(cpu.call_stack(), cpu.cycles(), |addr| cart.prg_bank(addr));
         }
 
         self.apu.borrow_mut().tick();
@@ -124,20 +199,463 @@ impl NES {
         }
 
         self.ppu.borrow_mut().cpu_cycle();
-        self.cart.borrow_mut().cycle(); // TODO: Probably per-ppu tick for some mappers
+        self.cart.borrow_mut().cycle();
         if self.ppu.borrow().nmi {
             self.ppu.borrow_mut().nmi = false;
             self.cpu.borrow_mut().nmi_timer = 2;
         } else if self.cart.borrow_mut().check_irq() || self.apu.borrow_mut().check_irq() {
             self.cpu.borrow_mut().irq();
         }
+
+        if self.ppu.borrow().frame_ready {
+            *self.last_frame = self.ppu.borrow().framebuffer;
+            if let Some(profiler) = &mut self.profiler {
+                profiler.end_frame(self.cpu.borrow().cycles());
+            }
+            if let Some(event_log) = &self.event_log {
+                event_log.borrow_mut().end_frame();
+            }
+        }
+
+        // After OAM DMA and DMC sample reads, which the instruction running now is blamed for
+        self.check_breakpoints();
+    }
+
+    fn check_breakpoints(&mut self) {
+        let hit = self
+            .debugger
+            .take_hit(&mut self.cpu.borrow_mut(), &self.ppu.borrow());
+        if let Some(hit) = hit {
+            self.paused = true;
+            self.break_hit = Some(hit);
+        }
+    }
+
+    /// Start logging each instruction with the given logger, or stop logging if None
+    pub fn set_trace_logger(&mut self, logger: Option<TraceLogger>) {
+        self.cpu.borrow_mut().set_tracing(logger.is_some());
+        if let Some(old_logger) = &mut self.trace_logger {
+            old_logger.flush();
+        }
+        self.trace_logger = logger;
+    }
+
+    /// Start recording how each byte of the cartridge's ROM is used, from a blank log
+    pub fn start_code_data_log(&mut self) -> Result<(), &'static str> {
+        let mut cart = self.cart.borrow_mut();
+        let log = CodeDataLog::new(cart.prg_rom_size(), cart.chr_rom_size());
+        cart.set_code_data_log(Some(log))
+    }
+
+    /// Continue recording into an existing .cdl file for the current ROM
+    pub fn load_code_data_log(&mut self, file: File) -> Result<(), &'static str> {
+        let mut cart = self.cart.borrow_mut();
+        let log = CodeDataLog::load(file, cart.prg_rom_size(), cart.chr_rom_size())?;
+        cart.set_code_data_log(Some(log))
+    }
+
+    pub fn save_code_data_log(&self, file: File) -> io::Result<()> {
+        match self.cart.borrow().code_data_log() {
+            Some(log) => log.save(file),
+            None => Err(io::Error::other("the code/data logger isn't running")),
+        }
+    }
+
+    /// Stop recording, returning what was recorded
+    pub fn stop_code_data_log(&mut self) -> Option<CodeDataLog> {
+        self.cart.borrow_mut().take_code_data_log()
+    }
+
+    /// Start attributing CPU cycles to subroutines with the given profiler, or stop if None
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Start recording CPU accesses to the PPU, APU and mapper registers with the PPU position
+    pub fn start_event_log(&mut self) {
+        if self.event_log.is_none() {
+            let event_log = Rc::new(RefCell::new(EventLog::new(self.ppu.clone())));
+            self.cpu.borrow_mut().add_bus_observer(event_log.clone());
+            self.event_log = Some(event_log);
+        }
+    }
+
+    pub fn stop_event_log(&mut self) {
+        if let Some(event_log) = self.event_log.take() {
+            let observer: Rc<RefCell<dyn BusObserver>> = event_log;
+            self.cpu.borrow_mut().remove_bus_observer(&observer);
+        }
+    }
+
+    pub fn event_log(&self) -> Option<Ref<'_, EventLog>> {
+        self.event_log.as_ref().map(|event_log| event_log.borrow())
+    }
+
+    /// Add a breakpoint, returning an ID that identifies it in hits and removal
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.debugger.add(
+            breakpoint,
+            &mut self.cpu.borrow_mut(),
+            &mut self.ppu.borrow_mut(),
+        )
+    }
+
+    /// Remove a breakpoint, returning whether one had the given ID
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.debugger
+            .remove(id, &mut self.cpu.borrow_mut(), &mut self.ppu.borrow_mut())
+    }
+
+    /// Only pause for a breakpoint when a condition is true, or always if None,
+    /// returning whether a breakpoint had the given ID
+    pub fn set_breakpoint_condition(&mut self, id: usize, condition: Option<Expression>) -> bool {
+        self.debugger.set_condition(id, condition)
+    }
+
+    pub fn breakpoints(&self) -> &[BreakpointEntry] {
+        self.debugger.breakpoints()
+    }
+
+    /// Take the breakpoint that most recently paused the NES, if any
+    pub fn take_break_hit(&mut self) -> Option<BreakpointHit> {
+        self.break_hit.take()
+    }
+
+    /// Unpause, running the next instruction even if there's an execute breakpoint on it,
+    /// which is where the last step or breakpoint left off
+    pub fn resume(&mut self) {
+        self.cpu.borrow_mut().resume();
+        self.paused = false;
+    }
+
+    /// Run until one instruction has executed or a breakpoint is hit, then pause
+    pub fn step_instruction(&mut self) {
+        self.resume();
+        self.run_instruction();
+    }
+
+    /// Step one instruction, halting for a breakpoint on it even if it's where the step started
+    fn run_instruction(&mut self) {
+        self.paused = false;
+        let mut started = false;
+        loop {
+            let at_boundary = self.cpu.borrow().at_instruction_boundary();
+            if started && at_boundary {
+                break;
+            }
+            started |= at_boundary;
+            self.tick();
+            if self.paused {
+                return;
+            }
+        }
+        self.paused = true;
+    }
+
+    /// Step one instruction, but run any subroutine it calls, or interrupt that fires, until it returns
+    pub fn step_over(&mut self) {
+        self.resume();
+        self.run_over();
+    }
+
+    fn run_over(&mut self) {
+        let depth = self.call_depth();
+        let mut stepped = false;
+        loop {
+            let depth_before = self.call_depth();
+            self.run_instruction();
+            if self.break_hit.is_some() {
+                return;
+            }
+
+            // An NMI or IRQ that fired first leaves the instruction to run once the handler returns
+            let interrupted = {
+                let cpu = self.cpu.borrow();
+                let call_stack = cpu.call_stack();
+                call_stack.len() > depth_before
+                    && matches!(
+                        call_stack.last().and_then(|frame| frame.interrupt),
+                        Some(Interrupt::Nmi) | Some(Interrupt::Irq)
+                    )
+            };
+            stepped |= depth_before <= depth && !interrupted;
+            if stepped && self.call_depth() <= depth {
+                return;
+            }
+        }
+    }
+
+    /// Run until the current subroutine or interrupt handler returns, or step over at the top level
+    pub fn step_out(&mut self) {
+        let depth = self.call_depth();
+        if depth == 0 {
+            self.step_over();
+            return;
+        }
+        self.step_while(|nes| nes.call_depth() >= depth);
+    }
+
+    /// Run until the instruction at an address is about to execute, or a breakpoint is hit
+    pub fn run_to(&mut self, addr: u16) {
+        self.step_while(|nes| nes.cpu_registers().pc != addr);
+    }
+
+    fn step_while(&mut self, keep_stepping: impl Fn(&NES) -> bool) {
+        self.resume();
+        loop {
+            self.run_instruction();
+            if self.break_hit.is_some() || !keep_stepping(self) {
+                return;
+            }
+        }
+    }
+
+    /// The calls and interrupts the CPU is inside of, outermost first
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        self.cpu.borrow().call_stack().to_vec()
+    }
+
+    fn call_depth(&self) -> usize {
+        self.cpu.borrow().call_stack().len()
+    }
+
+    /// Run until the start of the next source line, following calls, or until a breakpoint is hit
+    pub fn step_line(&mut self) {
+        if !self.has_source_lines() {
+            return self.step_instruction();
+        }
+        self.step_while(|nes| nes.source_line_at(nes.cpu_registers().pc).is_none());
+    }
+
+    /// Run until the start of the next source line in the current routine, or until a breakpoint is hit
+    pub fn step_line_over(&mut self) {
+        if !self.has_source_lines() {
+            return self.step_over();
+        }
+        self.resume();
+        loop {
+            self.run_over();
+            if self.break_hit.is_some() || self.source_line_at(self.cpu_registers().pc).is_some() {
+                return;
+            }
+        }
+    }
+
+    fn has_source_lines(&self) -> bool {
+        self.symbols
+            .as_ref()
+            .is_some_and(|symbols| symbols.has_source_lines())
+    }
+
+    /// Decode the instruction at an address, or None if it isn't a known opcode,
+    /// showing labels in place of addresses if symbols are loaded
+    pub fn disassemble(&self, addr: u16) -> Option<TraceEntry> {
+        let mut entry = self.cpu.borrow().disassemble(addr)?;
+        if let Some(symbols) = &self.symbols {
+            let cart = self.cart.borrow();
+            symbols.label_operand(&mut entry, |addr| cart.prg_bank(addr));
+        }
+        Some(entry)
+    }
+
+    /// Use names and source lines from debug symbols in disassembly and stepping,
+    /// and in traces whose loggers ask for labels
+    pub fn set_symbols(&mut self, symbols: Option<SymbolTable>) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        self.symbols.as_ref()
+    }
+
+    /// The label of an address, in whichever PRG bank is mapped there now
+    pub fn label_at(&self, addr: u16) -> Option<&str> {
+        let bank = self.cart.borrow().prg_bank(addr);
+        self.symbols.as_ref()?.label(bank, addr)
+    }
+
+    /// The source line whose code starts at an address, in whichever PRG bank is mapped there now
+    pub fn source_line_at(&self, addr: u16) -> Option<&SourceLine> {
+        let bank = self.cart.borrow().prg_bank(addr);
+        self.symbols.as_ref()?.source_line(bank, addr)
+    }
+
+    /// The address of a label, if it's in RAM or in a PRG bank that is mapped in now
+    pub fn address_of_label(&self, name: &str) -> Option<u16> {
+        let (bank, addr) = self.symbols.as_ref()?.address_of(name)?;
+        if bank.is_some() && self.cart.borrow().prg_bank(addr) != bank {
+            return None;
+        }
+        Some(addr)
+    }
+
+    pub fn cpu_registers(&self) -> Registers {
+        self.cpu.borrow().registers()
+    }
+
+    pub fn set_cpu_registers(&mut self, registers: Registers) {
+        self.cpu.borrow_mut().set_registers(registers);
+    }
+
+    /// Read the CPU address space without side effects, for debuggers
+    pub fn peek_cpu_memory(&self, addr: u16) -> u8 {
+        self.cpu.borrow().peek(addr)
+    }
+
+    /// Write to the CPU address space as if the CPU had written it, without triggering breakpoints
+    pub fn write_cpu_memory(&mut self, addr: u16, data: u8) {
+        self.cpu.borrow_mut().write(addr, data);
+        self.debugger.discard_access_hits();
+    }
+
+    /// Change RAM, PRG RAM or PRG ROM without side effects, for debuggers, returning false
+    /// for addresses like registers that can't be written without them
+    pub fn poke_cpu_memory(&mut self, addr: u16, data: u8) -> bool {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram.borrow_mut().memory[addr as usize % 0x0800] = data;
+                true
+            }
+            0x2000..=0x401F => false,
+            _ => self.cart.borrow_mut().poke(addr, data),
+        }
+    }
+
+    /// Read the PPU address space without side effects, for debuggers
+    pub fn peek_ppu_memory(&self, addr: u16) -> u8 {
+        self.ppu.borrow().peek_vram(addr)
+    }
+
+    pub fn write_ppu_memory(&mut self, addr: u16, data: u8) {
+        self.ppu.borrow_mut().write_vram(addr, data);
+        self.debugger.discard_access_hits();
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            cpu: self.cpu.borrow().save_state(),
+            ppu: self.ppu.borrow().save_state(),
+            apu: self.apu.borrow().save_state(),
+            cart: self.cart.borrow().save_state(),
+            ram: self.ram.borrow().memory.clone(),
+            nametables: self.nametables.borrow().save_state(),
+            palette_ram: self.palette_ram.borrow().save_state(),
+            joy1: self.joy1.borrow().save_state(),
+            joy2: self.joy2.borrow().save_state(),
+        }
+    }
+
+    /// Restore a save state, unless it's from a different ROM than the one loaded now
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), &'static str> {
+        if !self.cart.borrow().matches_state(&state.cart) {
+            return Err("save state is from a different ROM");
+        }
+        self.cpu.borrow_mut().load_state(&state.cpu);
+        self.ppu.borrow_mut().load_state(&state.ppu);
+        self.apu.borrow_mut().load_state(&state.apu);
+        self.cart.borrow_mut().load_state(&state.cart);
+        self.ram.borrow_mut().memory.copy_from_slice(&state.ram);
+        self.nametables.borrow_mut().load_state(&state.nametables);
+        self.palette_ram.borrow_mut().load_state(&state.palette_ram);
+        self.joy1.borrow_mut().load_state(&state.joy1);
+        self.joy2.borrow_mut().load_state(&state.joy2);
+        self.break_hit = None;
+        self.debugger.discard_access_hits();
+        Ok(())
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.ppu.borrow().scanline()
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.ppu.borrow().dot()
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.ppu.borrow().frame()
+    }
+
+    /// Where the color subcarrier started this frame, from 0 to 2, for NtscFilter::render
+    pub fn burst_phase(&self) -> usize {
+        self.ppu.borrow().burst_phase() as usize
+    }
+
+    /// Draw every sprite on a scanline rather than only the 8 the hardware can, to remove
+    /// flicker, while the game still sees sprite overflow and sprite 0 hits as usual
+    pub fn set_sprite_limit(&mut self, sprite_limit: bool) {
+        self.ppu.borrow_mut().set_sprite_limit(sprite_limit);
+    }
+
+    /// Hide the background, sprites or individual palettes from frames, for debugging and
+    /// screenshots, while the game still sees sprite overflow and sprite 0 hits as usual
+    pub fn set_layers(&mut self, layers: Layers) {
+        self.ppu.borrow_mut().set_layers(layers);
+    }
+
+    pub fn layers(&self) -> Layers {
+        self.ppu.borrow().layers()
+    }
+
+    /// Use a palette for the colors of frames
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Draw the four nametables with the scroll viewport outlined, given the RGB of the 64 colors
+    pub fn render_nametables(&self, colors: &[u32]) -> RgbImage {
+        self.ppu.borrow().render_nametables(colors)
+    }
+
+    /// Draw the pattern tables at $0000 and $1000 side by side, in a palette from 0 to 7
+    pub fn render_pattern_tables(&self, palette: u8, colors: &[u32]) -> RgbImage {
+        self.ppu.borrow().render_pattern_tables(palette, colors)
+    }
+
+    /// The 64 sprites in OAM, including whether each is dropped on any scanline
+    pub fn sprites(&self) -> Vec<SpriteInfo> {
+        self.ppu.borrow().sprites()
+    }
+
+    /// Draw the last picture with sprites' bounding boxes, beside a grid of every sprite,
+    /// in a palette, emphasis and all
+    pub fn render_sprite_view(&self, palette: &Palette) -> RgbImage {
+        self.ppu
+            .borrow()
+            .render_sprite_view(palette.colors(), |pixel| palette.rgb(pixel))
+    }
+
+    /// The sprite at a point in the image from render_sprite_view, if any
+    pub fn sprite_in_view(&self, x: usize, y: usize) -> Option<SpriteInfo> {
+        self.ppu.borrow().sprite_in_view(x, y)
+    }
+
+    /// The number of 4 KB banks of CHR ROM, or of CHR RAM for cartridges with RAM instead
+    pub fn chr_bank_count(&self) -> usize {
+        self.cart.borrow().chr_bank_count()
+    }
+
+    /// Draw a 4 KB bank of CHR ROM or RAM, whether or not it's banked in, in a palette from 0 to 7
+    pub fn render_chr_bank(&self, bank: usize, palette: u8, colors: &[u32]) -> Option<RgbImage> {
+        let palette = self.ppu.borrow().palette(palette);
+        self.cart.borrow().render_chr_bank(bank, &palette, colors)
     }
 
     pub fn has_cartridge(&self) -> bool {
         !self.cart.borrow().is_empty()
     }
 
-    pub fn get_new_frame(&self) -> Option<[[u8; 256]; 240]> {
+    /// The frame that was just finished, if any, as color indices with emphasis bits above them,
+    /// which Palette::rgb converts
+    pub fn get_new_frame(&self) -> Option<[[u16; 256]; 240]> {
         let ppu = self.ppu.borrow();
         if ppu.frame_ready {
             Some(ppu.framebuffer)
@@ -146,6 +664,12 @@ impl NES {
         }
     }
 
+    /// Draw the last finished frame in the active palette into a buffer of pixels, with lines
+    /// `pitch` bytes apart, even while the PPU is partway through the next one
+    pub fn render_frame(&self, format: PixelFormat, out: &mut [u8], pitch: usize) {
+        video::render_frame(&self.last_frame, &self.palette, format, out, pitch);
+    }
+
     pub fn take_audio_buff(&mut self) -> Vec<f32> {
         self.apu.borrow_mut().take_audio_buff()
     }
@@ -19,6 +19,14 @@ impl NametableMemory {
         }
     }
 
+    pub fn save_state(&self) -> Vec<u8> {
+        self.memory.memory.clone()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) {
+        self.memory.memory.copy_from_slice(state);
+    }
+
     fn mirror(&self, addr: u16) -> u16 {
         // Adapted from a clever approach by daniel5151
         let mut _addr = addr;
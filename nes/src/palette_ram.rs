@@ -13,6 +13,14 @@ impl PaletteRAM {
         }
     }
 
+    pub fn save_state(&self) -> Vec<u8> {
+        self.memory.memory.clone()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) {
+        self.memory.memory.copy_from_slice(state);
+    }
+
     fn mirror(&self, addr: u16) -> u16 {
         // "Addresses $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C"
         let mut mirrored = addr;
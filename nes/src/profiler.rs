@@ -0,0 +1,347 @@
+use crate::symbols::SymbolTable;
+use cpu::{CallFrame, Interrupt};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RoutineKind {
+    TopLevel, // Code that isn't inside any call we saw, like a main loop
+    Subroutine,
+    Interrupt(Interrupt),
+}
+
+impl RoutineKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            RoutineKind::TopLevel | RoutineKind::Subroutine => "",
+            RoutineKind::Interrupt(Interrupt::Nmi) => "NMI ",
+            RoutineKind::Interrupt(Interrupt::Irq) => "IRQ ",
+            RoutineKind::Interrupt(Interrupt::Brk) => "BRK ",
+        }
+    }
+}
+
+/// Where a routine starts, including the PRG bank, since banked routines can share an address
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Routine {
+    pub kind: RoutineKind,
+    pub bank: Option<usize>,
+    pub addr: u16,
+}
+
+impl Routine {
+    const TOP_LEVEL: Routine = Routine {
+        kind: RoutineKind::TopLevel,
+        bank: None,
+        addr: 0,
+    };
+}
+
+impl fmt::Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.kind == RoutineKind::TopLevel {
+            return write!(f, "(top level)");
+        }
+        write!(f, "{}", self.kind.prefix())?;
+        match self.bank {
+            Some(bank) => write!(f, "${:02X}:{:04X}", bank, self.addr),
+            None => write!(f, "${:04X}", self.addr),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoutineStats {
+    pub calls: u64,
+    pub inclusive_cycles: u64, // Including the routines it called
+    pub exclusive_cycles: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "table" => Ok(ReportFormat::Table),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err("unknown report format"),
+        }
+    }
+}
+
+/// The cycles spent in each routine over some number of frames
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub frames: u64,
+    pub cycles: u64,
+    pub routines: Vec<(Routine, RoutineStats)>, // Sorted by inclusive cycles, most first
+    pub labels: HashMap<Routine, String>,
+}
+
+impl Profile {
+    fn from_stats(frames: u64, cycles: u64, stats: &HashMap<Routine, RoutineStats>) -> Self {
+        let mut routines: Vec<(Routine, RoutineStats)> = stats
+            .iter()
+            .map(|(routine, stats)| (*routine, *stats))
+            .collect();
+        routines.sort_by(|(a_routine, a), (b_routine, b)| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(b.exclusive_cycles.cmp(&a.exclusive_cycles))
+                .then(a_routine.addr.cmp(&b_routine.addr))
+        });
+        Self {
+            frames,
+            cycles,
+            routines,
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Name the routines that have labels in a symbol table
+    pub fn label(&mut self, symbols: &SymbolTable) {
+        for (routine, _) in &self.routines {
+            if routine.kind != RoutineKind::TopLevel {
+                if let Some(label) = symbols.label(routine.bank, routine.addr) {
+                    self.labels.insert(*routine, label.to_string());
+                }
+            }
+        }
+    }
+
+    fn name(&self, routine: &Routine) -> String {
+        match self.labels.get(routine) {
+            Some(label) => format!("{}{}", routine.kind.prefix(), label),
+            None => routine.to_string(),
+        }
+    }
+
+    pub fn format(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Table => self.to_table(),
+            ReportFormat::Json => self.to_json(),
+        }
+    }
+
+    pub fn to_table(&self) -> String {
+        let frames = self.frames.max(1);
+        let percent = |cycles: u64| 100.0 * cycles as f64 / self.cycles.max(1) as f64;
+        let names: Vec<String> = self
+            .routines
+            .iter()
+            .map(|(routine, _)| self.name(routine))
+            .collect();
+        let width = names
+            .iter()
+            .map(|name| name.len() + 2)
+            .max()
+            .unwrap_or(0)
+            .max(18);
+        let mut table = format!(
+            "{} cycles over {} frame{}\n{:<width$}{:>10}{:>14}{:>14}{:>12}{:>12}{:>8}{:>8}\n",
+            self.cycles,
+            self.frames,
+            if self.frames == 1 { "" } else { "s" },
+            "routine",
+            "calls",
+            "inclusive",
+            "exclusive",
+            "incl/frame",
+            "excl/frame",
+            "incl%",
+            "excl%",
+            width = width
+        );
+        for ((_, stats), name) in self.routines.iter().zip(names) {
+            table += &format!(
+                "{:<width$}{:>10}{:>14}{:>14}{:>12}{:>12}{:>7.1}%{:>7.1}%\n",
+                name,
+                stats.calls,
+                stats.inclusive_cycles,
+                stats.exclusive_cycles,
+                stats.inclusive_cycles / frames,
+                stats.exclusive_cycles / frames,
+                percent(stats.inclusive_cycles),
+                percent(stats.exclusive_cycles),
+                width = width
+            );
+        }
+        table
+    }
+
+    pub fn to_json(&self) -> String {
+        let routines: Vec<String> = self
+            .routines
+            .iter()
+            .map(|(routine, stats)| {
+                let kind = match routine.kind {
+                    RoutineKind::TopLevel => "top_level",
+                    RoutineKind::Subroutine => "subroutine",
+                    RoutineKind::Interrupt(Interrupt::Nmi) => "nmi",
+                    RoutineKind::Interrupt(Interrupt::Irq) => "irq",
+                    RoutineKind::Interrupt(Interrupt::Brk) => "brk",
+                };
+                format!(
+                    "{{\"name\":\"{}\",\"kind\":\"{}\",\"bank\":{},\"address\":{},\"calls\":{},\"inclusive_cycles\":{},\"exclusive_cycles\":{}}}",
+                    self.name(routine).replace('\\', "\\\\").replace('"', "\\\""),
+                    kind,
+                    routine.bank.map_or("null".to_string(), |bank| bank.to_string()),
+                    routine.addr,
+                    stats.calls,
+                    stats.inclusive_cycles,
+                    stats.exclusive_cycles
+                )
+            })
+            .collect();
+        format!(
+            "{{\"frames\":{},\"cycles\":{},\"routines\":[{}]}}",
+            self.frames,
+            self.cycles,
+            routines.join(",")
+        )
+    }
+}
+
+struct ActiveCall {
+    frame: CallFrame,
+    routine: Routine,
+}
+
+/// Attributes CPU cycles to the routines on the CPU's call stack
+pub struct Profiler {
+    stack: Vec<ActiveCall>, // The CPU's call stack as of the last update, under the top level
+    last_cycles: Option<u64>,
+
+    frame_cycles: u64,
+    frame_stats: HashMap<Routine, RoutineStats>,
+    last_frame: Option<Profile>,
+
+    session_frames: u64,
+    session_cycles: u64,
+    session_stats: HashMap<Routine, RoutineStats>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            last_cycles: None,
+
+            frame_cycles: 0,
+            frame_stats: HashMap::new(),
+            last_frame: None,
+
+            session_frames: 0,
+            session_cycles: 0,
+            session_stats: HashMap::new(),
+        }
+    }
+
+    /// Charge the cycles since the last update to the routines that were on the call stack,
+    /// then follow the CPU's call stack, given a way to find the PRG bank of a new routine
+    pub fn update(
+        &mut self,
+        call_stack: &[CallFrame],
+        cycles: u64,
+        prg_bank: impl Fn(u16) -> Option<usize>,
+    ) {
+        self.advance(cycles);
+
+        let unchanged = self
+            .stack
+            .iter()
+            .zip(call_stack)
+            .take_while(|(call, frame)| call.frame == **frame)
+            .count();
+        self.stack.truncate(unchanged);
+        for frame in &call_stack[unchanged..] {
+            let kind = match frame.interrupt {
+                Some(interrupt) => RoutineKind::Interrupt(interrupt),
+                None => RoutineKind::Subroutine,
+            };
+            let routine = Routine {
+                kind,
+                bank: prg_bank(frame.to),
+                addr: frame.to,
+            };
+            self.frame_stats.entry(routine).or_default().calls += 1;
+            self.session_stats.entry(routine).or_default().calls += 1;
+            self.stack.push(ActiveCall {
+                frame: *frame,
+                routine,
+            });
+        }
+    }
+
+    /// Finish the current frame's profile
+    pub fn end_frame(&mut self, cycles: u64) {
+        self.advance(cycles);
+        self.session_frames += 1;
+        self.last_frame = Some(Profile::from_stats(1, self.frame_cycles, &self.frame_stats));
+        self.frame_cycles = 0;
+        self.frame_stats.clear();
+    }
+
+    pub fn last_frame(&self) -> Option<&Profile> {
+        self.last_frame.as_ref()
+    }
+
+    pub fn session(&self) -> Profile {
+        Profile::from_stats(
+            self.session_frames,
+            self.session_cycles,
+            &self.session_stats,
+        )
+    }
+
+    /// Charge the cycles since the last update to the routines on the stack
+    fn advance(&mut self, cycles: u64) {
+        let elapsed = match self.last_cycles {
+            Some(last_cycles) => cycles.saturating_sub(last_cycles),
+            None => 0,
+        };
+        self.last_cycles = Some(cycles);
+        if elapsed == 0 {
+            return;
+        }
+
+        self.frame_cycles += elapsed;
+        self.session_cycles += elapsed;
+        // Walked in place rather than collected, as this runs on every cycle
+        let stack = &self.stack;
+        let routine_at = |i: usize| match i {
+            0 => Routine::TOP_LEVEL,
+            _ => stack[i - 1].routine,
+        };
+        let depth = stack.len() + 1;
+        for i in 0..depth {
+            let routine = routine_at(i);
+            // A recursive routine's inclusive time is only counted once
+            let outermost = !(0..i).any(|outer| routine_at(outer) == routine);
+            let is_top = i == depth - 1;
+            for stats in [&mut self.frame_stats, &mut self.session_stats].iter_mut() {
+                let entry = stats.entry(routine).or_default();
+                if outermost {
+                    entry.inclusive_cycles += elapsed;
+                }
+                if is_top {
+                    entry.exclusive_cycles += elapsed;
+                }
+            }
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
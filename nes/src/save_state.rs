@@ -0,0 +1,20 @@
+use crate::cartridge::CartridgeState;
+use crate::controllers::ControllerState;
+
+use apu::APUState;
+use cpu::CPUState;
+use ppu::PPUState;
+
+/// A snapshot of the whole system, which can be loaded back while the same ROM is loaded
+#[derive(Clone)]
+pub struct SaveState {
+    pub(crate) cpu: CPUState,
+    pub(crate) ppu: PPUState,
+    pub(crate) apu: APUState,
+    pub(crate) cart: CartridgeState,
+    pub(crate) ram: Vec<u8>,
+    pub(crate) nametables: Vec<u8>,
+    pub(crate) palette_ram: Vec<u8>,
+    pub(crate) joy1: ControllerState,
+    pub(crate) joy2: ControllerState,
+}
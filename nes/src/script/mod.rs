@@ -0,0 +1,297 @@
+mod overlay;
+
+pub use overlay::{Overlay, OVERLAY_HEIGHT, OVERLAY_WIDTH};
+
+use crate::{SaveState, NES};
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, Map, AST, INT};
+
+use std::cell::{Ref, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+
+// The order of the buttons in the controller's shift register, from the highest bit
+const BUTTONS: [&str; 8] = ["right", "left", "down", "up", "start", "select", "b", "a"];
+
+/// What a script has set up, which the functions it calls can change
+#[derive(Default)]
+struct ScriptState {
+    frame_callbacks: Vec<FnPtr>,
+    scanline_callbacks: Vec<(u16, FnPtr)>,
+    input: Option<u8>, // Replaces the controller's input until the end of the frame
+    overlay: Overlay,
+    clear_overlay: bool, // Whether the overlay has been shown, and should be cleared on the next tick
+}
+
+/// A Rhai script that automates an NES, in the spirit of FCEUX and Mesen's Lua scripting.
+/// Scripts register callbacks to run after each frame or at a scanline, from which they can
+/// read and write memory and registers, set the controller's input, save and load states,
+/// and draw over the screen. https://rhai.rs/book/
+///
+/// The script runs the NES instead of the frontend, by calling tick in place of NES::tick.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    nes: Rc<RefCell<NES>>,
+    state: Rc<RefCell<ScriptState>>,
+    scanline: u16,
+}
+
+impl Script {
+    /// Compile and run the top level of a script, which usually registers callbacks
+    pub fn new(nes: Rc<RefCell<NES>>, source: &str) -> Result<Self, String> {
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let engine = Script::engine(&nes, &state);
+        let ast = engine.compile(source).map_err(|err| err.to_string())?;
+        engine.run_ast(&ast).map_err(|err| err.to_string())?;
+        let scanline = nes.borrow().scanline();
+        Ok(Self {
+            engine,
+            ast,
+            nes,
+            state,
+            scanline,
+        })
+    }
+
+    pub fn from_file(nes: Rc<RefCell<NES>>, path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Script::new(nes, &source)
+    }
+
+    /// Tick the NES, then run any callbacks for the scanline or frame it reached
+    pub fn tick(&mut self) -> Result<(), String> {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.clear_overlay {
+                state.overlay.clear();
+                state.clear_overlay = false;
+            }
+        }
+
+        let (scanline, frame_ready) = {
+            let mut nes = self.nes.borrow_mut();
+            nes.tick();
+            if let Some(input) = self.state.borrow().input {
+                nes.try_fill_controller_shift(input);
+            }
+            (nes.scanline(), nes.get_new_frame().is_some())
+        };
+
+        if scanline != self.scanline {
+            self.scanline = scanline;
+            let callbacks: Vec<FnPtr> = self
+                .state
+                .borrow()
+                .scanline_callbacks
+                .iter()
+                .filter(|(line, _)| *line == scanline)
+                .map(|(_, callback)| callback.clone())
+                .collect();
+            self.call(&callbacks)?;
+        }
+
+        if frame_ready {
+            let callbacks = {
+                let mut state = self.state.borrow_mut();
+                state.input = None;
+                state.clear_overlay = true;
+                state.frame_callbacks.clone()
+            };
+            self.call(&callbacks)?;
+        }
+        Ok(())
+    }
+
+    /// The controller input the script has set for this frame, if any, as a shift register byte
+    pub fn input(&self) -> Option<u8> {
+        self.state.borrow().input
+    }
+
+    /// What the script has drawn for the frame that was just finished
+    pub fn overlay(&self) -> Ref<'_, Overlay> {
+        Ref::map(self.state.borrow(), |state| &state.overlay)
+    }
+
+    fn call(&self, callbacks: &[FnPtr]) -> Result<(), String> {
+        for callback in callbacks {
+            let _: Dynamic = callback
+                .call(&self.engine, &self.ast, ())
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn engine(nes: &Rc<RefCell<NES>>, state: &Rc<RefCell<ScriptState>>) -> Engine {
+        let mut engine = Engine::new();
+        engine.register_type_with_name::<SaveState>("SaveState");
+
+        // Memory
+        let nes_ref = nes.clone();
+        engine.register_fn("read", move |addr: INT| {
+            nes_ref.borrow().peek_cpu_memory(addr as u16) as INT
+        });
+        let nes_ref = nes.clone();
+        engine.register_fn("read_word", move |addr: INT| {
+            let nes = nes_ref.borrow();
+            let lo = nes.peek_cpu_memory(addr as u16) as INT;
+            let hi = nes.peek_cpu_memory((addr as u16).wrapping_add(1)) as INT;
+            (hi << 8) | lo
+        });
+        let nes_ref = nes.clone();
+        engine.register_fn("write", move |addr: INT, data: INT| {
+            nes_ref
+                .borrow_mut()
+                .write_cpu_memory(addr as u16, data as u8);
+        });
+        let nes_ref = nes.clone();
+        engine.register_fn("read_ppu", move |addr: INT| {
+            nes_ref.borrow().peek_ppu_memory(addr as u16) as INT
+        });
+        let nes_ref = nes.clone();
+        engine.register_fn("write_ppu", move |addr: INT, data: INT| {
+            nes_ref
+                .borrow_mut()
+                .write_ppu_memory(addr as u16, data as u8);
+        });
+
+        // Registers
+        let nes_ref = nes.clone();
+        engine.register_fn(
+            "get_register",
+            move |name: &str| -> Result<INT, Box<EvalAltResult>> {
+                let registers = nes_ref.borrow().cpu_registers();
+                let value = match &name.to_lowercase()[..] {
+                    "a" => registers.a as INT,
+                    "x" => registers.x as INT,
+                    "y" => registers.y as INT,
+                    "p" => registers.p as INT,
+                    "s" | "sp" => registers.s as INT,
+                    "pc" => registers.pc as INT,
+                    _ => return Err(format!("unknown register: {}", name).into()),
+                };
+                Ok(value)
+            },
+        );
+        let nes_ref = nes.clone();
+        engine.register_fn(
+            "set_register",
+            move |name: &str, value: INT| -> Result<(), Box<EvalAltResult>> {
+                let mut nes = nes_ref.borrow_mut();
+                let mut registers = nes.cpu_registers();
+                match &name.to_lowercase()[..] {
+                    "a" => registers.a = value as u8,
+                    "x" => registers.x = value as u8,
+                    "y" => registers.y = value as u8,
+                    "p" => registers.p = value as u8,
+                    "s" | "sp" => registers.s = value as u8,
+                    "pc" => registers.pc = value as u16,
+                    _ => return Err(format!("unknown register: {}", name).into()),
+                }
+                nes.set_cpu_registers(registers);
+                Ok(())
+            },
+        );
+
+        // Input, either as a map like #{a: true, right: true} or a shift register byte
+        let state_ref = state.clone();
+        engine.register_fn(
+            "set_input",
+            move |buttons: Map| -> Result<(), Box<EvalAltResult>> {
+                let mut input = 0;
+                for (name, pressed) in buttons {
+                    let bit = BUTTONS
+                        .iter()
+                        .position(|button| *button == name.as_str())
+                        .ok_or_else(|| format!("unknown button: {}", name))?;
+                    if pressed.as_bool().unwrap_or(false) {
+                        input |= 0x80 >> bit;
+                    }
+                }
+                state_ref.borrow_mut().input = Some(input);
+                Ok(())
+            },
+        );
+        let state_ref = state.clone();
+        engine.register_fn("set_input", move |input: INT| {
+            state_ref.borrow_mut().input = Some(input as u8);
+        });
+
+        // Callbacks, given as closures or as Fn("name")
+        let state_ref = state.clone();
+        engine.register_fn("on_frame", move |callback: FnPtr| {
+            state_ref.borrow_mut().frame_callbacks.push(callback);
+        });
+        let state_ref = state.clone();
+        engine.register_fn("on_scanline", move |scanline: INT, callback: FnPtr| {
+            state_ref
+                .borrow_mut()
+                .scanline_callbacks
+                .push((scanline as u16, callback));
+        });
+
+        // Emulation
+        let nes_ref = nes.clone();
+        engine.register_fn("save_state", move || nes_ref.borrow().save_state());
+        let nes_ref = nes.clone();
+        engine.register_fn(
+            "load_state",
+            move |save_state: SaveState| -> Result<(), Box<EvalAltResult>> {
+                Ok(nes_ref.borrow_mut().load_state(&save_state)?)
+            },
+        );
+        let nes_ref = nes.clone();
+        engine.register_fn("reset", move || nes_ref.borrow_mut().reset());
+        let nes_ref = nes.clone();
+        engine.register_fn("frame", move || nes_ref.borrow().frame() as INT);
+        let nes_ref = nes.clone();
+        engine.register_fn("scanline", move || nes_ref.borrow().scanline() as INT);
+        let nes_ref = nes.clone();
+        engine.register_fn("dot", move || nes_ref.borrow().dot() as INT);
+
+        // Drawing, with colors like 0xRRGGBB
+        let state_ref = state.clone();
+        engine.register_fn("pixel", move |x: INT, y: INT, color: INT| {
+            state_ref.borrow_mut().overlay.set_pixel(x, y, color as u32);
+        });
+        let state_ref = state.clone();
+        engine.register_fn(
+            "line",
+            move |x0: INT, y0: INT, x1: INT, y1: INT, color: INT| {
+                state_ref
+                    .borrow_mut()
+                    .overlay
+                    .line(x0, y0, x1, y1, color as u32);
+            },
+        );
+        let state_ref = state.clone();
+        engine.register_fn(
+            "rect",
+            move |x: INT, y: INT, width: INT, height: INT, color: INT| {
+                state_ref
+                    .borrow_mut()
+                    .overlay
+                    .rect(x, y, width, height, color as u32);
+            },
+        );
+        let state_ref = state.clone();
+        engine.register_fn(
+            "fill_rect",
+            move |x: INT, y: INT, width: INT, height: INT, color: INT| {
+                state_ref
+                    .borrow_mut()
+                    .overlay
+                    .fill_rect(x, y, width, height, color as u32);
+            },
+        );
+        let state_ref = state.clone();
+        engine.register_fn("text", move |x: INT, y: INT, text: &str, color: INT| {
+            state_ref
+                .borrow_mut()
+                .overlay
+                .text(x, y, text, color as u32);
+        });
+
+        engine
+    }
+}
@@ -0,0 +1,212 @@
+pub const OVERLAY_WIDTH: usize = 256;
+pub const OVERLAY_HEIGHT: usize = 240;
+
+const GLYPH_WIDTH: i64 = 3;
+const GLYPH_HEIGHT: i64 = 5;
+
+// 3x5 glyphs, read from the top row down, with the leftmost pixel of each row in its highest bit
+const FONT: &[(char, u16)] = &[
+    ('0', 0b111_101_101_101_111),
+    ('1', 0b010_110_010_010_111),
+    ('2', 0b111_001_111_100_111),
+    ('3', 0b111_001_011_001_111),
+    ('4', 0b101_101_111_001_001),
+    ('5', 0b111_100_111_001_111),
+    ('6', 0b111_100_111_101_111),
+    ('7', 0b111_001_001_010_010),
+    ('8', 0b111_101_111_101_111),
+    ('9', 0b111_101_111_001_111),
+    ('A', 0b010_101_111_101_101),
+    ('B', 0b110_101_110_101_110),
+    ('C', 0b011_100_100_100_011),
+    ('D', 0b110_101_101_101_110),
+    ('E', 0b111_100_110_100_111),
+    ('F', 0b111_100_110_100_100),
+    ('G', 0b011_100_101_101_011),
+    ('H', 0b101_101_111_101_101),
+    ('I', 0b111_010_010_010_111),
+    ('J', 0b001_001_001_101_010),
+    ('K', 0b101_101_110_101_101),
+    ('L', 0b100_100_100_100_111),
+    ('M', 0b101_111_111_101_101),
+    ('N', 0b110_101_101_101_101),
+    ('O', 0b010_101_101_101_010),
+    ('P', 0b110_101_110_100_100),
+    ('Q', 0b010_101_101_110_011),
+    ('R', 0b110_101_110_101_101),
+    ('S', 0b011_100_010_001_110),
+    ('T', 0b111_010_010_010_010),
+    ('U', 0b101_101_101_101_111),
+    ('V', 0b101_101_101_101_010),
+    ('W', 0b101_101_111_111_101),
+    ('X', 0b101_101_010_101_101),
+    ('Y', 0b101_101_010_010_010),
+    ('Z', 0b111_001_010_100_111),
+    (' ', 0),
+    ('.', 0b000_000_000_000_010),
+    (',', 0b000_000_000_010_100),
+    (':', 0b000_010_000_010_000),
+    ('-', 0b000_000_111_000_000),
+    ('+', 0b000_010_111_010_000),
+    ('=', 0b000_111_000_111_000),
+    ('/', 0b001_001_010_100_100),
+    ('!', 0b010_010_010_000_010),
+    ('?', 0b110_001_010_000_010),
+    ('(', 0b001_010_010_010_001),
+    (')', 0b100_010_010_010_100),
+    ('<', 0b001_010_100_010_001),
+    ('>', 0b100_010_001_010_100),
+    ('#', 0b101_111_101_111_101),
+    ('$', 0b011_110_010_011_110),
+    ('%', 0b101_001_010_100_101),
+    ('_', 0b000_000_000_000_111),
+];
+
+/// What scripts have drawn over the screen, as 0xRRGGBB colors, with None where nothing is drawn
+pub struct Overlay {
+    pixels: Vec<Option<u32>>,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            pixels: vec![None; OVERLAY_WIDTH * OVERLAY_HEIGHT],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = None);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels.iter().all(Option::is_none)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u32> {
+        self.pixels[y * OVERLAY_WIDTH + x]
+    }
+
+    /// Draw a pixel, ignoring any that are off the screen
+    pub fn set_pixel(&mut self, x: i64, y: i64, color: u32) {
+        if (0..OVERLAY_WIDTH as i64).contains(&x) && (0..OVERLAY_HEIGHT as i64).contains(&y) {
+            self.pixels[y as usize * OVERLAY_WIDTH + x as usize] = Some(color & 0xFFFFFF);
+        }
+    }
+
+    // https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm#All_cases
+    pub fn line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: u32) {
+        // Only the part on the screen is walked, however far off it the ends are
+        let (x0, y0, x1, y1) = match clip_line(x0, y0, x1, y1) {
+            Some(line) => line,
+            None => return,
+        };
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            if 2 * error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if 2 * error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draw the outline of a rectangle
+    pub fn rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: u32) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        let (right, bottom) = (x.saturating_add(width - 1), y.saturating_add(height - 1));
+        self.line(x, y, right, y, color);
+        self.line(x, bottom, right, bottom, color);
+        self.line(x, y, x, bottom, color);
+        self.line(right, y, right, bottom, color);
+    }
+
+    pub fn fill_rect(&mut self, x: i64, y: i64, width: i64, height: i64, color: u32) {
+        let clamp_x = |x: i64| x.clamp(0, OVERLAY_WIDTH as i64);
+        let clamp_y = |y: i64| y.clamp(0, OVERLAY_HEIGHT as i64);
+        for row in clamp_y(y)..clamp_y(y.saturating_add(height)) {
+            for column in clamp_x(x)..clamp_x(x.saturating_add(width)) {
+                self.set_pixel(column, row, color);
+            }
+        }
+    }
+
+    /// Draw text in a small built-in font, with lines separated by newlines.
+    /// Letters are drawn in uppercase, and unsupported characters as '?'.
+    pub fn text(&mut self, x: i64, y: i64, text: &str, color: u32) {
+        for (line_num, line) in text.lines().enumerate() {
+            let top = y.saturating_add(line_num as i64 * (GLYPH_HEIGHT + 1));
+            for (i, c) in line.chars().enumerate() {
+                let left = x.saturating_add(i as i64 * (GLYPH_WIDTH + 1));
+                let c = c.to_ascii_uppercase();
+                let glyph = FONT
+                    .iter()
+                    .find(|(glyph_char, _)| *glyph_char == c)
+                    .or_else(|| FONT.iter().find(|(glyph_char, _)| *glyph_char == '?'))
+                    .map_or(0, |(_, glyph)| *glyph);
+                for row in 0..GLYPH_HEIGHT {
+                    for column in 0..GLYPH_WIDTH {
+                        let bit =
+                            (GLYPH_HEIGHT - 1 - row) * GLYPH_WIDTH + (GLYPH_WIDTH - 1 - column);
+                        if (glyph >> bit) & 1 == 1 {
+                            self.set_pixel(
+                                left.saturating_add(column),
+                                top.saturating_add(row),
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Cut a line down to the part on the screen, or None if it misses the screen.
+/// Lines that are already on the screen keep their ends exactly.
+// https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+fn clip_line(x0: i64, y0: i64, x1: i64, y1: i64) -> Option<(i64, i64, i64, i64)> {
+    let (x0, y0, x1, y1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (max_x, max_y) = ((OVERLAY_WIDTH - 1) as f64, (OVERLAY_HEIGHT - 1) as f64);
+    let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+    for (p, q) in [(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None; // Parallel to this edge, and outside it
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let point = |t: f64| {
+        (
+            (x0 + t * dx).round().clamp(0.0, max_x) as i64,
+            (y0 + t * dy).round().clamp(0.0, max_y) as i64,
+        )
+    };
+    let ((x0, y0), (x1, y1)) = (point(t0), point(t1));
+    Some((x0, y0, x1, y1))
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Overlay::new()
+    }
+}
@@ -0,0 +1,195 @@
+use crate::cartridge::PRG_BANK_SIZE;
+use crate::symbols::{SourceLine, SymbolTable};
+
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+const INES_HEADER_SIZE: u32 = 16;
+
+// Line types, where 2 is a line inside a macro, which is attributed to the line invoking it
+const LINE_ASSEMBLY: u32 = 0;
+const LINE_EXTERNAL: u32 = 1; // e.g. C source that cc65 compiled to the assembly
+
+struct Segment {
+    start: u32,
+    output_offset: Option<u32>, // Where the segment is in the ROM file, including the header
+}
+
+impl Segment {
+    /// The PRG bank of a byte in the segment, None if the segment isn't in the ROM file,
+    /// or Err if the segment is in the ROM file but not in PRG ROM, or the offset is invalid
+    fn bank(&self, offset: u32) -> Result<Option<usize>, ()> {
+        match self.output_offset {
+            None => Ok(None),
+            Some(_) if self.start < 0x6000 => Err(()), // The header, or CHR ROM
+            Some(output_offset) => {
+                let prg_offset = output_offset
+                    .checked_add(offset)
+                    .and_then(|rom_offset| rom_offset.checked_sub(INES_HEADER_SIZE));
+                Ok(Some(prg_offset.ok_or(())? as usize / PRG_BANK_SIZE))
+            }
+        }
+    }
+}
+
+struct Span {
+    segment: u32,
+    start: u32, // Relative to the start of the segment
+    size: u32,
+}
+
+// https://cc65.github.io/doc/debugfile.html
+impl SymbolTable {
+    /// Load the labels and line info from a ca65/ld65 .dbg file, made with ld65 --dbgfile
+    pub fn load_ca65_dbg(&mut self, reader: impl BufRead) -> Result<(), &'static str> {
+        let mut files = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut lines = Vec::new();
+        let mut symbols = Vec::new();
+
+        for line in reader.lines() {
+            let line = line.map_err(|_| "failed to read .dbg file")?;
+            let (record, fields) = match line.split_once(char::is_whitespace) {
+                Some((record, fields)) => (record, parse_fields(fields.trim())),
+                None => continue,
+            };
+            let id = fields.get("id").and_then(|id| parse_number(id));
+            match (record, id) {
+                ("file", Some(id)) => {
+                    let name = fields
+                        .get("name")
+                        .ok_or("file without a name in .dbg file")?;
+                    files.insert(id, name.to_string());
+                }
+                ("seg", Some(id)) => {
+                    let start = field_number(&fields, "start")?;
+                    let output_offset = fields.get("ooffs").and_then(|ooffs| parse_number(ooffs));
+                    segments.insert(
+                        id,
+                        Segment {
+                            start,
+                            output_offset,
+                        },
+                    );
+                }
+                ("span", Some(id)) => {
+                    let span = Span {
+                        segment: field_number(&fields, "seg")?,
+                        start: field_number(&fields, "start")?,
+                        size: field_number(&fields, "size")?,
+                    };
+                    spans.insert(id, span);
+                }
+                ("line", Some(_)) => lines.push(fields),
+                ("sym", Some(_)) => symbols.push(fields),
+                _ => {}
+            }
+        }
+
+        // Cheap locals like "@loop" have a parent, and shouldn't name an address over it
+        symbols.sort_by_key(|fields| fields.contains_key("parent"));
+        for fields in symbols {
+            if fields.get("type").map(String::as_str) != Some("lab") {
+                continue; // Constants aren't addresses
+            }
+            let (name, value, segment) = match (
+                fields.get("name"),
+                fields.get("val").and_then(|val| parse_number(val)),
+                fields
+                    .get("seg")
+                    .and_then(|seg| parse_number(seg))
+                    .and_then(|seg| segments.get(&seg)),
+            ) {
+                (Some(name), Some(value), Some(segment)) => (name, value, segment),
+                _ => continue,
+            };
+            if let Ok(bank) = segment.bank(value.wrapping_sub(segment.start)) {
+                self.add_label(bank, value as u16, name);
+            }
+        }
+
+        for fields in lines {
+            let line_type = fields
+                .get("type")
+                .and_then(|line_type| parse_number(line_type))
+                .unwrap_or(LINE_ASSEMBLY);
+            if line_type != LINE_ASSEMBLY && line_type != LINE_EXTERNAL {
+                continue;
+            }
+            let file = fields
+                .get("file")
+                .and_then(|file| parse_number(file))
+                .and_then(|file| files.get(&file));
+            let (file, line, span_ids) =
+                match (file, field_number(&fields, "line"), fields.get("span")) {
+                    (Some(file), Ok(line), Some(span_ids)) => (file, line, span_ids),
+                    _ => continue, // Lines without code have no spans
+                };
+
+            for span in span_ids
+                .split('+')
+                .filter_map(|id| spans.get(&parse_number(id)?))
+            {
+                let (segment, addr) = match segments.get(&span.segment) {
+                    Some(segment) if span.size > 0 => match segment.start.checked_add(span.start) {
+                        Some(addr) => (segment, addr as u16),
+                        None => continue, // A span past the end of the address space
+                    },
+                    _ => continue,
+                };
+                let bank = match segment.bank(span.start) {
+                    Ok(bank) => bank,
+                    Err(()) => continue,
+                };
+                // Prefer the C source to the assembly generated from it
+                if line_type == LINE_EXTERNAL || self.source_line(bank, addr).is_none() {
+                    self.add_source_line(
+                        bank,
+                        addr,
+                        SourceLine {
+                            file: file.clone(),
+                            line,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split a list like `id=0,name="a, b.s",size=10` into its keys and values, without quotes
+fn parse_fields(s: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut in_quotes = false;
+    let mut field_start = 0;
+    for (i, c) in s.char_indices().chain(std::iter::once((s.len(), ','))) {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                if let Some((key, value)) = s[field_start..i].split_once('=') {
+                    let value = value.trim().trim_matches('"');
+                    fields.insert(key.trim().to_string(), value.to_string());
+                }
+                field_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn field_number(fields: &HashMap<String, String>, key: &str) -> Result<u32, &'static str> {
+    fields
+        .get(key)
+        .and_then(|value| parse_number(value))
+        .ok_or("missing or invalid number in .dbg file")
+}
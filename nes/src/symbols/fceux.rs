@@ -0,0 +1,42 @@
+use crate::symbols::SymbolTable;
+
+use std::io::prelude::*;
+use std::path::Path;
+
+// http://fceux.com/web/help/NLFilesFormat.html
+impl SymbolTable {
+    /// Load an FCEUX .nl file, whose lines look like "$C000#Reset#Comment",
+    /// with the PRG bank its addresses are in, or None for RAM
+    pub fn load_fceux_nl(
+        &mut self,
+        reader: impl BufRead,
+        bank: Option<usize>,
+    ) -> Result<(), &'static str> {
+        for line in reader.lines() {
+            let line = line.map_err(|_| "failed to read .nl file")?;
+            let line = line.trim();
+            if !line.starts_with('$') {
+                continue; // Blank lines, or the continuation of a multi-line comment
+            }
+
+            let mut fields = line[1..].split('#');
+            let addr = fields.next().unwrap_or("");
+            let name = fields.next().unwrap_or("").trim();
+            // Arrays have a size after the address, like "$0300/10"
+            let addr = addr.split('/').next().unwrap_or("");
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| "invalid address in .nl file")?;
+            if !name.is_empty() {
+                self.add_label(bank, addr, name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// FCEUX names a ROM's .nl files like "game.nes.0.nl" for each 16 KB PRG bank, in hex,
+/// and "game.nes.ram.nl" for RAM
+pub fn bank_from_path(path: &Path) -> Option<usize> {
+    let stem = path.file_stem()?.to_str()?;
+    let (_, suffix) = stem.rsplit_once('.')?;
+    usize::from_str_radix(suffix, 16).ok()
+}
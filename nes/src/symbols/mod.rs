@@ -0,0 +1,104 @@
+mod ca65;
+mod fceux;
+
+use cpu::TraceEntry;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Where in the source an instruction was assembled from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: u32,
+}
+
+/// Names for addresses, keyed by PRG bank since banked code can share an address.
+/// Addresses outside of PRG ROM, like RAM, have no bank.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    labels: HashMap<(Option<usize>, u16), String>,
+    addresses: HashMap<String, (Option<usize>, u16)>,
+    lines: HashMap<(Option<usize>, u16), SourceLine>, // Keyed by where each line's code starts
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a ca65 .dbg file, or an FCEUX .nl file named like "game.nes.0.nl" for PRG bank 0
+    /// or "game.nes.ram.nl" for RAM
+    pub fn load_file(&mut self, path: &Path) -> Result<(), &'static str> {
+        let file = File::open(path).map_err(|_| "failed to open symbol file")?;
+        let reader = BufReader::new(file);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("dbg") => self.load_ca65_dbg(reader),
+            Some("nl") => self.load_fceux_nl(reader, fceux::bank_from_path(path)),
+            _ => Err("unknown symbol file type, expected .dbg or .nl"),
+        }
+    }
+
+    /// Name an address, unless an earlier label already names it, though every label's
+    /// address can be looked up
+    pub fn add_label(&mut self, bank: Option<usize>, addr: u16, name: &str) {
+        self.labels
+            .entry((bank, addr))
+            .or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), (bank, addr));
+    }
+
+    pub fn add_source_line(&mut self, bank: Option<usize>, addr: u16, line: SourceLine) {
+        self.lines.insert((bank, addr), line);
+    }
+
+    /// The name of an address in a bank, or of the address in any bank
+    pub fn label(&self, bank: Option<usize>, addr: u16) -> Option<&str> {
+        self.labels
+            .get(&(bank, addr))
+            .or_else(|| self.labels.get(&(None, addr)))
+            .map(|name| &name[..])
+    }
+
+    /// The bank and address of a label
+    pub fn address_of(&self, name: &str) -> Option<(Option<usize>, u16)> {
+        self.addresses.get(name).copied()
+    }
+
+    /// The source line whose code starts at an address
+    pub fn source_line(&self, bank: Option<usize>, addr: u16) -> Option<&SourceLine> {
+        self.lines
+            .get(&(bank, addr))
+            .or_else(|| self.lines.get(&(None, addr)))
+    }
+
+    pub fn has_source_lines(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.lines.is_empty()
+    }
+
+    /// Replace the address in an instruction's operand with its label, e.g. "JSR $C72D" becomes
+    /// "JSR ReadJoypad", given a function to find the bank of an address
+    pub fn label_operand(&self, entry: &mut TraceEntry, bank_of: impl Fn(u16) -> Option<usize>) {
+        if entry.operand.starts_with('#') {
+            return; // Immediate values aren't addresses
+        }
+        let start = match entry.operand.find('$') {
+            Some(start) => start,
+            None => return,
+        };
+        let digits = entry.operand[start + 1..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .map_or(entry.operand.len(), |len| start + 1 + len);
+        if let Ok(addr) = u16::from_str_radix(&entry.operand[start + 1..digits], 16) {
+            if let Some(label) = self.label(bank_of(addr), addr) {
+                entry.operand.replace_range(start..digits, label);
+            }
+        }
+    }
+}
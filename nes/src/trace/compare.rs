@@ -0,0 +1,293 @@
+use crate::trace::{TraceFormat, TraceLogger};
+use crate::NES;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::rc::Rc;
+
+// Emulators disagree on how to show the B and unused flags, so they aren't compared
+const IGNORED_FLAGS: u8 = 0b0011_0000;
+
+// Give up if the emulator goes this long without executing an instruction
+const MAX_TICKS_PER_INSTRUCTION: usize = 100_000;
+
+/// The CPU state described by one line of a trace, independent of the log format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceRecord {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub s: u8,
+    pub cycles: Option<u64>,
+}
+
+impl TraceRecord {
+    /// Parse a line in any of the supported trace formats, returning None for lines
+    /// that don't describe an instruction, like headers and blank lines
+    pub fn parse(line: &str) -> Option<Self> {
+        let pc = line.split_whitespace().find_map(parse_pc)?;
+        let p_str = field(line, "P")?;
+        let p = if p_str.len() == 8 && p_str.chars().all(|c| c.is_ascii_alphabetic()) {
+            // A flag string like "nvUbdIzc"
+            p_str
+                .chars()
+                .fold(0, |acc, c| (acc << 1) | c.is_ascii_uppercase() as u8)
+        } else {
+            u8::from_str_radix(p_str, 16).ok()?
+        };
+
+        // FCEUX prefixes the cycle count with "c", Mesen labels it "CPU Cycle", and nestest "CYC",
+        // except that old nestest logs use CYC for the PPU dot, next to an SL (scanline) field
+        let cycles_str = field(line, "Cycle")
+            .or_else(|| {
+                line.split_whitespace().find_map(|token| {
+                    let digits = token.strip_prefix('c')?;
+                    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                        Some(digits)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .or_else(|| {
+                if field(line, "SL").is_some() {
+                    None
+                } else {
+                    field(line, "CYC")
+                }
+            });
+
+        Some(Self {
+            pc,
+            a: hex_field(line, "A")?,
+            x: hex_field(line, "X")?,
+            y: hex_field(line, "Y")?,
+            p,
+            s: hex_field(line, "SP").or_else(|| hex_field(line, "S"))?,
+            cycles: cycles_str.and_then(|cycles| cycles.parse().ok()),
+        })
+    }
+
+    /// The names of the registers that differ between two records
+    pub fn differences(&self, other: &TraceRecord) -> Vec<&'static str> {
+        let mut differences = Vec::new();
+        if self.pc != other.pc {
+            differences.push("PC");
+        }
+        if self.a != other.a {
+            differences.push("A");
+        }
+        if self.x != other.x {
+            differences.push("X");
+        }
+        if self.y != other.y {
+            differences.push("Y");
+        }
+        if (self.p & !IGNORED_FLAGS) != (other.p & !IGNORED_FLAGS) {
+            differences.push("P");
+        }
+        if self.s != other.s {
+            differences.push("S");
+        }
+        differences
+    }
+}
+
+/// The value following "key:" in a line, where the key is a whole word
+fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("{}:", key);
+    let mut search_from = 0;
+    while let Some(found) = line[search_from..].find(&pattern) {
+        let start = search_from + found;
+        let after = start + pattern.len();
+        let at_word_start = start == 0 || line[..start].ends_with(char::is_whitespace);
+        if at_word_start {
+            let value = line[after..].trim_start();
+            return value.split_whitespace().next();
+        }
+        search_from = after;
+    }
+    None
+}
+
+fn hex_field(line: &str, key: &str) -> Option<u8> {
+    u8::from_str_radix(field(line, key)?, 16).ok()
+}
+
+/// Parse a program counter token like "C000", "$C000:4C" or "$07:C000:4C"
+fn parse_pc(token: &str) -> Option<u16> {
+    // Logs write addresses in upper case, which avoids confusion with FCEUX's "c123" cycle counts
+    let is_pc = |s: &str| {
+        s.len() == 4
+            && s.chars()
+                .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+    };
+    if is_pc(token) {
+        u16::from_str_radix(token, 16).ok()
+    } else if let Some(rest) = token.strip_prefix('$') {
+        let mut segments: Vec<&str> = rest.split(':').collect();
+        segments.pop(); // The PC is always followed by the instruction bytes
+        segments
+            .into_iter()
+            .find(|segment| is_pc(segment))
+            .and_then(|segment| u16::from_str_radix(segment, 16).ok())
+    } else {
+        None
+    }
+}
+
+/// The first point where two traces disagree, along with the lines leading up to it
+pub struct Divergence {
+    pub line_number: usize,
+    pub reference: String,
+    pub ours: String,
+    pub differences: Vec<&'static str>,
+    pub context: Vec<(String, String)>, // (reference, ours) pairs, oldest first
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "traces diverge at line {} ({} differ{})",
+            self.line_number,
+            self.differences.join(", "),
+            if self.differences.len() == 1 { "s" } else { "" }
+        )?;
+        for (reference, ours) in &self.context {
+            writeln!(f, "    reference: {}", reference)?;
+            writeln!(f, "    ours:      {}", ours)?;
+        }
+        writeln!(f, "  > reference: {}", self.reference)?;
+        write!(f, "  > ours:      {}", self.ours)
+    }
+}
+
+/// Compares our trace against a reference trace one line at a time
+pub struct TraceComparison {
+    pub context_len: usize, // The number of matching lines to show before a divergence
+    pub compare_cycles: bool, // Compare the cycles taken by each instruction, if both logs have them
+    pub exact: bool, // Require lines to match textually, rather than only in register values
+    history: VecDeque<(String, String)>,
+    previous: Option<(TraceRecord, TraceRecord)>,
+    line_number: usize,
+}
+
+impl TraceComparison {
+    pub fn new(context_len: usize) -> Self {
+        Self {
+            context_len,
+            compare_cycles: false,
+            exact: false,
+            history: VecDeque::new(),
+            previous: None,
+            line_number: 0,
+        }
+    }
+
+    /// Compare the next line of each trace
+    pub fn check(&mut self, reference: &str, ours: &str) -> Result<(), Divergence> {
+        self.line_number += 1;
+
+        let mut differences = match (TraceRecord::parse(reference), TraceRecord::parse(ours)) {
+            (Some(reference_record), Some(our_record)) => {
+                let mut differences = reference_record.differences(&our_record);
+                if self.compare_cycles {
+                    if let Some((previous_reference, previous_ours)) = self.previous {
+                        let taken = |current: &TraceRecord, previous: &TraceRecord| {
+                            current.cycles?.checked_sub(previous.cycles?)
+                        };
+                        if let (Some(reference_taken), Some(our_taken)) = (
+                            taken(&reference_record, &previous_reference),
+                            taken(&our_record, &previous_ours),
+                        ) {
+                            if reference_taken != our_taken {
+                                differences.push("cycles");
+                            }
+                        }
+                    }
+                }
+                self.previous = Some((reference_record, our_record));
+                differences
+            }
+            _ => vec!["format"],
+        };
+        if self.exact && differences.is_empty() && reference.trim_end() != ours.trim_end() {
+            differences.push("text");
+        }
+
+        if !differences.is_empty() {
+            return Err(Divergence {
+                line_number: self.line_number,
+                reference: reference.to_string(),
+                ours: ours.to_string(),
+                differences,
+                context: self.history.iter().cloned().collect(),
+            });
+        }
+
+        self.history
+            .push_back((reference.to_string(), ours.to_string()));
+        while self.history.len() > self.context_len {
+            self.history.pop_front();
+        }
+        Ok(())
+    }
+}
+
+/// Run the emulator alongside a reference trace, returning the first divergence,
+/// or None if every instruction in the reference matched
+pub fn compare_with_reference(
+    nes: &mut NES,
+    reference: impl BufRead,
+    mut comparison: TraceComparison,
+) -> io::Result<Option<Divergence>> {
+    let lines = Rc::new(RefCell::new(VecDeque::new()));
+    let logger_lines = lines.clone();
+    nes.set_trace_logger(Some(TraceLogger::to_callback(
+        move |line| logger_lines.borrow_mut().push_back(line.to_string()),
+        TraceFormat::Nestest,
+    )));
+
+    let mut result = Ok(None);
+    for reference_line in reference.lines() {
+        let reference_line = match reference_line {
+            Ok(line) => line,
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        };
+        if TraceRecord::parse(&reference_line).is_none() {
+            continue;
+        }
+
+        let mut ticks = 0;
+        while lines.borrow().is_empty() && ticks < MAX_TICKS_PER_INSTRUCTION {
+            nes.tick();
+            ticks += 1;
+        }
+        let our_line = match lines.borrow_mut().pop_front() {
+            Some(line) => line,
+            None => {
+                result = Err(io::Error::other(
+                    "the emulator stopped executing instructions",
+                ));
+                break;
+            }
+        };
+
+        if let Err(divergence) = comparison.check(&reference_line, &our_line) {
+            result = Ok(Some(divergence));
+            break;
+        }
+    }
+
+    nes.set_trace_logger(None);
+    result
+}
@@ -0,0 +1,113 @@
+use crate::trace::TraceContext;
+use cpu::TraceEntry;
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    Nestest, // Nintendulator, as in the well-known nestest.log
+    Mesen,
+    Fceux,
+}
+
+impl TraceFormat {
+    pub fn format(&self, entry: &TraceEntry, context: &TraceContext) -> String {
+        let regs = &entry.registers;
+        match self {
+            // C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+            TraceFormat::Nestest => format!(
+                "{:<48}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+                entry.format_instruction(),
+                regs.a,
+                regs.x,
+                regs.y,
+                regs.p,
+                regs.s,
+                context.scanline,
+                context.dot,
+                entry.cycles
+            ),
+            // C000 $4C $F5 $C5 JMP $C5F5                     A:00 X:00 Y:00 P:24 SP:FD CYC:21  SL:0   FC:0 CPU Cycle:7 PRG:$00
+            TraceFormat::Mesen => {
+                let bytes = entry
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("${:02X}", byte))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!(
+                    "{:<48}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{:<3} SL:{:<3} FC:{} CPU Cycle:{}{}",
+                    format!(
+                        "{:04X} {:<11} {}{}",
+                        regs.pc,
+                        bytes,
+                        entry.disassembly(),
+                        entry.annotation
+                    ),
+                    regs.a,
+                    regs.x,
+                    regs.y,
+                    regs.p,
+                    regs.s,
+                    context.dot,
+                    context.scanline,
+                    context.frame,
+                    entry.cycles,
+                    match context.prg_bank {
+                        Some(bank) => format!(" PRG:${:02X}", bank),
+                        None => String::new(),
+                    }
+                )
+            }
+            // f0     c7         A:00 X:00 Y:00 S:FD P:nvUbdIzc  $00:C000:4C F5 C5  JMP $C5F5
+            TraceFormat::Fceux => format!(
+                "f{:<6} c{:<10} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}  ${}{:04X}:{}  {}{}",
+                context.frame,
+                entry.cycles,
+                regs.a,
+                regs.x,
+                regs.y,
+                regs.s,
+                format_flags(regs.p),
+                match context.prg_bank {
+                    Some(bank) => format!("{:02X}:", bank),
+                    None => String::new(),
+                },
+                regs.pc,
+                entry.format_bytes(),
+                entry.disassembly(),
+                entry.annotation
+            )
+            .trim_end()
+            .to_string(),
+        }
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "nestest" | "nintendulator" => Ok(TraceFormat::Nestest),
+            "mesen" => Ok(TraceFormat::Mesen),
+            "fceux" => Ok(TraceFormat::Fceux),
+            _ => Err("unknown trace format"),
+        }
+    }
+}
+
+/// Show each status flag as an upper-case letter if set and a lower-case one if clear, e.g. "nvUbdIzc"
+pub fn format_flags(p: u8) -> String {
+    "NVUBDIZC"
+        .chars()
+        .enumerate()
+        .map(|(i, flag)| {
+            if p & (0x80 >> i) != 0 {
+                flag
+            } else {
+                flag.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
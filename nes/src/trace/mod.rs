@@ -0,0 +1,152 @@
+mod compare;
+mod format;
+
+pub use compare::{compare_with_reference, Divergence, TraceComparison, TraceRecord};
+pub use format::format_flags;
+pub use format::TraceFormat;
+
+use cpu::TraceEntry;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::str::FromStr;
+
+/// What the rest of the system was doing when an instruction was traced
+pub struct TraceContext {
+    pub scanline: u16,
+    pub dot: u16,
+    pub frame: u64,
+    pub prg_bank: Option<usize>,
+}
+
+pub enum TraceOutput {
+    File(BufWriter<File>),
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+/// The condition that starts a trace, so that logs only cover the interesting part of a run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceArm {
+    Always,
+    Address(u16, u16), // Once the PC lands in this (inclusive) range
+    Frame(u64),        // Once the PPU reaches this frame
+}
+
+impl FromStr for TraceArm {
+    type Err = &'static str;
+
+    /// Parse "pc:C000", "pc:C000-C0FF" or "frame:60"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once(':')
+            .ok_or("expected pc:<address> or frame:<n>")?;
+        match &kind.to_lowercase()[..] {
+            "pc" => {
+                let (start, end) = value.split_once('-').unwrap_or((value, value));
+                let parse = |addr: &str| {
+                    u16::from_str_radix(addr.trim_start_matches('$'), 16)
+                        .map_err(|_| "invalid address")
+                };
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err("the start of the range is after its end");
+                }
+                Ok(TraceArm::Address(start, end))
+            }
+            "frame" => value
+                .parse()
+                .map(TraceArm::Frame)
+                .map_err(|_| "invalid frame number"),
+            _ => Err("expected pc:<address> or frame:<n>"),
+        }
+    }
+}
+
+pub struct TraceLogger {
+    pub format: TraceFormat,
+    pub arm: TraceArm,
+    pub frame_limit: Option<u64>, // Stop after logging this many frames
+    pub labels: bool,             // Show labels from the NES's symbols in place of addresses
+    output: TraceOutput,
+    armed_frame: Option<u64>,
+    finished: bool,
+}
+
+impl TraceLogger {
+    pub fn new(format: TraceFormat, output: TraceOutput) -> Self {
+        Self {
+            format,
+            arm: TraceArm::Always,
+            frame_limit: None,
+            labels: false,
+            output,
+            armed_frame: None,
+            finished: false,
+        }
+    }
+
+    pub fn to_file(file: File, format: TraceFormat) -> Self {
+        Self::new(format, TraceOutput::File(BufWriter::new(file)))
+    }
+
+    pub fn to_callback(callback: impl FnMut(&str) + 'static, format: TraceFormat) -> Self {
+        Self::new(format, TraceOutput::Callback(Box::new(callback)))
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed_frame.is_some() && !self.finished
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn log(&mut self, entry: &TraceEntry, context: &TraceContext) {
+        if self.finished {
+            return;
+        }
+
+        let armed_frame = match self.armed_frame {
+            Some(frame) => frame,
+            None => {
+                let pc = entry.registers.pc;
+                let triggered = match self.arm {
+                    TraceArm::Always => true,
+                    TraceArm::Address(start, end) => start <= pc && pc <= end,
+                    TraceArm::Frame(frame) => context.frame >= frame,
+                };
+                if !triggered {
+                    return;
+                }
+                self.armed_frame = Some(context.frame);
+                context.frame
+            }
+        };
+
+        if let Some(limit) = self.frame_limit {
+            if context.frame >= armed_frame + limit {
+                self.finished = true;
+                self.flush();
+                return;
+            }
+        }
+
+        let line = self.format.format(entry, context);
+        match &mut self.output {
+            TraceOutput::File(writer) => {
+                if writeln!(writer, "{}", line).is_err() {
+                    // Stop rather than spending time on a log that can't be written
+                    self.finished = true;
+                }
+            }
+            TraceOutput::Callback(callback) => callback(&line),
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let TraceOutput::File(writer) = &mut self.output {
+            let _ = writer.flush();
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::parse_settings;
+
+// How far, in source pixels, the glow of bright areas spreads
+const BLOOM_RADIUS_X: usize = 3;
+const BLOOM_RADIUS_Y: usize = 2;
+
+/// How strong each part of the CRT look is, from 0 (off) to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrtSettings {
+    pub scanlines: f32, // Darkening between the lines the beam draws
+    pub mask: f32,      // Darkening of the other two colors of each aperture grille stripe
+    pub blur: f32,      // Softening along lines, as the beam can't change instantly
+    pub bloom: f32,     // Glow around bright areas
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        Self {
+            scanlines: 0.5,
+            mask: 0.3,
+            blur: 0.5,
+            bloom: 0.25,
+        }
+    }
+}
+
+/// Settings like "scanlines=0.8,mask=0", where any that are left out are the default
+impl std::str::FromStr for CrtSettings {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        parse_settings(s, |name, value| {
+            if !(0.0..=1.0).contains(&value) {
+                return Err("CRT settings must be from 0 to 1");
+            }
+            match name {
+                "scanlines" => settings.scanlines = value,
+                "mask" => settings.mask = value,
+                "blur" => settings.blur = value,
+                "bloom" => settings.bloom = value,
+                _ => return Err("unknown CRT setting"),
+            }
+            Ok(())
+        })?;
+        Ok(settings)
+    }
+}
+
+/// Draws a frame as a CRT would show it, at the resolution it will be displayed at
+/// so that scanlines and the mask line up with real pixels
+pub struct CrtFilter {
+    settings: CrtSettings,
+    // Each source pixel as RGB from 0 to 1, and blurred for bloom
+    colors: Vec<[f32; 3]>,
+    glow: Vec<[f32; 3]>,
+    scratch: Vec<[f32; 3]>,
+    // One source line stretched to the output width, and how much of the light its glow
+    // doesn't already give, as glow adds to the picture like light does, never past white
+    line: Vec<[f32; 3]>,
+    line_glow: Vec<[f32; 3]>,
+}
+
+impl CrtFilter {
+    pub fn new(settings: CrtSettings) -> Self {
+        Self {
+            settings,
+            colors: Vec::new(),
+            glow: Vec::new(),
+            scratch: Vec::new(),
+            line: Vec::new(),
+            line_glow: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> CrtSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: CrtSettings) {
+        self.settings = settings;
+    }
+
+    /// Draw a frame of 0xRRGGBB colors into a larger frame of them, or nothing if either is empty,
+    /// like the viewport of a minimized window
+    pub fn render(
+        &mut self,
+        src: &[u32],
+        width: usize,
+        height: usize,
+        out: &mut [u32],
+        out_width: usize,
+        out_height: usize,
+    ) {
+        assert!(
+            src.len() >= width * height,
+            "source is smaller than the frame"
+        );
+        assert!(
+            out.len() >= out_width * out_height,
+            "buffer is too small for the output frame"
+        );
+        if width == 0 || height == 0 || out_width == 0 || out_height == 0 {
+            return;
+        }
+
+        self.colors.clear();
+        self.colors
+            .extend(src[..width * height].iter().map(|&color| {
+                let channel = |shift: u32| ((color >> shift) & 0xFF) as f32 / 255.0;
+                [channel(16), channel(8), channel(0)]
+            }));
+        self.blur_glow(width, height);
+        self.line.resize(out_width, [0.0; 3]);
+        self.line_glow.resize(out_width, [0.0; 3]);
+
+        let scanlines = self.settings.scanlines;
+        for y in 0..height {
+            self.stretch_line(y, width, out_width);
+
+            let (start, end) = (y * out_height / height, (y + 1) * out_height / height);
+            for row in start..end {
+                // The beam is brightest in the middle of its line
+                let middle = ((row - start) as f32 + 0.5) / (end - start) as f32;
+                let offset = 2.0 * middle - 1.0;
+                let brightness = 1.0 - scanlines * offset * offset;
+
+                let out_line = &mut out[row * out_width..(row + 1) * out_width];
+                for ((color, line), glow) in out_line
+                    .iter_mut()
+                    .zip(self.line.iter())
+                    .zip(self.line_glow.iter())
+                {
+                    let mut rgb = 0;
+                    for channel in 0..3 {
+                        let value = 1.0 - (1.0 - line[channel] * brightness) * glow[channel];
+                        let byte = (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u32;
+                        rgb |= byte << (16 - 8 * channel);
+                    }
+                    *color = rgb;
+                }
+            }
+        }
+    }
+
+    /// Blur the whole frame into the glow that bright areas give off
+    fn blur_glow(&mut self, width: usize, height: usize) {
+        self.glow.resize(width * height, [0.0; 3]);
+        self.scratch.resize(width * height, [0.0; 3]);
+        box_blur(
+            &self.colors,
+            &mut self.scratch,
+            width,
+            height,
+            BLOOM_RADIUS_X,
+            false,
+        );
+        box_blur(
+            &self.scratch,
+            &mut self.glow,
+            width,
+            height,
+            BLOOM_RADIUS_Y,
+            true,
+        );
+        // Squaring leaves mostly the glow of bright areas
+        for glow in &mut self.glow {
+            for value in glow.iter_mut() {
+                *value *= *value;
+            }
+        }
+    }
+
+    /// Stretch a source line to the output width, blurred along the line, through the mask
+    fn stretch_line(&mut self, y: usize, width: usize, out_width: usize) {
+        let CrtSettings {
+            mask, blur, bloom, ..
+        } = self.settings;
+        let line = &self.colors[y * width..(y + 1) * width];
+        let glow = &self.glow[y * width..(y + 1) * width];
+        for x in 0..out_width {
+            let position = (x as f32 + 0.5) * width as f32 / out_width as f32 - 0.5;
+            let left = (position.floor().max(0.0) as usize).min(width - 1);
+            let right = (left + 1).min(width - 1);
+            let fraction = (position - left as f32).clamp(0.0, 1.0);
+            let nearest = if fraction < 0.5 { left } else { right };
+
+            // Each column of the aperture grille shows one of red, green and blue
+            let stripe = x % 3;
+            let (out, out_glow) = (&mut self.line[x], &mut self.line_glow[x]);
+            for (channel, (value, value_glow)) in out.iter_mut().zip(out_glow).enumerate() {
+                let smooth =
+                    line[left][channel] * (1.0 - fraction) + line[right][channel] * fraction;
+                let color = line[nearest][channel] * (1.0 - blur) + smooth * blur;
+                let masked = if channel == stripe { 1.0 } else { 1.0 - mask };
+                *value = color * masked;
+                *value_glow = 1.0 - glow[nearest][channel] * bloom;
+            }
+        }
+    }
+}
+
+/// Average each value with those up to a radius away along rows or columns, clamped to the edges
+fn box_blur(
+    values: &[[f32; 3]],
+    out: &mut [[f32; 3]],
+    width: usize,
+    height: usize,
+    radius: usize,
+    vertical: bool,
+) {
+    let (lines, length) = if vertical {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    if length == 0 {
+        return;
+    }
+    for line in 0..lines {
+        let index = |i: usize| {
+            if vertical {
+                i * width + line
+            } else {
+                line * width + i
+            }
+        };
+        let mut sum = [0.0; 3];
+        // Start with the window around the first value, with the edge repeated before it
+        for i in 0..=radius {
+            let value = values[index(i.min(length - 1))];
+            let weight = if i == 0 { radius as f32 + 1.0 } else { 1.0 };
+            for channel in 0..3 {
+                sum[channel] += value[channel] * weight;
+            }
+        }
+        let count = (2 * radius + 1) as f32;
+        for i in 0..length {
+            for channel in 0..3 {
+                out[index(i)][channel] = sum[channel] / count;
+            }
+            let entering = values[index((i + radius + 1).min(length - 1))];
+            let leaving = values[index(i.saturating_sub(radius))];
+            for channel in 0..3 {
+                sum[channel] += entering[channel] - leaving[channel];
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use super::Palette;
+
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
+
+/// How each pixel of a frame is laid out in bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelFormat {
+    Rgb8,     // Bytes R, G, B
+    Rgba8,    // Bytes R, G, B, then 0xFF
+    Rgb565,   // A u16 of 5 bits of red, 6 of green and 5 of blue, in native byte order
+    Argb8888, // A u32 0xFFRRGGBB, in native byte order
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Argb8888 => 4,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+
+    /// Write a 0xRRGGBB color as one pixel, into exactly bytes_per_pixel bytes
+    pub fn write(self, color: u32, out: &mut [u8]) {
+        let (r, g, b) = ((color >> 16) as u8, (color >> 8) as u8, color as u8);
+        match self {
+            PixelFormat::Rgb8 => out.copy_from_slice(&[r, g, b]),
+            PixelFormat::Rgba8 => out.copy_from_slice(&[r, g, b, 0xFF]),
+            PixelFormat::Rgb565 => {
+                let packed = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+                out.copy_from_slice(&packed.to_ne_bytes());
+            }
+            PixelFormat::Argb8888 => out.copy_from_slice(&(0xFF00_0000 | color).to_ne_bytes()),
+        }
+    }
+}
+
+/// Convert a frame of color indices with emphasis into pixels of a format, without allocating.
+/// Each line starts `pitch` bytes after the last, which is at least a line of pixels, so that
+/// frames can be drawn straight into textures and framebuffers with padding.
+pub fn render_frame(
+    framebuffer: &[[u16; FRAME_WIDTH]; FRAME_HEIGHT],
+    palette: &Palette,
+    format: PixelFormat,
+    out: &mut [u8],
+    pitch: usize,
+) {
+    let line_bytes = FRAME_WIDTH * format.bytes_per_pixel();
+    assert!(pitch >= line_bytes, "pitch is shorter than a line");
+    assert!(
+        out.len() >= pitch * (FRAME_HEIGHT - 1) + line_bytes,
+        "buffer is too small for a frame"
+    );
+
+    for (line, out_line) in framebuffer.iter().zip(out.chunks_mut(pitch)) {
+        let pixels = out_line[..line_bytes].chunks_exact_mut(format.bytes_per_pixel());
+        for (&pixel, out_pixel) in line.iter().zip(pixels) {
+            format.write(palette.rgb(pixel), out_pixel);
+        }
+    }
+}
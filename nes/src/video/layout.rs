@@ -0,0 +1,202 @@
+use super::{parse_settings, FRAME_HEIGHT, FRAME_WIDTH};
+
+/// A rectangle of pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// How many pixels to crop from each edge of a frame, where TVs hid the picture behind the bezel
+/// https://wiki.nesdev.com/w/index.php/Overscan
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Overscan {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Overscan {
+    /// NTSC TVs hid about 8 lines at the top and bottom, where games often left garbage
+    pub const NTSC: Overscan = Overscan {
+        top: 8,
+        bottom: 8,
+        left: 0,
+        right: 0,
+    };
+    /// PAL TVs showed nearly every line, but the PAL PPU draws black over the top line and
+    /// 2 pixels at each side
+    pub const PAL: Overscan = Overscan {
+        top: 1,
+        bottom: 0,
+        left: 2,
+        right: 2,
+    };
+}
+
+/// "none", "ntsc", "pal", or margins like "top=8,bottom=8", where any left out are 0
+impl std::str::FromStr for Overscan {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => return Ok(Overscan::default()),
+            "ntsc" => return Ok(Overscan::NTSC),
+            "pal" => return Ok(Overscan::PAL),
+            _ => {}
+        }
+        let mut overscan = Overscan::default();
+        parse_settings(s, |name, value| {
+            if value < 0.0 || value.fract() != 0.0 {
+                return Err("overscan margins must be whole numbers of pixels");
+            }
+            let margin = value as usize;
+            match name {
+                "top" => overscan.top = margin,
+                "bottom" => overscan.bottom = margin,
+                "left" => overscan.left = margin,
+                "right" => overscan.right = margin,
+                _ => return Err("unknown overscan margin"),
+            }
+            Ok(())
+        })?;
+        if overscan.top + overscan.bottom >= FRAME_HEIGHT
+            || overscan.left + overscan.right >= FRAME_WIDTH
+        {
+            return Err("overscan can't crop the whole frame");
+        }
+        Ok(overscan)
+    }
+}
+
+/// The shape the picture is shown in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AspectRatio {
+    Square,     // Square pixels, as the frame is stored
+    Pixel8x7,   // Pixels 8/7 as wide as they are tall, as an NTSC TV shows them
+    Display4x3, // The whole picture stretched to the 4:3 shape of a TV screen
+}
+
+impl AspectRatio {
+    /// How wide each pixel is shown, for each unit of its height, in a picture of a size
+    pub fn pixel_aspect(self, width: usize, height: usize) -> f32 {
+        match self {
+            AspectRatio::Square => 1.0,
+            AspectRatio::Pixel8x7 => 8.0 / 7.0,
+            AspectRatio::Display4x3 => (4.0 / 3.0) * height as f32 / width as f32,
+        }
+    }
+}
+
+impl std::str::FromStr for AspectRatio {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(AspectRatio::Square),
+            "8:7" => Ok(AspectRatio::Pixel8x7),
+            "4:3" => Ok(AspectRatio::Display4x3),
+            _ => Err("unknown aspect ratio"),
+        }
+    }
+}
+
+impl std::fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AspectRatio::Square => write!(f, "square"),
+            AspectRatio::Pixel8x7 => write!(f, "8:7"),
+            AspectRatio::Display4x3 => write!(f, "4:3"),
+        }
+    }
+}
+
+/// Which part of a frame to show, and how to fit it in a window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub overscan: Overscan,
+    pub aspect_ratio: AspectRatio,
+    pub integer_scaling: bool, // Scale lines by a whole factor, so each is as tall as the rest
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            overscan: Overscan::default(),
+            aspect_ratio: AspectRatio::Square,
+            integer_scaling: false,
+        }
+    }
+}
+
+impl Layout {
+    /// The part of a picture that's shown, where the picture is the frame at any size,
+    /// such as scaled up or through the NTSC filter, and the overscan is scaled to match
+    pub fn crop(&self, width: usize, height: usize) -> Rect {
+        let Overscan {
+            top,
+            bottom,
+            left,
+            right,
+        } = self.overscan;
+        let x = left * width / FRAME_WIDTH;
+        let y = top * height / FRAME_HEIGHT;
+        Rect {
+            x,
+            y,
+            width: (FRAME_WIDTH - right) * width / FRAME_WIDTH - x,
+            height: (FRAME_HEIGHT - bottom) * height / FRAME_HEIGHT - y,
+        }
+    }
+
+    /// The size of the picture at a whole scale, to open a window at
+    pub fn size(&self, scale: usize) -> (usize, usize) {
+        let crop = self.crop(FRAME_WIDTH, FRAME_HEIGHT);
+        let pixel_aspect = self.aspect_ratio.pixel_aspect(crop.width, crop.height);
+        let width = (crop.width as f32 * pixel_aspect * scale as f32).round() as usize;
+        (width, crop.height * scale)
+    }
+
+    /// Where to draw the picture in a window, as large as fits and centered,
+    /// leaving black bars on the sides that don't fit
+    pub fn viewport(&self, window_width: usize, window_height: usize) -> Rect {
+        let crop = self.crop(FRAME_WIDTH, FRAME_HEIGHT);
+        let pixel_aspect = self.aspect_ratio.pixel_aspect(crop.width, crop.height);
+        letterbox(
+            crop.width as f32 * pixel_aspect,
+            crop.height,
+            self.integer_scaling,
+            window_width,
+            window_height,
+        )
+    }
+}
+
+/// Fit a picture of a height and a display width into a window, centered. With integer
+/// scaling, the height is scaled by a whole factor and the width follows it, which needn't be
+/// whole if the pixels aren't square.
+pub fn letterbox(
+    width: f32,
+    height: usize,
+    integer_scaling: bool,
+    window_width: usize,
+    window_height: usize,
+) -> Rect {
+    let mut scale = (window_width as f32 / width).min(window_height as f32 / height as f32);
+    if integer_scaling {
+        scale = scale.floor().max(1.0);
+    }
+    let (width, height) = (
+        (width * scale).round() as usize,
+        (height as f32 * scale).round() as usize,
+    );
+    Rect {
+        x: window_width.saturating_sub(width) / 2,
+        y: window_height.saturating_sub(height) / 2,
+        width,
+        height,
+    }
+}
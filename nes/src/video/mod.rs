@@ -0,0 +1,30 @@
+mod crt;
+mod frame;
+mod layout;
+mod ntsc;
+mod palette;
+mod scalers;
+
+pub use crt::{CrtFilter, CrtSettings};
+pub use frame::{render_frame, PixelFormat, FRAME_HEIGHT, FRAME_WIDTH};
+pub use layout::{letterbox, AspectRatio, Layout, Overscan, Rect};
+pub use ntsc::{NtscFilter, NtscSetup, NTSC_HEIGHT, NTSC_WIDTH};
+pub use palette::{Palette, PaletteSettings};
+pub use scalers::Scaler;
+
+/// Parse settings like "hue=-5,saturation=1.2", giving each name and value to `set`
+fn parse_settings(
+    s: &str,
+    mut set: impl FnMut(&str, f32) -> Result<(), &'static str>,
+) -> Result<(), &'static str> {
+    for setting in s.split(',').filter(|setting| !setting.is_empty()) {
+        let mut parts = setting.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts
+            .next()
+            .and_then(|value| value.trim().parse::<f32>().ok())
+            .ok_or("settings must be like name=<number>")?;
+        set(name, value)?;
+    }
+    Ok(())
+}
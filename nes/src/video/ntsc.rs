@@ -0,0 +1,279 @@
+use ppu::{COLOR_INDEX_MASK, EMPHASIS_SHIFT};
+
+use std::f32::consts::PI;
+
+// Like blargg's nes_ntsc, 256 pixels become 602, which is close to the right aspect ratio
+pub const NTSC_WIDTH: usize = 602;
+pub const NTSC_HEIGHT: usize = 240;
+
+// The PPU outputs 8 samples of its signal per pixel, at 12 samples per color subcarrier cycle
+// https://wiki.nesdev.com/w/index.php/NTSC_video
+const SAMPLES_PER_PIXEL: usize = 8;
+const SAMPLES_PER_CYCLE: usize = 12;
+const LINE_SAMPLES: usize = 256 * SAMPLES_PER_PIXEL;
+pub(crate) const PIXEL_VALUES: usize = 0x200;
+
+// Voltages relative to sync, for the low and high halves of the wave at each luma level
+const LOW_LEVELS: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const HIGH_LEVELS: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+const EMPHASIS_ATTENUATION: f32 = 0.746;
+// Where the color burst is relative to the samples, and the gain that chroma is decoded with,
+// which together give the same colors as the usual palettes
+const BURST_HUE: f32 = 120.0;
+pub(crate) const CHROMA_GAIN: f32 = 1.6;
+
+/// How the signal gets from the console to the TV, which decides how much luma and chroma
+/// interfere. Amounts are from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NtscSetup {
+    pub artifacts: f32, // Chroma leaking into luma, as dot crawl and rainbows along edges
+    pub fringing: f32,  // Luma edges leaking into chroma, as color fringes
+    pub bleed: f32,     // Color spreading sideways, up to 3 pixels
+    pub hue: f32,       // In degrees
+    pub saturation: f32,
+}
+
+impl NtscSetup {
+    /// The console's own composite output, with luma and chroma on one wire
+    pub fn composite() -> Self {
+        Self {
+            artifacts: 1.0,
+            fringing: 1.0,
+            bleed: 0.5,
+            hue: 0.0,
+            saturation: 1.0,
+        }
+    }
+
+    /// Luma and chroma on separate wires, as with a modded console, so only color blurs
+    pub fn svideo() -> Self {
+        Self {
+            artifacts: 0.0,
+            fringing: 0.0,
+            bleed: 0.5,
+            hue: 0.0,
+            saturation: 1.0,
+        }
+    }
+
+    /// A clean signal, as from an RGB PPU, which only softens the picture
+    pub fn rgb() -> Self {
+        Self {
+            artifacts: 0.0,
+            fringing: 0.0,
+            bleed: 0.0,
+            hue: 0.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for NtscSetup {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "composite" => Ok(NtscSetup::composite()),
+            "svideo" | "s-video" => Ok(NtscSetup::svideo()),
+            "rgb" => Ok(NtscSetup::rgb()),
+            _ => Err("unknown NTSC setup"),
+        }
+    }
+}
+
+/// Simulates the NTSC signal a frame makes, and decodes it as a TV would, in the spirit of
+/// blargg's nes_ntsc. Each line is generated as the PPU's square wave of voltages, from the
+/// color index and emphasis of each pixel, then separated back into luma and chroma.
+pub struct NtscFilter {
+    setup: NtscSetup,
+    cos: [f32; SAMPLES_PER_CYCLE],
+    sin: [f32; SAMPLES_PER_CYCLE],
+    // For each 9 bit pixel, its wave over a cycle, and what it decodes to alone
+    waves: Vec<[f32; SAMPLES_PER_CYCLE]>,
+    clean: Vec<(f32, f32, f32)>,
+    // Running totals of a line's signal, and of it demodulated, for averaging over any window
+    sums: [Vec<f32>; 3],
+    // A line's decoded luma and chroma, at each output pixel
+    luma: Vec<f32>,
+    i: Vec<f32>,
+    q: Vec<f32>,
+    scratch: Vec<f32>,
+}
+
+impl NtscFilter {
+    pub fn new(setup: NtscSetup) -> Self {
+        let mut cos = [0.0; SAMPLES_PER_CYCLE];
+        let mut sin = [0.0; SAMPLES_PER_CYCLE];
+        for phase in 0..SAMPLES_PER_CYCLE {
+            let angle = PI * phase as f32 / 6.0 + (BURST_HUE + setup.hue).to_radians();
+            cos[phase] = angle.cos();
+            sin[phase] = angle.sin();
+        }
+
+        let waves = (0..PIXEL_VALUES as u16)
+            .map(|pixel| {
+                let mut wave = [0.0; SAMPLES_PER_CYCLE];
+                for (phase, level) in wave.iter_mut().enumerate() {
+                    *level = signal_level(pixel, phase);
+                }
+                wave
+            })
+            .collect();
+        let clean = (0..PIXEL_VALUES as u16)
+            .map(|pixel| decode_pixel(pixel, setup.hue))
+            .collect();
+
+        Self {
+            setup,
+            cos,
+            sin,
+            waves,
+            clean,
+            sums: [
+                vec![0.0; LINE_SAMPLES + 1],
+                vec![0.0; LINE_SAMPLES + 1],
+                vec![0.0; LINE_SAMPLES + 1],
+            ],
+            luma: vec![0.0; NTSC_WIDTH],
+            i: vec![0.0; NTSC_WIDTH],
+            q: vec![0.0; NTSC_WIDTH],
+            scratch: vec![0.0; NTSC_WIDTH],
+        }
+    }
+
+    pub fn setup(&self) -> NtscSetup {
+        self.setup
+    }
+
+    /// Filter a frame of pixels, as color indices with emphasis, into a 602x240 frame of
+    /// 0xRRGGBB colors. The burst phase (0-2) is where the color subcarrier starts in the frame,
+    /// which NES::burst_phase follows from frame to frame, so that the dots crawl like on a TV.
+    pub fn render(&mut self, framebuffer: &[[u16; 256]; 240], burst_phase: usize, out: &mut [u32]) {
+        assert!(out.len() >= NTSC_WIDTH * NTSC_HEIGHT);
+        for (y, line) in framebuffer.iter().enumerate() {
+            // Each line is 341 dots of 8 samples, which moves the phase on by 4 samples
+            let phase = 4 * (burst_phase + y) % SAMPLES_PER_CYCLE;
+            self.decode_line(line, phase);
+            let out_line = &mut out[y * NTSC_WIDTH..(y + 1) * NTSC_WIDTH];
+            for (x, color) in out_line.iter_mut().enumerate() {
+                *color = yiq_to_rgb(self.luma[x], self.i[x], self.q[x]);
+            }
+        }
+    }
+
+    /// Fill the luma and chroma of each output pixel of a line
+    fn decode_line(&mut self, line: &[u16; 256], phase: usize) {
+        let [sums_y, sums_i, sums_q] = &mut self.sums;
+        let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+        let mut sample_phase = phase;
+        for (x, &pixel) in line.iter().enumerate() {
+            let wave = &self.waves[pixel as usize % PIXEL_VALUES];
+            for sample in x * SAMPLES_PER_PIXEL + 1..=(x + 1) * SAMPLES_PER_PIXEL {
+                let level = wave[sample_phase];
+                y += level;
+                i += level * self.cos[sample_phase];
+                q += level * self.sin[sample_phase];
+                sums_y[sample] = y;
+                sums_i[sample] = i;
+                sums_q[sample] = q;
+                sample_phase += 1;
+                if sample_phase == SAMPLES_PER_CYCLE {
+                    sample_phase = 0;
+                }
+            }
+        }
+
+        // A TV separates luma and chroma by averaging over a cycle of the subcarrier,
+        // which doesn't quite work where the colors change. What a pixel decodes to alone
+        // is mixed with that, by how much of each kind of crosstalk there is.
+        let saturation = CHROMA_GAIN * self.setup.saturation;
+        for x in 0..NTSC_WIDTH {
+            let sample = (x * LINE_SAMPLES + LINE_SAMPLES / 2) / NTSC_WIDTH;
+            let start = sample.saturating_sub(SAMPLES_PER_CYCLE / 2);
+            let end = (sample + SAMPLES_PER_CYCLE / 2).min(LINE_SAMPLES);
+            let average = |sums: &[f32]| (sums[end] - sums[start]) / (end - start) as f32;
+
+            let pixel = line[sample / SAMPLES_PER_PIXEL] as usize % PIXEL_VALUES;
+            let (clean_y, clean_i, clean_q) = self.clean[pixel];
+            let (artifacts, fringing) = (self.setup.artifacts, self.setup.fringing);
+            self.luma[x] = clean_y + artifacts * (average(sums_y) - clean_y);
+            self.i[x] = saturation * (clean_i + fringing * (average(sums_i) - clean_i));
+            self.q[x] = saturation * (clean_q + fringing * (average(sums_q) - clean_q));
+        }
+
+        let bleed = (self.setup.bleed * 3.0 * NTSC_WIDTH as f32 / 256.0) as usize;
+        if bleed > 0 {
+            for chroma in [&mut self.i, &mut self.q].iter_mut() {
+                box_filter(chroma, bleed, &mut self.scratch, sums_y);
+                chroma.copy_from_slice(&self.scratch);
+            }
+        }
+    }
+}
+
+/// What a TV would decode a pixel that goes on forever to, as its wave averaged over a cycle,
+/// before chroma is scaled by CHROMA_GAIN
+pub(crate) fn decode_pixel(pixel: u16, hue: f32) -> (f32, f32, f32) {
+    let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+    for phase in 0..SAMPLES_PER_CYCLE {
+        let level = signal_level(pixel, phase);
+        let angle = PI * phase as f32 / 6.0 + (BURST_HUE + hue).to_radians();
+        y += level;
+        i += level * angle.cos();
+        q += level * angle.sin();
+    }
+    let scale = 1.0 / SAMPLES_PER_CYCLE as f32;
+    (y * scale, i * scale, q * scale)
+}
+
+/// The voltage, from 0 at black to 1 at white, of a pixel's wave at a phase of the subcarrier
+/// https://wiki.nesdev.com/w/index.php/NTSC_video#Emulating_in_C.2B.2B_code
+fn signal_level(pixel: u16, phase: usize) -> f32 {
+    let index = pixel & COLOR_INDEX_MASK;
+    let color = (index & 0x0F) as usize;
+    let level = if color > 13 { 1 } else { (index >> 4) as usize };
+    let emphasis = pixel >> EMPHASIS_SHIFT;
+
+    let in_color_phase = |color: usize| (color + phase) % SAMPLES_PER_CYCLE < 6;
+    let (mut low, mut high) = (LOW_LEVELS[level], HIGH_LEVELS[level]);
+    if color == 0 {
+        low = high;
+    }
+    if color > 12 {
+        high = low;
+    }
+    let mut signal = if in_color_phase(color) { high } else { low };
+
+    // Emphasis attenuates the parts of the wave in phase with red, green or blue
+    if (emphasis & 1 != 0 && in_color_phase(0))
+        || (emphasis & 2 != 0 && in_color_phase(4))
+        || (emphasis & 4 != 0 && in_color_phase(8))
+    {
+        signal *= EMPHASIS_ATTENUATION;
+    }
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+/// Average each value with those up to a radius away, clamped to the ends
+fn box_filter(values: &[f32], radius: usize, out: &mut [f32], sums: &mut [f32]) {
+    sums[0] = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        sums[i + 1] = sums[i] + value;
+    }
+    for (i, value) in out.iter_mut().enumerate() {
+        let start = i.saturating_sub(radius);
+        let end = (i + radius).min(values.len());
+        *value = (sums[end] - sums[start]) / (end - start) as f32;
+    }
+}
+
+/// https://en.wikipedia.org/wiki/YIQ#From_YIQ_to_RGB
+pub(crate) fn yiq_to_rgb(y: f32, i: f32, q: f32) -> u32 {
+    let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u32;
+    let r = to_byte(y + 0.946_882 * i + 0.623_557 * q);
+    let g = to_byte(y - 0.274_788 * i - 0.635_691 * q);
+    let b = to_byte(y - 1.108_545 * i + 1.709_007 * q);
+    (r << 16) | (g << 8) | b
+}
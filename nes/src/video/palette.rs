@@ -0,0 +1,153 @@
+use super::ntsc::{decode_pixel, yiq_to_rgb, CHROMA_GAIN, PIXEL_VALUES};
+use super::parse_settings;
+use ppu::{pixel_to_rgb, COLOR_INDEX_MASK};
+
+use std::io::Read;
+
+const DEFAULT_COLORS: [u32; 64] = [
+    0x666666, 0x002A88, 0x1412A7, 0x3B00A4, 0x5C007E, 0x6E0040, 0x6C0600, 0x561D00, 0x333500,
+    0x0B4800, 0x005200, 0x004F08, 0x00404D, 0x000000, 0x000000, 0x000000, 0xADADAD, 0x155FD9,
+    0x4240FF, 0x7527FE, 0xA01ACC, 0xB71E7B, 0xB53120, 0x994E00, 0x6B6D00, 0x388700, 0x0C9300,
+    0x008F32, 0x007C8D, 0x000000, 0x000000, 0x000000, 0xFFFEFF, 0x64B0FF, 0x9290FF, 0xC676FF,
+    0xF36AFF, 0xFE6ECC, 0xFE8170, 0xEA9E22, 0xBCBE00, 0x88D800, 0x5CE430, 0x45E082, 0x48CDDE,
+    0x4F4F4F, 0x000000, 0x000000, 0xFFFEFF, 0xC0DFFF, 0xD3D2FF, 0xE8C8FF, 0xFBC2FF, 0xFEC4EA,
+    0xFECCC5, 0xF7D8A5, 0xE4E594, 0xCFEF96, 0xBDF4AB, 0xB3F3CC, 0xB5EBF2, 0xB8B8B8, 0x000000,
+    0x000000,
+];
+
+/// How the built-in palette generator decodes the PPU's signal, like a TV's picture settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaletteSettings {
+    pub hue: f32,        // In degrees
+    pub saturation: f32, // 1 is as decoded, 0 is greyscale
+    pub contrast: f32,   // 1 is as decoded
+    pub brightness: f32, // Added to every channel, from -1 to 1
+    pub gamma: f32,      // Above 1 brightens the midtones
+}
+
+impl Default for PaletteSettings {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+/// Settings like "hue=-5,saturation=1.2", where any that are left out are the default
+impl std::str::FromStr for PaletteSettings {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        parse_settings(s, |name, value| {
+            match name {
+                "hue" => settings.hue = value,
+                "saturation" => settings.saturation = value,
+                "contrast" => settings.contrast = value,
+                "brightness" => settings.brightness = value,
+                "gamma" if value > 0.0 => settings.gamma = value,
+                "gamma" => return Err("gamma must be above 0"),
+                _ => return Err("unknown palette setting"),
+            }
+            Ok(())
+        })?;
+        Ok(settings)
+    }
+}
+
+/// The RGB of every color the PPU can output, for each of the 64 color indices
+/// with each of the 8 combinations of emphasis bits
+/// https://wiki.nesdev.com/w/index.php/PPU_palettes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<u32>, // 0xRRGGBB, indexed by framebuffer pixel
+}
+
+impl Palette {
+    /// KindNES's usual palette
+    pub fn new() -> Self {
+        Self::from_base_colors(&DEFAULT_COLORS)
+    }
+
+    /// Emphasize 64 colors by darkening their other channels, as the PPU roughly does
+    fn from_base_colors(base_colors: &[u32]) -> Self {
+        let colors = (0..PIXEL_VALUES as u16)
+            .map(|pixel| pixel_to_rgb(pixel, base_colors))
+            .collect();
+        Self { colors }
+    }
+
+    /// Load a .pal file, which is 64 colors of 3 bytes (R, G, B), or 512 colors that include
+    /// every combination of emphasis bits in order
+    pub fn load(mut reader: impl Read) -> Result<Self, &'static str> {
+        let mut bytes = Vec::new();
+        if reader.read_to_end(&mut bytes).is_err() {
+            return Err("failed to read palette");
+        }
+
+        let colors: Vec<u32> = bytes
+            .chunks_exact(3)
+            .map(|rgb| ((rgb[0] as u32) << 16) | ((rgb[1] as u32) << 8) | rgb[2] as u32)
+            .collect();
+        match bytes.len() {
+            192 => Ok(Self::from_base_colors(&colors)),
+            1536 => Ok(Self { colors }),
+            _ => Err("palette files must be 192 or 1536 bytes"),
+        }
+    }
+
+    /// Generate the palette by decoding each color's NTSC signal, from the voltage levels
+    /// the PPU outputs, so that emphasis is as accurate as the signal
+    /// https://wiki.nesdev.com/w/index.php/NTSC_video
+    pub fn generate(settings: PaletteSettings) -> Self {
+        let saturation = CHROMA_GAIN * settings.saturation;
+        let colors = (0..PIXEL_VALUES as u16)
+            .map(|pixel| {
+                let (y, i, q) = decode_pixel(pixel, settings.hue);
+                let rgb = yiq_to_rgb(
+                    y * settings.contrast,
+                    i * saturation * settings.contrast,
+                    q * saturation * settings.contrast,
+                );
+
+                let mut color = 0;
+                for shift in &[16, 8, 0] {
+                    let value = ((rgb >> shift) & 0xFF) as f32 / 255.0 + settings.brightness;
+                    let value = value.clamp(0.0, 1.0).powf(1.0 / settings.gamma);
+                    color |= ((value * 255.0).round() as u32) << shift;
+                }
+                color
+            })
+            .collect();
+        Self { colors }
+    }
+
+    /// The 0xRRGGBB of a framebuffer pixel, with its emphasis
+    pub fn rgb(&self, pixel: u16) -> u32 {
+        self.colors[pixel as usize % PIXEL_VALUES]
+    }
+
+    /// The 64 colors without emphasis, as the viewers take them
+    pub fn colors(&self) -> &[u32] {
+        &self.colors[..=COLOR_INDEX_MASK as usize]
+    }
+
+    /// Save as a .pal file with every combination of emphasis bits
+    pub fn to_pal(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.colors.len() * 3);
+        for &color in &self.colors {
+            bytes.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+        bytes
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
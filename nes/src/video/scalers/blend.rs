@@ -0,0 +1,119 @@
+use super::{interpolate, write_block, Image};
+
+// Colors are compared in YUV, where these are the largest differences that are still alike
+const Y_THRESHOLD: i32 = 48;
+const U_THRESHOLD: i32 = 7;
+const V_THRESHOLD: i32 = 6;
+
+// Indices of the neighbors of a pixel, by rows, that each corner of its block is between:
+// the neighbors across the two edges at the corner, then the one diagonally across it
+const TOP_LEFT: [usize; 3] = [1, 3, 0];
+const TOP_RIGHT: [usize; 3] = [1, 5, 2];
+const BOTTOM_LEFT: [usize; 3] = [7, 3, 6];
+const BOTTOM_RIGHT: [usize; 3] = [7, 5, 8];
+
+/// Blend each corner of a pixel's block with the neighbors around it, by which of them look
+/// different from it, so that edges between colors are smoothed while areas of one color and
+/// lines stay sharp
+pub(super) fn scale(image: &Image, factor: usize, out: &mut [u32]) {
+    let yuv: Vec<_> = image.src.iter().map(|&color| to_yuv(color)).collect();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let indices = image.neighbor_indices(x, y);
+            let mut neighbors = Neighbors {
+                colors: [0; 9],
+                yuv: [(0, 0, 0); 9],
+            };
+            for (i, &index) in indices.iter().enumerate() {
+                neighbors.colors[i] = image.src[index];
+                neighbors.yuv[i] = yuv[index];
+            }
+            let c = neighbors.colors[4];
+            let corner = |corner| blend_corner(&neighbors, corner, factor);
+            let (top_left, top_right) = (corner(TOP_LEFT), corner(TOP_RIGHT));
+            let (bottom_left, bottom_right) = (corner(BOTTOM_LEFT), corner(BOTTOM_RIGHT));
+            if factor == 2 {
+                let block = [top_left, top_right, bottom_left, bottom_right];
+                write_block(out, image, 2, x, y, &block);
+            } else {
+                let block = [
+                    top_left,
+                    blend_edge(&neighbors, 1, 3, 5),
+                    top_right,
+                    blend_edge(&neighbors, 3, 1, 7),
+                    c,
+                    blend_edge(&neighbors, 5, 1, 7),
+                    bottom_left,
+                    blend_edge(&neighbors, 7, 3, 5),
+                    bottom_right,
+                ];
+                write_block(out, image, 3, x, y, &block);
+            }
+        }
+    }
+}
+
+/// The 3x3 pixels around a pixel, with their colors in YUV
+struct Neighbors {
+    colors: [u32; 9],
+    yuv: [(i32, i32, i32); 9],
+}
+
+impl Neighbors {
+    /// Whether two of the pixels look different
+    fn differ(&self, a: usize, b: usize) -> bool {
+        let ((y_a, u_a, v_a), (y_b, u_b, v_b)) = (self.yuv[a], self.yuv[b]);
+        (y_a - y_b).abs() > Y_THRESHOLD
+            || (u_a - u_b).abs() > U_THRESHOLD
+            || (v_a - v_b).abs() > V_THRESHOLD
+    }
+}
+
+fn blend_corner(neighbors: &Neighbors, corner: [usize; 3], factor: usize) -> u32 {
+    const CENTER: usize = 4;
+    let [edge_1, edge_2, diagonal] = corner;
+    let differ = |x, y| neighbors.differ(x, y);
+    let color = |i: usize| neighbors.colors[i];
+    let (c, a, b, d) = (color(CENTER), color(edge_1), color(edge_2), color(diagonal));
+    match (differ(CENTER, edge_1), differ(CENTER, edge_2)) {
+        // Inside an area of about one color
+        (false, false) => interpolate(&[(c, 2), (a, 1), (b, 1)]),
+        // Along an edge, so blend only with the side that's alike
+        (true, false) if differ(CENTER, diagonal) => interpolate(&[(c, 3), (b, 1)]),
+        (true, false) => interpolate(&[(c, 2), (d, 1), (b, 1)]),
+        (false, true) if differ(CENTER, diagonal) => interpolate(&[(c, 3), (a, 1)]),
+        (false, true) => interpolate(&[(c, 2), (d, 1), (a, 1)]),
+        // At a corner where two different colors meet, which stays sharp
+        (true, true) if differ(edge_1, edge_2) && differ(CENTER, diagonal) => c,
+        (true, true) if differ(edge_1, edge_2) => interpolate(&[(c, 3), (d, 1)]),
+        // Where one color's edge crosses the corner, which is rounded off, unless this pixel
+        // continues diagonally as part of a thin line
+        (true, true) if !differ(CENTER, diagonal) => interpolate(&[(c, 6), (a, 1), (b, 1)]),
+        (true, true) if factor == 2 => interpolate(&[(c, 2), (a, 1), (b, 1)]),
+        (true, true) => interpolate(&[(c, 2), (a, 7), (b, 7)]),
+    }
+}
+
+/// The middle of one side of a 3x block, which is blended with the neighbor across it
+/// where an edge crosses the corners at either end
+fn blend_edge(neighbors: &Neighbors, across: usize, side_1: usize, side_2: usize) -> u32 {
+    const CENTER: usize = 4;
+    let differ = |x, y| neighbors.differ(x, y);
+    let crosses = |side| differ(CENTER, across) && differ(CENTER, side) && !differ(across, side);
+    let (c, across) = (neighbors.colors[CENTER], neighbors.colors[across]);
+    match (crosses(side_1), crosses(side_2)) {
+        (true, true) => interpolate(&[(c, 1), (across, 1)]),
+        (true, false) | (false, true) => interpolate(&[(c, 3), (across, 1)]),
+        (false, false) => c,
+    }
+}
+
+fn to_yuv(color: u32) -> (i32, i32, i32) {
+    let r = ((color >> 16) & 0xFF) as f32;
+    let g = ((color >> 8) & 0xFF) as f32;
+    let b = (color & 0xFF) as f32;
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let u = -0.169 * r - 0.331 * g + 0.5 * b + 128.0;
+    let v = 0.5 * r - 0.419 * g - 0.081 * b + 128.0;
+    (y as i32, u as i32, v as i32)
+}
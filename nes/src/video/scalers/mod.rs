@@ -0,0 +1,140 @@
+mod blend;
+mod scalex;
+mod xbrz;
+
+/// A pixel art upscaler, which enlarges a frame of 0xRRGGBB colors by a whole factor,
+/// smoothing the edges between colors rather than just making each pixel bigger
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scaler {
+    Scale2x,
+    Scale3x,
+    Blend2x, // Blends the corners of each pixel with its neighbors by which look alike
+    Blend3x,
+    Xbrz(usize), // Any factor from 2 to 6
+}
+
+impl Scaler {
+    pub fn factor(self) -> usize {
+        match self {
+            Scaler::Scale2x | Scaler::Blend2x => 2,
+            Scaler::Scale3x | Scaler::Blend3x => 3,
+            Scaler::Xbrz(factor) => factor,
+        }
+    }
+
+    /// Scale a frame into one `factor` times as wide and as tall
+    pub fn scale(self, src: &[u32], width: usize, height: usize, out: &mut [u32]) {
+        let factor = self.factor();
+        assert!(
+            src.len() >= width * height,
+            "source is smaller than the frame"
+        );
+        assert!(
+            out.len() >= width * height * factor * factor,
+            "buffer is too small for the scaled frame"
+        );
+
+        let image = Image { src, width, height };
+        match self {
+            Scaler::Scale2x => scalex::scale2x(&image, out),
+            Scaler::Scale3x => scalex::scale3x(&image, out),
+            Scaler::Blend2x | Scaler::Blend3x => blend::scale(&image, factor, out),
+            Scaler::Xbrz(factor) => {
+                assert!((2..=6).contains(&factor), "xBRZ scales by 2 to 6");
+                xbrz::scale(&image, factor, out)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Scaler {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scale2x" => Ok(Scaler::Scale2x),
+            "scale3x" => Ok(Scaler::Scale3x),
+            "blend2x" => Ok(Scaler::Blend2x),
+            "blend3x" => Ok(Scaler::Blend3x),
+            "xbrz2" => Ok(Scaler::Xbrz(2)),
+            "xbrz3" => Ok(Scaler::Xbrz(3)),
+            "xbrz4" => Ok(Scaler::Xbrz(4)),
+            "xbrz5" => Ok(Scaler::Xbrz(5)),
+            "xbrz6" => Ok(Scaler::Xbrz(6)),
+            _ => Err("unknown scaler"),
+        }
+    }
+}
+
+impl std::fmt::Display for Scaler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scaler::Scale2x => write!(f, "scale2x"),
+            Scaler::Scale3x => write!(f, "scale3x"),
+            Scaler::Blend2x => write!(f, "blend2x"),
+            Scaler::Blend3x => write!(f, "blend3x"),
+            Scaler::Xbrz(factor) => write!(f, "xbrz{}", factor),
+        }
+    }
+}
+
+/// A frame being scaled, where pixels past the edges are the nearest edge pixel
+struct Image<'a> {
+    src: &'a [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Image<'_> {
+    fn index(&self, x: isize, y: isize) -> usize {
+        let x = x.max(0).min(self.width as isize - 1) as usize;
+        let y = y.max(0).min(self.height as isize - 1) as usize;
+        y * self.width + x
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> u32 {
+        self.src[self.index(x, y)]
+    }
+
+    /// The indices of the 3x3 pixels around a pixel, by rows
+    fn neighbor_indices(&self, x: usize, y: usize) -> [usize; 9] {
+        let (x, y) = (x as isize, y as isize);
+        let mut indices = [0; 9];
+        for (i, index) in indices.iter_mut().enumerate() {
+            *index = self.index(x + (i % 3) as isize - 1, y + (i / 3) as isize - 1);
+        }
+        indices
+    }
+
+    /// The 3x3 pixels around a pixel, by rows
+    fn neighbors(&self, x: usize, y: usize) -> [u32; 9] {
+        let mut neighbors = [0; 9];
+        for (neighbor, &i) in neighbors.iter_mut().zip(self.neighbor_indices(x, y).iter()) {
+            *neighbor = self.src[i];
+        }
+        neighbors
+    }
+}
+
+/// The average of colors, each counted a number of times
+fn interpolate(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|&(_, weight)| weight).sum();
+    let mut color = 0;
+    for shift in &[16, 8, 0] {
+        let sum: u32 = colors
+            .iter()
+            .map(|&(color, weight)| ((color >> shift) & 0xFF) * weight)
+            .sum();
+        color |= (sum / total) << shift;
+    }
+    color
+}
+
+/// Write the `factor` by `factor` block of output pixels that a source pixel scales to, by rows
+fn write_block(out: &mut [u32], image: &Image, factor: usize, x: usize, y: usize, block: &[u32]) {
+    let out_width = image.width * factor;
+    for (row, pixels) in block.chunks_exact(factor).enumerate() {
+        let start = (y * factor + row) * out_width + x * factor;
+        out[start..start + factor].copy_from_slice(pixels);
+    }
+}
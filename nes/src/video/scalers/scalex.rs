@@ -0,0 +1,66 @@
+use super::{write_block, Image};
+
+// The neighbors of a pixel E are named by rows:
+// A B C
+// D E F
+// G H I
+// https://www.scale2x.it/algorithm
+
+pub(super) fn scale2x(image: &Image, out: &mut [u32]) {
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let [_, b, _, d, e, f, _, h, _] = image.neighbors(x, y);
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if b == f { f } else { e },
+                    if d == h { d } else { e },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 4]
+            };
+            write_block(out, image, 2, x, y, &block);
+        }
+    }
+}
+
+pub(super) fn scale3x(image: &Image, out: &mut [u32]) {
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let [a, b, c, d, e, f, g, h, i] = image.neighbors(x, y);
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+            write_block(out, image, 3, x, y, &block);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use super::Image;
+
+// Hyllian's xBR as refined by Zenju's xBRZ, with its default settings
+// https://sourceforge.net/projects/xbrz/
+const LUMINANCE_WEIGHT: f32 = 1.0;
+const EQUAL_COLOR_TOLERANCE: f32 = 30.0;
+const DOMINANT_DIRECTION_THRESHOLD: f32 = 3.6;
+const STEEP_DIRECTION_THRESHOLD: f32 = 2.2;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Blend {
+    None,
+    Normal,
+    Dominant, // Along an edge that's much clearer in one direction
+}
+
+// The corners of a pixel, clockwise, so that rotating the pixel rotates the array
+const TOP_LEFT: usize = 0;
+const TOP_RIGHT: usize = 1;
+const BOTTOM_RIGHT: usize = 2;
+const BOTTOM_LEFT: usize = 3;
+
+/// Which output pixels of the bottom right corner of a block are blended with a color,
+/// as (row, column, alpha numerator, alpha denominator), for each kind of edge there
+struct BlendRules {
+    shallow: &'static [(usize, usize, u32, u32)], // Steep lines are the same, transposed
+    steep_and_shallow: &'static [(usize, usize, u32, u32)],
+    diagonal: &'static [(usize, usize, u32, u32)],
+    corner: &'static [(usize, usize, u32, u32)],
+}
+
+// For scales of 2 to 6
+const RULES: [BlendRules; 5] = [
+    BlendRules {
+        shallow: &[(1, 0, 1, 4), (1, 1, 3, 4)],
+        steep_and_shallow: &[(1, 0, 1, 4), (0, 1, 1, 4), (1, 1, 5, 6)],
+        diagonal: &[(1, 1, 1, 2)],
+        corner: &[(1, 1, 21, 100)],
+    },
+    BlendRules {
+        shallow: &[(2, 0, 1, 4), (1, 2, 1, 4), (2, 1, 3, 4), (2, 2, 1, 1)],
+        steep_and_shallow: &[
+            (2, 0, 1, 4),
+            (0, 2, 1, 4),
+            (2, 1, 3, 4),
+            (1, 2, 3, 4),
+            (2, 2, 1, 1),
+        ],
+        diagonal: &[(1, 2, 1, 8), (2, 1, 1, 8), (2, 2, 7, 8)],
+        corner: &[(2, 2, 45, 100)],
+    },
+    BlendRules {
+        shallow: &[
+            (3, 0, 1, 4),
+            (2, 2, 1, 4),
+            (3, 1, 3, 4),
+            (2, 3, 3, 4),
+            (3, 2, 1, 1),
+            (3, 3, 1, 1),
+        ],
+        steep_and_shallow: &[
+            (3, 1, 3, 4),
+            (1, 3, 3, 4),
+            (3, 0, 1, 4),
+            (0, 3, 1, 4),
+            (2, 2, 1, 3),
+            (3, 3, 1, 1),
+            (3, 2, 1, 1),
+            (2, 3, 1, 1),
+        ],
+        diagonal: &[(3, 2, 1, 2), (2, 3, 1, 2), (3, 3, 1, 1)],
+        corner: &[(3, 3, 68, 100), (3, 2, 9, 100), (2, 3, 9, 100)],
+    },
+    BlendRules {
+        shallow: &[
+            (4, 0, 1, 4),
+            (3, 2, 1, 4),
+            (2, 4, 1, 4),
+            (4, 1, 3, 4),
+            (3, 3, 3, 4),
+            (4, 2, 1, 1),
+            (4, 3, 1, 1),
+            (4, 4, 1, 1),
+            (3, 4, 1, 1),
+        ],
+        steep_and_shallow: &[
+            (0, 4, 1, 4),
+            (2, 3, 1, 4),
+            (1, 4, 3, 4),
+            (4, 0, 1, 4),
+            (3, 2, 1, 4),
+            (4, 1, 3, 4),
+            (3, 3, 2, 3),
+            (2, 4, 1, 1),
+            (3, 4, 1, 1),
+            (4, 4, 1, 1),
+            (4, 2, 1, 1),
+            (4, 3, 1, 1),
+        ],
+        diagonal: &[
+            (4, 2, 1, 8),
+            (3, 3, 1, 8),
+            (2, 4, 1, 8),
+            (4, 3, 7, 8),
+            (3, 4, 7, 8),
+            (4, 4, 1, 1),
+        ],
+        corner: &[(4, 4, 86, 100), (4, 3, 23, 100), (3, 4, 23, 100)],
+    },
+    BlendRules {
+        shallow: &[
+            (5, 0, 1, 4),
+            (4, 2, 1, 4),
+            (3, 4, 1, 4),
+            (5, 1, 3, 4),
+            (4, 3, 3, 4),
+            (3, 5, 3, 4),
+            (5, 2, 1, 1),
+            (5, 3, 1, 1),
+            (5, 4, 1, 1),
+            (5, 5, 1, 1),
+            (4, 4, 1, 1),
+            (4, 5, 1, 1),
+        ],
+        steep_and_shallow: &[
+            (0, 5, 1, 4),
+            (2, 4, 1, 4),
+            (1, 5, 3, 4),
+            (3, 4, 3, 4),
+            (5, 0, 1, 4),
+            (4, 2, 1, 4),
+            (5, 1, 3, 4),
+            (4, 3, 3, 4),
+            (2, 5, 1, 1),
+            (3, 5, 1, 1),
+            (4, 5, 1, 1),
+            (5, 5, 1, 1),
+            (4, 4, 1, 1),
+            (5, 4, 1, 1),
+            (5, 2, 1, 1),
+            (5, 3, 1, 1),
+        ],
+        diagonal: &[
+            (5, 3, 1, 2),
+            (4, 4, 1, 2),
+            (3, 5, 1, 2),
+            (4, 5, 1, 1),
+            (5, 5, 1, 1),
+            (5, 4, 1, 1),
+        ],
+        corner: &[
+            (5, 5, 97, 100),
+            (4, 5, 42, 100),
+            (5, 4, 42, 100),
+            (5, 3, 6, 100),
+            (3, 5, 6, 100),
+        ],
+    },
+];
+
+pub(super) fn scale(image: &Image, factor: usize, out: &mut [u32]) {
+    let corners = detect_corners(image);
+    let out_width = image.width * factor;
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let neighbors = image.neighbors(x, y);
+            for row in 0..factor {
+                let start = (y * factor + row) * out_width + x * factor;
+                for pixel in &mut out[start..start + factor] {
+                    *pixel = neighbors[4];
+                }
+            }
+
+            let pixel_corners = corners[y * image.width + x];
+            if pixel_corners.iter().all(|&blend| blend == Blend::None) {
+                continue;
+            }
+            // Each corner is blended the same way, by turning the pixel to put it bottom right
+            for rotation in 0..4 {
+                let mut kernel = [0; 9];
+                for (i, pixel) in kernel.iter_mut().enumerate() {
+                    let (row, column) = rotate(i / 3, i % 3, 3, rotation);
+                    *pixel = neighbors[row * 3 + column];
+                }
+                let mut rotated_corners = [Blend::None; 4];
+                for (i, blend) in rotated_corners.iter_mut().enumerate() {
+                    *blend = pixel_corners[(i + 4 - rotation) % 4];
+                }
+
+                let mut block = Block {
+                    out,
+                    start: y * factor * out_width + x * factor,
+                    out_width,
+                    factor,
+                    rotation,
+                };
+                blend_pixel(&kernel, &rotated_corners, &RULES[factor - 2], &mut block);
+            }
+        }
+    }
+}
+
+/// The block of output pixels that a pixel scales to, turned along with the pixel
+struct Block<'a> {
+    out: &'a mut [u32],
+    start: usize, // Index of the top left output pixel
+    out_width: usize,
+    factor: usize,
+    rotation: usize,
+}
+
+impl Block<'_> {
+    fn pixel(&mut self, row: usize, column: usize) -> &mut u32 {
+        let (row, column) = rotate(row, column, self.factor, self.rotation);
+        &mut self.out[self.start + row * self.out_width + column]
+    }
+}
+
+/// The point of an n by n square that's at a row and column once the square is turned
+/// a quarter clockwise some number of times
+fn rotate(row: usize, column: usize, n: usize, rotation: usize) -> (usize, usize) {
+    let (mut row, mut column) = (row, column);
+    for _ in 0..rotation {
+        let turned = (n - 1 - column, row);
+        row = turned.0;
+        column = turned.1;
+    }
+    (row, column)
+}
+
+/// Decide, for the corners between each 2x2 block of pixels, whether an edge runs diagonally
+/// through it, by which diagonal has the more different colors along it
+fn detect_corners(image: &Image) -> Vec<[Blend; 4]> {
+    let mut corners = vec![[Blend::None; 4]; image.width * image.height];
+    for y in -1..image.height as isize {
+        for x in -1..image.width as isize {
+            // The 4x4 pixels around the block of f, g, j and k
+            // a b c d
+            // e f g h
+            // i j k l
+            // m n o p
+            let mut kernel = [0; 16];
+            for (i, pixel) in kernel.iter_mut().enumerate() {
+                *pixel = image.pixel(x - 1 + (i % 4) as isize, y - 1 + (i / 4) as isize);
+            }
+            let [_, b, c, _, e, f, g, h, i, j, k, l, _, n, o, _] = kernel;
+            if (f == g && j == k) || (f == j && g == k) {
+                continue;
+            }
+
+            let jg = distance(i, f)
+                + distance(f, c)
+                + distance(n, k)
+                + distance(k, h)
+                + 4.0 * distance(j, g);
+            let fk = distance(e, j)
+                + distance(j, o)
+                + distance(b, g)
+                + distance(g, l)
+                + 4.0 * distance(f, k);
+            let mut set = |dx: isize, dy: isize, corner: usize, dominant: bool| {
+                let (x, y) = (x + dx, y + dy);
+                if x >= 0 && y >= 0 && (x as usize) < image.width && (y as usize) < image.height {
+                    corners[y as usize * image.width + x as usize][corner] = if dominant {
+                        Blend::Dominant
+                    } else {
+                        Blend::Normal
+                    };
+                }
+            };
+            if jg < fk {
+                let dominant = DOMINANT_DIRECTION_THRESHOLD * jg < fk;
+                if f != g && f != j {
+                    set(0, 0, BOTTOM_RIGHT, dominant);
+                }
+                if k != j && k != g {
+                    set(1, 1, TOP_LEFT, dominant);
+                }
+            } else if fk < jg {
+                let dominant = DOMINANT_DIRECTION_THRESHOLD * fk < jg;
+                if j != f && j != k {
+                    set(0, 1, TOP_RIGHT, dominant);
+                }
+                if g != f && g != k {
+                    set(1, 0, BOTTOM_LEFT, dominant);
+                }
+            }
+        }
+    }
+    corners
+}
+
+/// Blend the bottom right corner of a pixel's block with its neighbors, as a line through it
+/// if the edge continues, or just rounding off the corner if not
+fn blend_pixel(kernel: &[u32; 9], corners: &[Blend; 4], rules: &BlendRules, block: &mut Block) {
+    if corners[BOTTOM_RIGHT] == Blend::None {
+        return;
+    }
+    let [_, b, c, d, e, f, g, h, i] = *kernel;
+    let eq = |x: u32, y: u32| distance(x, y) < EQUAL_COLOR_TOLERANCE;
+
+    let line_blend = if corners[BOTTOM_RIGHT] >= Blend::Dominant {
+        true
+    } else if (corners[TOP_RIGHT] != Blend::None && !eq(e, g))
+        || (corners[BOTTOM_LEFT] != Blend::None && !eq(e, c))
+    {
+        // Another corner blends too, as with a single pixel, unless it's a 90 degree corner
+        false
+    } else {
+        // An L shape only has its corner rounded
+        eq(e, i) || !eq(g, h) || !eq(h, i) || !eq(i, f) || !eq(f, c)
+    };
+
+    let color = if distance(e, f) <= distance(e, h) {
+        f
+    } else {
+        h
+    };
+    let mut apply = |ops: &[(usize, usize, u32, u32)], transpose: bool| {
+        for &(row, column, m, n) in ops {
+            let (row, column) = if transpose {
+                (column, row)
+            } else {
+                (row, column)
+            };
+            let pixel = block.pixel(row, column);
+            *pixel = alpha_blend(*pixel, color, m, n);
+        }
+    };
+
+    if !line_blend {
+        apply(rules.corner, false);
+        return;
+    }
+    let fg = distance(f, g);
+    let hc = distance(h, c);
+    let shallow = STEEP_DIRECTION_THRESHOLD * fg <= hc && e != g && d != g;
+    let steep = STEEP_DIRECTION_THRESHOLD * hc <= fg && e != c && b != c;
+    match (shallow, steep) {
+        (true, true) => apply(rules.steep_and_shallow, false),
+        (true, false) => apply(rules.shallow, false),
+        (false, true) => apply(rules.shallow, true),
+        (false, false) => apply(rules.diagonal, false),
+    }
+}
+
+/// How different two colors look, in YCbCr
+fn distance(x: u32, y: u32) -> f32 {
+    const K_B: f32 = 0.0593;
+    const K_R: f32 = 0.2627;
+    const K_G: f32 = 1.0 - K_B - K_R;
+    let channel = |color: u32, shift: u32| ((color >> shift) & 0xFF) as f32;
+    let r = channel(x, 16) - channel(y, 16);
+    let g = channel(x, 8) - channel(y, 8);
+    let b = channel(x, 0) - channel(y, 0);
+
+    let luma = K_R * r + K_G * g + K_B * b;
+    let c_b = 0.5 / (1.0 - K_B) * (b - luma);
+    let c_r = 0.5 / (1.0 - K_R) * (r - luma);
+    ((LUMINANCE_WEIGHT * luma).powi(2) + c_b.powi(2) + c_r.powi(2)).sqrt()
+}
+
+/// Mix m/n of the front color into the back color
+fn alpha_blend(back: u32, front: u32, m: u32, n: u32) -> u32 {
+    let mut color = 0;
+    for shift in &[16, 8, 0] {
+        let back = (back >> shift) & 0xFF;
+        let front = (front >> shift) & 0xFF;
+        color |= ((front * m + back * (n - m)) / n) << shift;
+    }
+    color
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::{load_nestest, load_nestest_automated};
+use nes::debugger::{Breakpoint, Interrupt};
+use nes::trace::{TraceFormat, TraceLogger};
+use nes::NES;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn run_until_paused(nes: &mut NES) {
+    for _ in 0..1_000_000 {
+        nes.tick();
+        if nes.paused {
+            return;
+        }
+    }
+    panic!("no breakpoint was hit");
+}
+
+#[test]
+fn breakpoint_execute() {
+    let mut nes = load_nestest_automated();
+    let id = nes.add_breakpoint(Breakpoint::Execute(0xC72D));
+    run_until_paused(&mut nes);
+
+    // C5FD  20 2D C7  JSR $C72D
+    let hit = nes.take_break_hit().unwrap();
+    assert_eq!(hit.id, id);
+    assert_eq!(hit.pc, 0xC72D);
+    assert_eq!(hit.access, None);
+    let registers = nes.cpu_registers();
+    assert_eq!((registers.pc, registers.s), (0xC72D, 0xFB));
+
+    // Resuming runs the instruction that was halted on, rather than hitting it again
+    nes.step_instruction();
+    assert_eq!(nes.cpu_registers().pc, 0xC72E);
+    assert!(nes.take_break_hit().is_none());
+
+    assert!(nes.remove_breakpoint(id));
+    assert!(!nes.remove_breakpoint(id));
+    nes.paused = false;
+    for _ in 0..100_000 {
+        nes.tick();
+    }
+    assert!(!nes.paused);
+}
+
+#[test]
+fn step_onto_breakpoint() {
+    let mut nes = load_nestest_automated();
+    let id = nes.add_breakpoint(Breakpoint::Execute(0xC5F7));
+    // C000  JMP $C5F5, then C5F5  LDX #$00 lands on the breakpoint without hitting it
+    nes.step_instruction();
+    nes.step_instruction();
+    assert_eq!(nes.cpu_registers().pc, 0xC5F7);
+    assert!(nes.take_break_hit().is_none());
+
+    // Stepping from there runs the instruction, rather than hitting the breakpoint in place
+    nes.step_instruction();
+    assert!(nes.take_break_hit().is_none());
+    assert_eq!(nes.cpu_registers().pc, 0xC5F9);
+
+    // Only the instruction a step starts on is let through
+    let mut registers = nes.cpu_registers();
+    registers.pc = 0xC5F5;
+    nes.set_cpu_registers(registers);
+    nes.run_to(0xC5F9);
+    assert_eq!(nes.take_break_hit().map(|hit| hit.id), Some(id));
+    assert_eq!(nes.cpu_registers().pc, 0xC5F7);
+}
+
+#[test]
+fn breakpoint_write() {
+    let mut nes = load_nestest_automated();
+    nes.add_breakpoint(Breakpoint::Read(0x0010, 0x0011));
+    let id = nes.add_breakpoint(Breakpoint::Write(0x0010, 0x0011));
+    run_until_paused(&mut nes);
+
+    // C5F9  86 10     STX $10 = 00, paused after the write
+    let hit = nes.take_break_hit().unwrap();
+    assert_eq!(hit.id, id);
+    assert_eq!(hit.pc, 0xC5F9);
+    assert_eq!(hit.access, Some((0x0010, 0x00)));
+    assert_eq!(nes.cpu_registers().pc, 0xC5FB);
+}
+
+#[test]
+fn breakpoint_dmc_read() {
+    let mut nes = load_nestest();
+    // Play a one-byte DMC sample from $C000, then loop forever
+    let program = [
+        0xA9, 0x00, 0x8D, 0x12, 0x40, // 0300  LDA #$00, 0302  STA $4012
+        0xA9, 0x00, 0x8D, 0x13, 0x40, // 0305  LDA #$00, 0307  STA $4013
+        0xA9, 0x10, 0x8D, 0x15, 0x40, // 030A  LDA #$10, 030C  STA $4015
+        0x4C, 0x0F, 0x03, // 030F  JMP $030F
+    ];
+    for (i, &byte) in program.iter().enumerate() {
+        nes.write_cpu_memory(0x0300 + i as u16, byte);
+    }
+    let mut registers = nes.cpu_registers();
+    registers.pc = 0x0300;
+    nes.set_cpu_registers(registers);
+    let id = nes.add_breakpoint(Breakpoint::Read(0xC000, 0xC000));
+
+    // The NES pauses on the tick the sample is read, not after the next instruction starts
+    for _ in 0..100 {
+        nes.tick();
+        let enabled = nes.cpu_registers().pc >= 0x030F;
+        let sample_read = enabled && nes.peek_cpu_memory(0x4015) & 0x10 == 0;
+        assert_eq!(nes.paused, sample_read);
+        if sample_read {
+            let hit = nes.take_break_hit().unwrap();
+            assert_eq!(hit.id, id);
+            assert_eq!(hit.access.map(|(addr, _)| addr), Some(0xC000));
+            assert_eq!(hit.pc, 0x030C); // The STA that started the sample
+            return;
+        }
+    }
+    panic!("no sample was read");
+}
+
+#[test]
+fn breakpoint_nmi() {
+    let mut nes = load_nestest();
+    let id = nes.add_breakpoint(Breakpoint::Interrupt(Interrupt::Nmi));
+    run_until_paused(&mut nes);
+
+    // Paused before the first instruction of the handler
+    let hit = nes.take_break_hit().unwrap();
+    assert_eq!(hit.id, id);
+    assert_eq!(hit.pc, 0xC5AF);
+    assert_eq!(nes.cpu_registers().pc, 0xC5AF);
+}
+
+/// Trace until the first instruction of the NMI handler, resuming from any breakpoint,
+/// returning that line and the one for the instruction before it
+fn trace_to_nmi(nes: &mut NES) -> Vec<String> {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let lines_clone = lines.clone();
+    nes.set_trace_logger(Some(TraceLogger::to_callback(
+        move |line| lines_clone.borrow_mut().push(line.to_string()),
+        TraceFormat::Nestest,
+    )));
+    for _ in 0..1_000_000 {
+        nes.tick();
+        if nes.paused {
+            nes.take_break_hit();
+            nes.paused = false;
+        }
+        let lines = lines.borrow();
+        if lines.last().is_some_and(|line| line.starts_with("C5AF")) {
+            return lines[lines.len() - 2..].to_vec();
+        }
+    }
+    panic!("no NMI");
+}
+
+#[test]
+fn breakpoint_keeps_nmi_timing() {
+    let mut nes = load_nestest();
+    let expected = trace_to_nmi(&mut nes);
+
+    // Halting on the instruction the NMI is about to interrupt doesn't move it
+    let mut nes = load_nestest();
+    nes.add_breakpoint(Breakpoint::Execute(
+        u16::from_str_radix(&expected[0][..4], 16).unwrap(),
+    ));
+    assert_eq!(trace_to_nmi(&mut nes), expected);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{load_nestest, load_nestest_automated};
+use nes::CallFrame;
+
+#[test]
+fn call_stack_step_out() {
+    let mut nes = load_nestest_automated();
+
+    // C5FD  20 2D C7  JSR $C72D
+    nes.run_to(0xC72D);
+    assert_eq!(
+        nes.call_stack(),
+        vec![CallFrame {
+            interrupt: None,
+            from: 0xC5FD,
+            to: 0xC72D,
+            sp: 0xFD,
+        }]
+    );
+
+    nes.step_out();
+    assert_eq!(nes.cpu_registers().pc, 0xC600);
+    assert!(nes.call_stack().is_empty());
+
+    // Stepping over the same call runs all of it
+    let mut registers = nes.cpu_registers();
+    registers.pc = 0xC5FD;
+    nes.set_cpu_registers(registers);
+    nes.step_over();
+    assert_eq!(nes.cpu_registers().pc, 0xC600);
+}
+
+#[test]
+fn call_stack_step_over_nmi() {
+    let mut nes = load_nestest();
+
+    // C28F  C5 D2     CMP $D2
+    // C291  F0 FC     BEQ $C28F
+    // The menu waits for NMI here, inside the subroutine at $C28D, and the next NMI fires just before the CMP
+    for _ in 0..27791 {
+        nes.step_instruction();
+    }
+    assert_eq!(nes.cpu_registers().pc, 0xC28F);
+    let outer_stack = nes.call_stack();
+
+    // Stepping into the NMI shows the handler on the call stack
+    let mut stepped_in = load_nestest();
+    for _ in 0..27792 {
+        stepped_in.step_instruction();
+    }
+    let frame = *stepped_in.call_stack().last().unwrap();
+    assert_eq!(frame.interrupt, Some(nes::debugger::Interrupt::Nmi));
+    assert_eq!((frame.from, frame.to), (0xC28F, 0xC5AF));
+
+    // Stepping over runs the whole handler, then the CMP it interrupted
+    nes.step_over();
+    assert_eq!(nes.call_stack(), outer_stack);
+    assert_eq!(nes.cpu_registers().pc, 0xC291);
+}
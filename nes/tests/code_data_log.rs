@@ -0,0 +1,81 @@
+mod common;
+
+use common::load_nestest_automated;
+use nes::code_data_log::{CodeDataLog, PRG_CODE, PRG_DATA, PRG_PCM};
+use nes::NES;
+
+use std::fs::{self, File};
+use std::process;
+
+#[test]
+fn code_data_log_nestest() {
+    let mut nes = load_nestest_automated();
+
+    nes.start_code_data_log().unwrap();
+    for _ in 0..1000 {
+        nes.tick();
+    }
+
+    let mut cdl_path = std::env::temp_dir();
+    cdl_path.push(format!("kind-nes-nestest-{}.cdl", process::id()));
+    nes.save_code_data_log(File::create(&cdl_path).unwrap())
+        .unwrap();
+    let log = nes.stop_code_data_log().unwrap();
+    let loaded = CodeDataLog::load(File::open(&cdl_path).unwrap(), 0x4000, 0x2000).unwrap();
+    assert_eq!(loaded.prg(), log.prg());
+    assert_eq!(loaded.chr(), log.chr());
+
+    // C000  4C F5 C5  JMP $C5F5, run from the $C000-$DFFF window
+    for offset in 0..3 {
+        assert_eq!(log.prg()[offset], PRG_CODE | (2 << 2));
+    }
+    // C5F5  A2 00     LDX #$00 is code, even though its operand is loaded
+    assert_eq!(log.prg()[0x05F6] & (PRG_CODE | PRG_DATA), PRG_CODE);
+    assert!(log.prg_bytes_logged() > 0);
+
+    // A log for this ROM can be continued, and one for a ROM of a different size is rejected
+    assert!(nes
+        .load_code_data_log(File::open(&cdl_path).unwrap())
+        .is_ok());
+    assert!(CodeDataLog::load(File::open(&cdl_path).unwrap(), 0x8000, 0x2000).is_err());
+    fs::remove_file(&cdl_path).unwrap();
+}
+
+#[test]
+fn code_data_log_dmc_samples() {
+    #[rustfmt::skip]
+    let program = [
+        0xA9, 0x00,       // LDA #0
+        0x8D, 0x12, 0x40, // STA $4012 ; Sample at $C000
+        0x8D, 0x13, 0x40, // STA $4013 ; 1 byte long
+        0xA9, 0x0F,       // LDA #$0F
+        0x8D, 0x10, 0x40, // STA $4010 ; The fastest rate
+        0xA9, 0x10,       // LDA #$10
+        0x8D, 0x15, 0x40, // STA $4015 ; Start the DMC
+        0x4C, 0x12, 0xE0, // JMP $E012
+    ];
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 2, 1];
+    rom.resize(0x10, 0);
+    let mut prg = vec![0xEA; 0x8000]; // NOP
+    prg[0x6000..0x6000 + program.len()].copy_from_slice(&program);
+    prg[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0xE0]);
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("kind-nes-dmc-{}.nes", process::id()));
+    fs::write(&path, rom).unwrap();
+    let mut nes = NES::new();
+    nes.load_rom(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    nes.start_code_data_log().unwrap();
+    for _ in 0..1000 {
+        nes.tick();
+    }
+    let log = nes.stop_code_data_log().unwrap();
+
+    // The sample byte is read as data over the CPU's bus, through the $C000-$DFFF window
+    assert_eq!(log.prg()[0x4000], PRG_PCM | PRG_DATA | (2 << 2));
+    assert_eq!(log.prg()[0x4001], 0);
+}
@@ -0,0 +1,46 @@
+// Each test file is its own crate and uses only some of these
+#![allow(dead_code)]
+
+use nes::{Registers, NES};
+
+use std::fs::File;
+use std::path::PathBuf;
+
+pub fn resource_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "resources", name]
+        .iter()
+        .collect()
+}
+
+/// Load nestest, which starts at its menu
+pub fn load_nestest() -> NES {
+    let mut nes = NES::new();
+    nes.load_rom(File::open(resource_path("nestest.nes")).unwrap())
+        .unwrap();
+    nes
+}
+
+/// Load nestest in its automated mode, which runs every test from $C000 without input
+pub fn load_nestest_automated() -> NES {
+    let mut nes = load_nestest();
+    let mut registers = nes.cpu_registers();
+    registers.pc = 0xC000;
+    nes.set_cpu_registers(registers);
+    nes
+}
+
+/// Run until the next frame is finished, and return it
+pub fn run_frame(nes: &mut NES) -> [[u16; 256]; 240] {
+    loop {
+        nes.tick();
+        if let Some(framebuffer) = nes.get_new_frame() {
+            return framebuffer;
+        }
+    }
+}
+
+/// What the game can see: the CPU registers, RAM and the PPU status
+pub fn game_state(nes: &NES) -> (Registers, Vec<u8>, u8) {
+    let memory = (0..0x0800).map(|addr| nes.peek_cpu_memory(addr)).collect();
+    (nes.cpu_registers(), memory, nes.peek_cpu_memory(0x2002))
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::load_nestest;
+use memory::ram::RAM;
+use memory::Memory;
+use nes::debugger::{Breakpoint, Expression, ExpressionContext};
+use nes::Registers;
+
+#[test]
+fn expression_evaluate() {
+    let mut ram = RAM::new(0x1000, 0);
+    ram.write(0x0300, 5);
+    ram.write(0x0010, 0x34);
+    ram.write(0x0011, 0x12);
+    let context = ExpressionContext {
+        registers: Registers {
+            a: 0xFF,
+            x: 2,
+            y: 0,
+            p: 0b1000_0001,
+            s: 0xFD,
+            pc: 0xC000,
+        },
+        scanline: 10,
+        dot: 100,
+        frame: 7,
+        access: Some((0x2007, 0x42)),
+        memory: &ram,
+    };
+    let evaluate = |source: &str| source.parse::<Expression>().unwrap().evaluate(&context);
+
+    assert_eq!(evaluate("A == $FF && [$0300] > 3 && scanline < 20"), 1);
+    assert_eq!(evaluate("{$10}"), 0x1234);
+    assert_eq!(evaluate("[$0300 - x + 2] * 2 + 1"), 11);
+    assert_eq!(evaluate("1 + 2 * 3 == 7 || 0"), 1);
+    assert_eq!(evaluate("(1 + 2) * 3"), 9);
+    assert_eq!(evaluate("n && c && !z"), 1);
+    assert_eq!(evaluate("value == $42 && address == 0x2007"), 1);
+    assert_eq!(evaluate("0b1010 >> 1 | 1 << 4"), 0x15);
+    assert_eq!(evaluate("23 %10"), 3);
+    assert_eq!(evaluate("-x + ~0 + 10 / 0 + pc - $C000"), -3);
+    assert_eq!(evaluate("frame % 4 != 3 || dot >= 341"), 0);
+
+    for invalid in &["", "A ==", "[$10", "B == 1", "1 2", "$", "a # 2"] {
+        assert!(invalid.parse::<Expression>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn breakpoint_condition() {
+    let mut nes = load_nestest();
+    let nmi_handler = nes.add_breakpoint(Breakpoint::Execute(0xC5AF));
+    assert!(nes.set_breakpoint_condition(nmi_handler, Some("frame == 3".parse().unwrap())));
+    assert!(!nes.set_breakpoint_condition(nmi_handler + 1, None));
+
+    for _ in 0..1_000_000 {
+        nes.tick();
+        if nes.paused {
+            break;
+        }
+    }
+    let hit = nes.take_break_hit().unwrap();
+    assert_eq!(hit.id, nmi_handler);
+    assert_eq!(nes.cpu_registers().pc, 0xC5AF);
+    assert_eq!(nes.breakpoints()[0].hits, 1);
+
+    // Breakpoints whose conditions are false don't pause, or disturb the timing of emulation
+    let mut reference = load_nestest();
+    let mut conditional = load_nestest();
+    let id = conditional.add_breakpoint(Breakpoint::Execute(0xC5AF));
+    conditional.set_breakpoint_condition(id, Some("0".parse().unwrap()));
+    let watch = conditional.add_breakpoint(Breakpoint::Read(0x2002, 0x2002));
+    conditional.set_breakpoint_condition(watch, Some("value == $FF".parse().unwrap()));
+    for _ in 0..300_000 {
+        reference.tick();
+        conditional.tick();
+    }
+    assert!(!conditional.paused);
+    assert_eq!(conditional.cpu_registers(), reference.cpu_registers());
+    assert!(conditional
+        .breakpoints()
+        .iter()
+        .all(|entry| entry.hits == 0));
+}
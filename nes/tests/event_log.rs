@@ -0,0 +1,44 @@
+mod common;
+
+use common::load_nestest;
+use nes::event_log::{EventKind, DOTS_PER_SCANLINE, SCANLINES_PER_FRAME};
+
+#[test]
+fn event_log_nestest() {
+    let mut nes = load_nestest();
+    nes.start_event_log();
+
+    let mut frames = 0;
+    while frames < 10 {
+        nes.tick();
+        if nes.get_new_frame().is_some() {
+            frames += 1;
+        }
+    }
+
+    let event_log = nes.event_log().unwrap();
+    let events = event_log.last_frame();
+    assert!(!events.is_empty());
+    for event in events {
+        assert!((event.dot as usize) < DOTS_PER_SCANLINE);
+        assert!((event.scanline as usize) < SCANLINES_PER_FRAME);
+    }
+
+    // The menu's NMI handler reads PPUSTATUS, and sets the scroll and PPUCTRL, during vblank
+    let status_read = events
+        .iter()
+        .find(|event| event.kind == EventKind::PpuStatusRead)
+        .unwrap();
+    assert_eq!(status_read.addr, 0x2002);
+    assert!(events
+        .iter()
+        .any(|event| event.kind == EventKind::PpuRegisterWrite && event.addr == 0x2000));
+    assert!(events
+        .iter()
+        .filter(|event| event.kind == EventKind::PpuRegisterWrite)
+        .all(|event| event.scanline >= 241));
+    drop(event_log);
+
+    nes.stop_event_log();
+    assert!(nes.event_log().is_none());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::load_nestest_automated;
+use nes::gdb::GdbServer;
+
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    /// Send a command and return the reply, checking the acknowledgements and checksum
+    fn command(&mut self, command: &str) -> String {
+        let checksum = command.bytes().fold(0u8, |a, b| a.wrapping_add(b));
+        write!(self.stream, "${}#{:02x}", command, checksum).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+
+        assert_eq!(self.read_byte(), b'$');
+        let mut reply = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+        let checksum_hex = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum_hex).unwrap(), 16);
+        assert_eq!(
+            checksum.unwrap(),
+            reply.iter().fold(0u8, |a, &b| a.wrapping_add(b))
+        );
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn gdb_nestest() {
+    // The NES isn't Send, so it's created on the server's thread
+    let (port_sender, port_receiver) = mpsc::channel();
+    let server_thread = thread::spawn(move || {
+        let mut nes = load_nestest_automated();
+
+        let mut server = GdbServer::bind("127.0.0.1:0").unwrap();
+        port_sender
+            .send(server.local_addr().unwrap().port())
+            .unwrap();
+        server.run(&mut nes).unwrap();
+        nes.paused
+    });
+
+    let port = port_receiver.recv().unwrap();
+    let mut client = Client {
+        stream: TcpStream::connect(("127.0.0.1", port)).unwrap(),
+    };
+
+    assert!(client
+        .command("qSupported:swbreak+")
+        .contains("qXfer:features:read+"));
+    assert!(client
+        .command("qXfer:features:read:target.xml:0,1000")
+        .starts_with("l<?xml"));
+    // The length comes from the client, so it can be anything
+    assert!(client
+        .command("qXfer:features:read:target.xml:10,ffffffffffffffff")
+        .starts_with("l"));
+    assert_eq!(
+        client.command("qXfer:features:read:target.xml:ffff,1"),
+        "E00"
+    );
+    assert_eq!(client.command("?"), "S05");
+
+    // A, X, Y, P, S, then PC in little-endian order
+    assert_eq!(client.command("g"), "00000024fd00c0");
+    assert_eq!(client.command("p5"), "00c0");
+    assert_eq!(client.command("mc000,3"), "4cf5c5"); // JMP $C5F5
+
+    // C5F9  86 10     STX $10 = 00
+    assert_eq!(client.command("Z2,0010,1"), "OK");
+    assert_eq!(client.command("c"), "T05watch:0010;");
+    assert_eq!(client.command("p5"), "fbc5");
+    assert_eq!(client.command("z2,0010,1"), "OK");
+
+    // C5FD  20 2D C7  JSR $C72D
+    assert_eq!(client.command("Z0,c72d,1"), "OK");
+    assert_eq!(client.command("c"), "S05");
+    assert_eq!(client.command("g"), "00000026fb2dc7");
+    assert_eq!(client.command("s"), "S05");
+    assert_eq!(client.command("p5"), "2ec7");
+    assert_eq!(client.command("z0,c72d,1"), "OK");
+
+    assert_eq!(client.command("M200,2:abcd"), "OK");
+    assert_eq!(client.command("m200,2"), "abcd");
+    assert_eq!(client.command("Mc72e,1:60"), "OK"); // RTS, in PRG ROM
+    assert_eq!(client.command("mc72e,1"), "60");
+    // Writing registers would have side effects, like turning on NMIs
+    assert_eq!(client.command("M2000,1:80"), "E01");
+
+    // Unsupported commands, including empty and non-ASCII ones, get an empty reply
+    assert_eq!(client.command(""), "");
+    assert_eq!(client.command("\u{e9}"), "");
+    assert_eq!(client.command("P0=42"), "OK");
+    assert_eq!(client.command("p0"), "42");
+
+    assert_eq!(client.command("D"), "OK");
+    let paused = server_thread.join().unwrap();
+    assert!(!paused);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::{game_state, load_nestest, run_frame};
+use nes::{Layers, NES};
+
+/// Sprite 0 over the menu text at (16, 32) and a sprite in palette 1 at (100, 101),
+/// with sprites on and shown in the leftmost column
+fn place_sprites(nes: &mut NES) {
+    nes.write_cpu_memory(0x2001, 0x1E);
+    nes.write_cpu_memory(0x2003, 0);
+    for i in 0..64 {
+        let sprite = match i {
+            0 => [31, 0x41, 0x00, 16],
+            1 => [100, 0x41, 0x01, 100],
+            _ => [0xF0, 0, 0, 0],
+        };
+        for &data in &sprite {
+            nes.write_cpu_memory(0x2004, data);
+        }
+    }
+}
+
+/// Run a frame on both, returning where their pictures differ
+fn frame_differences(shown: &mut NES, hidden: &mut NES) -> Vec<(usize, usize)> {
+    place_sprites(shown);
+    place_sprites(hidden);
+    let shown_frame = run_frame(shown).concat();
+    let hidden_frame = run_frame(hidden).concat();
+    (0..shown_frame.len())
+        .filter(|&i| shown_frame[i] != hidden_frame[i])
+        .map(|i| (i % 256, i / 256))
+        .collect()
+}
+
+#[test]
+fn hidden_layers() {
+    let mut shown = load_nestest();
+    let mut hidden = load_nestest();
+    for nes in &mut [&mut shown, &mut hidden] {
+        for _ in 0..3 {
+            run_frame(nes);
+        }
+    }
+
+    let in_sprite = |(x, y): (usize, usize), left, top| {
+        (left..left + 8).contains(&x) && (top..top + 8).contains(&y)
+    };
+
+    // Hiding the background takes away the menu text, but sprite 0 still hits it
+    hidden.set_layers(Layers {
+        background: false,
+        ..Layers::default()
+    });
+    let differences = frame_differences(&mut shown, &mut hidden);
+    assert!(!differences.is_empty());
+    assert!(differences.iter().all(|&(_, y)| y >= 32));
+    assert_eq!(game_state(&shown), game_state(&hidden));
+    assert_eq!(game_state(&hidden).2 & 0x40, 0x40);
+
+    hidden.set_layers(Layers {
+        sprites: false,
+        ..Layers::default()
+    });
+    let differences = frame_differences(&mut shown, &mut hidden);
+    assert!(differences.iter().any(|&pixel| in_sprite(pixel, 16, 32)));
+    assert!(differences.iter().any(|&pixel| in_sprite(pixel, 100, 101)));
+    assert!(differences
+        .iter()
+        .all(|&pixel| in_sprite(pixel, 16, 32) || in_sprite(pixel, 100, 101)));
+    assert_eq!(game_state(&shown), game_state(&hidden));
+    assert_eq!(game_state(&hidden).2 & 0x40, 0x40);
+
+    // Hiding sprite palette 1 only takes away the sprite that uses it
+    let mut layers = Layers::default();
+    layers.palettes[5] = false;
+    hidden.set_layers(layers);
+    let differences = frame_differences(&mut shown, &mut hidden);
+    assert!(!differences.is_empty());
+    assert!(differences.iter().all(|&pixel| in_sprite(pixel, 100, 101)));
+    assert_eq!(game_state(&shown), game_state(&hidden));
+
+    hidden.set_layers(Layers::default());
+    assert!(frame_differences(&mut shown, &mut hidden).is_empty());
+}
@@ -0,0 +1,129 @@
+use nes::NES;
+
+use std::fs::{self, File};
+use std::process;
+
+const IRQ_HANDLER: u16 = 0xE080;
+
+/// Build and load an iNES ROM with 32 KB of PRG ROM, where the program starts at $E000,
+/// the IRQ handler is at IRQ_HANDLER, and NMIs return straight away
+fn load_program(name: &str, mapper: u8, program: &[u8], irq_handler: &[u8], chr: &[u8]) -> NES {
+    let n_chr_banks = (chr.len() / 0x2000) as u8;
+    let mut rom = vec![
+        b'N',
+        b'E',
+        b'S',
+        0x1A,
+        2,
+        n_chr_banks,
+        mapper << 4,
+        mapper & 0xF0,
+    ];
+    rom.resize(0x10, 0);
+
+    let mut prg = vec![0xEA; 0x8000]; // NOP
+    let at = |addr: u16| addr as usize - 0x8000;
+    prg[at(0xE000)..at(0xE000) + program.len()].copy_from_slice(program);
+    prg[at(IRQ_HANDLER)..at(IRQ_HANDLER) + irq_handler.len()].copy_from_slice(irq_handler);
+    prg[at(0xFFF0)] = 0x40; // RTI
+    let [irq_lo, irq_hi] = IRQ_HANDLER.to_le_bytes();
+    prg[at(0xFFFA)..].copy_from_slice(&[0xF0, 0xFF, 0x00, 0xE0, irq_lo, irq_hi]);
+    rom.extend(prg);
+    rom.extend(chr);
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("kind-nes-{}-{}.nes", name, process::id()));
+    fs::write(&path, rom).unwrap();
+    let mut nes = NES::new();
+    nes.load_rom(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    nes
+}
+
+#[test]
+fn mmc3_counts_filtered_a12_rises() {
+    #[rustfmt::skip]
+    let program = [
+        0x78,             // SEI
+        0xA9, 0x40,       // LDA #$40
+        0x8D, 0x17, 0x40, // STA $4017 ; No APU frame IRQs
+        0x2C, 0x02, 0x20, // BIT $2002 ; Wait for two vblanks
+        0x10, 0xFB,       // BPL -5
+        0x2C, 0x02, 0x20, // BIT $2002
+        0x10, 0xFB,       // BPL -5
+        0xA9, 0x08,       // LDA #$08 ; Sprites from $1000, background from $0000
+        0x8D, 0x00, 0x20, // STA $2000
+        0xA9, 0x0A,       // LDA #10
+        0x8D, 0x00, 0xC0, // STA $C000 ; IRQ latch
+        0x8D, 0x01, 0xC0, // STA $C001 ; IRQ reload
+        0x8D, 0x01, 0xE0, // STA $E001 ; IRQ enable
+        0xA9, 0x18,       // LDA #$18
+        0x8D, 0x01, 0x20, // STA $2001 ; Show the background and sprites
+        0x58,             // CLI
+        0x4C, 0x26, 0xE0, // JMP $E026
+    ];
+    #[rustfmt::skip]
+    let irq_handler = [
+        0x8D, 0x00, 0xE0, // STA $E000 ; IRQ disable
+        0x40,             // RTI
+    ];
+    let mut nes = load_program("mmc3", 4, &program, &irq_handler, &[0; 0x2000]);
+
+    // A12 rises once per line when sprite patterns are fetched, after the low background
+    // fetches, and between sprites it's low too briefly to count. The reload happens on the
+    // pre-render line, then each line counts down from 10, reaching 0 on line 9.
+    let mut irq_position = None;
+    for _ in 0..200_000 {
+        nes.tick();
+        if nes.cpu_registers().pc == IRQ_HANDLER {
+            irq_position = Some((nes.scanline(), nes.dot()));
+            break;
+        }
+    }
+    let (scanline, dot) = irq_position.expect("no IRQ");
+    assert_eq!(scanline, 9);
+    assert!((260..=340).contains(&dot));
+}
+
+/// Read PPUDATA at an address in the left pattern table when `read`, or just put the address
+/// on the bus, then return what's read from $0000, where the $FD bank is 0 and $FE bank is 1
+fn mmc2_latch(mapper: u8, addr_lo: u8, read: bool) -> u8 {
+    let read_op = if read { [0xAD, 0x07, 0x20] } else { [0xEA; 3] };
+    #[rustfmt::skip]
+    let program = [
+        0xA9, 0x01,          // LDA #1
+        0x8D, 0x00, 0xC0,    // STA $C000 ; $FE bank for $0000-$0FFF
+        0xA9, 0x0F,          // LDA #$0F
+        0x8D, 0x06, 0x20,    // STA $2006
+        0xA9, addr_lo,       // LDA #addr_lo
+        0x8D, 0x06, 0x20,    // STA $2006
+        read_op[0], read_op[1], read_op[2], // LDA $2007, or NOPs
+        0xA9, 0x00,          // LDA #0
+        0x8D, 0x06, 0x20,    // STA $2006
+        0x8D, 0x06, 0x20,    // STA $2006
+        0xAD, 0x07, 0x20,    // LDA $2007 ; Fill the read buffer
+        0xAD, 0x07, 0x20,    // LDA $2007
+        0x85, 0x00,          // STA $00
+        0x4C, 0x22, 0xE0,    // JMP $E022
+    ];
+    let mut chr = vec![0xAA; 0x1000];
+    chr.extend(vec![0xBB; 0x1000]);
+    let name = format!("mmc{}-{:02x}-{}", mapper, addr_lo, read);
+    let mut nes = load_program(&name, mapper, &program, &[0x40], &chr);
+    for _ in 0..1000 {
+        nes.tick();
+    }
+    nes.peek_cpu_memory(0x00)
+}
+
+#[test]
+fn mmc2_and_mmc4_latches() {
+    // Reading $0FE8 switches to the $FE bank for later reads on both
+    assert_eq!(mmc2_latch(9, 0xE8, true), 0xBB);
+    assert_eq!(mmc2_latch(10, 0xE8, true), 0xBB);
+    // MMC4 latches on any row of the tile, and MMC2 only on the first
+    assert_eq!(mmc2_latch(9, 0xE9, true), 0xAA);
+    assert_eq!(mmc2_latch(10, 0xE9, true), 0xBB);
+    // The address alone on the bus isn't a read
+    assert_eq!(mmc2_latch(9, 0xE8, false), 0xAA);
+}
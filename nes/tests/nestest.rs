@@ -2,6 +2,7 @@ use cpu::CPU;
 use memory::mmu::MMU;
 use memory::ram::RAM;
 use memory::Memory;
+use nes::trace::TraceComparison;
 
 use std::cell::RefCell;
 use std::fs::File;
@@ -47,17 +48,21 @@ fn nestest_cpu() {
     );
     cpu_mmu.write_u16(0xFFFC, 0xC000);
     let mut cpu = CPU::new(Box::from(cpu_mmu));
-    cpu.log = true;
+    cpu.set_tracing(true);
     cpu.reset();
 
     let log_file = File::open(log_path).unwrap();
     let reader = BufReader::new(log_file);
+    let mut comparison = TraceComparison::new(5);
+    comparison.exact = true;
     for line in reader.lines() {
-        let log = iter::repeat_with(|| cpu.tick())
+        let entry = iter::repeat_with(|| cpu.tick())
             .skip_while(|x| x.is_none())
             .next()
             .unwrap()
             .unwrap();
-        assert_eq!(log, line.unwrap());
+        if let Err(divergence) = comparison.check(&line.unwrap(), &entry.to_string()) {
+            panic!("{}", divergence);
+        }
     }
 }
@@ -0,0 +1,35 @@
+mod common;
+
+use common::load_nestest_automated;
+use nes::profiler::{Profiler, RoutineKind};
+
+#[test]
+fn profiler_nestest() {
+    let mut nes = load_nestest_automated();
+
+    nes.set_profiler(Some(Profiler::new()));
+    for _ in 0..20000 {
+        nes.tick();
+    }
+    let profile = nes.profiler().unwrap().session();
+
+    // Everything happens under the top level, which nestest never leaves
+    let (top_level, top_level_stats) = profile.routines[0];
+    assert_eq!(top_level.kind, RoutineKind::TopLevel);
+    assert_eq!(top_level_stats.inclusive_cycles, profile.cycles);
+    let exclusive_total: u64 = profile
+        .routines
+        .iter()
+        .map(|(_, stats)| stats.exclusive_cycles)
+        .sum();
+    assert_eq!(exclusive_total, profile.cycles);
+
+    // C5FD  20 2D C7  JSR $C72D, the first group of tests
+    let (_, stats) = profile
+        .routines
+        .iter()
+        .find(|(routine, _)| routine.kind == RoutineKind::Subroutine && routine.addr == 0xC72D)
+        .unwrap();
+    assert!(stats.calls > 0);
+    assert!(stats.inclusive_cycles >= stats.exclusive_cycles);
+}
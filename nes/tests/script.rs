@@ -0,0 +1,126 @@
+mod common;
+
+use common::load_nestest;
+use nes::script::{Overlay, Script, OVERLAY_HEIGHT, OVERLAY_WIDTH};
+use nes::NES;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn save_state_restores_everything() {
+    let nes = Rc::new(RefCell::new(load_nestest()));
+    let mut nes = nes.borrow_mut();
+    for _ in 0..100_000 {
+        nes.tick();
+    }
+    let state = nes.save_state();
+
+    let run = |nes: &mut NES| {
+        for _ in 0..100_000 {
+            nes.tick();
+        }
+        let memory: Vec<u8> = (0..0x0800).map(|addr| nes.peek_cpu_memory(addr)).collect();
+        let vram: Vec<u8> = (0x2000..0x3F20)
+            .map(|addr| nes.peek_ppu_memory(addr))
+            .collect();
+        (nes.cpu_registers(), nes.scanline(), nes.dot(), memory, vram)
+    };
+    let first = run(&mut nes);
+    nes.load_state(&state).unwrap();
+    assert_eq!(run(&mut nes), first);
+
+    // The controller's latched buttons and strobe come back too
+    nes.write_cpu_memory(0x4016, 1);
+    nes.try_fill_controller_shift(0b1010_0101);
+    let state = nes.save_state();
+    nes.write_cpu_memory(0x4016, 0);
+    nes.peek_cpu_memory(0x4016);
+    nes.load_state(&state).unwrap();
+    assert!(nes.get_shift_strobe());
+    assert_eq!(nes.peek_cpu_memory(0x4016) & 1, 1);
+    nes.try_fill_controller_shift(0);
+    assert_eq!(nes.peek_cpu_memory(0x4016) & 1, 0);
+
+    // States only load into the ROM they're from
+    assert!(NES::new().load_state(&state).is_err());
+}
+
+#[test]
+fn overlay_clips_to_screen() {
+    let mut overlay = Overlay::new();
+    let far = 1_000_000_000_000;
+
+    // These would take hours to walk, or overflow, if they weren't cut down to the screen first
+    overlay.fill_rect(-far, -far, i64::MAX, i64::MAX, 0x0000FF);
+    assert_eq!(overlay.pixel(0, 0), Some(0x0000FF));
+    assert_eq!(
+        overlay.pixel(OVERLAY_WIDTH - 1, OVERLAY_HEIGHT - 1),
+        Some(0x0000FF)
+    );
+    overlay.clear();
+    overlay.rect(i64::MAX - 1, 0, i64::MAX, 10, 0xFF0000);
+    overlay.text(i64::MAX, i64::MAX, "Off screen\nand beyond", 0xFF0000);
+    assert!(overlay.is_empty());
+
+    // A line from far off the screen to far off the other side crosses it where it would
+    overlay.line(-far, -far, far, far, 0x00FF00);
+    assert_eq!(overlay.pixel(0, 0), Some(0x00FF00));
+    assert_eq!(overlay.pixel(100, 100), Some(0x00FF00));
+    assert_eq!(overlay.pixel(239, 239), Some(0x00FF00));
+    assert_eq!(overlay.pixel(100, 101), None);
+
+    // Lines on the screen keep their exact ends
+    overlay.clear();
+    overlay.line(10, 20, 13, 20, 0xFFFFFF);
+    let drawn: Vec<usize> = (0..OVERLAY_WIDTH)
+        .filter(|&x| overlay.pixel(x, 20).is_some())
+        .collect();
+    assert_eq!(drawn, [10, 11, 12, 13]);
+}
+
+#[test]
+fn script_callbacks() {
+    let nes = Rc::new(RefCell::new(load_nestest()));
+    let source = r#"
+        let frames = 0;
+        on_frame(|| {
+            frames += 1;
+            write(0x0300, frames);
+            text(0, 0, "Hi", 0xFF0000);
+            set_input(#{ start: true, a: true });
+        });
+        on_scanline(100, || write(0x0301, scanline()));
+    "#;
+    let mut script = Script::new(nes.clone(), source).unwrap();
+
+    for _ in 0..2 {
+        // Run to the end of a frame, when the frame callback draws
+        script.tick().unwrap();
+        while script.overlay().is_empty() {
+            script.tick().unwrap();
+        }
+    }
+    assert_eq!(nes.borrow().peek_cpu_memory(0x0300), 2);
+    assert_eq!(nes.borrow().peek_cpu_memory(0x0301), 100);
+    assert_eq!(script.input(), Some(0b0000_1001));
+
+    // The top left of the "H"
+    assert_eq!(script.overlay().pixel(0, 0), Some(0xFF0000));
+    assert_eq!(script.overlay().pixel(1, 0), None);
+    // What was drawn is cleared as the next frame starts
+    script.tick().unwrap();
+    assert!(script.overlay().is_empty());
+
+    // Loading a state at the end of each frame keeps replaying the same frame
+    let source = "let start = save_state(); on_frame(|| load_state(start));";
+    let mut script = Script::new(nes.clone(), source).unwrap();
+    let frame = nes.borrow().frame();
+    for _ in 0..100_000 {
+        script.tick().unwrap(); // About three frames
+        assert!(nes.borrow().frame() <= frame + 1);
+    }
+
+    let error = Script::new(nes, "set_register(\"q\", 1);").err().unwrap();
+    assert!(error.contains("unknown register"), "{}", error);
+}
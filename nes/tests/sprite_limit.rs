@@ -0,0 +1,46 @@
+mod common;
+
+use common::{game_state, load_nestest, run_frame};
+
+#[test]
+fn sprite_limit_off() {
+    let mut limited = load_nestest();
+    let mut unlimited = load_nestest();
+    unlimited.set_sprite_limit(false);
+
+    // Ten sprites on the same scanlines, with sprites on and shown in the leftmost column
+    for nes in &mut [&mut limited, &mut unlimited] {
+        for _ in 0..3 {
+            run_frame(nes);
+        }
+        nes.write_cpu_memory(0x2001, 0x1E);
+        nes.write_cpu_memory(0x2003, 0);
+        for i in 0..64 {
+            let y = if i < 10 { 100 } else { 0xF0 };
+            for &data in &[y, 0x41, 0x00, (9 * i) as u8] {
+                nes.write_cpu_memory(0x2004, data);
+            }
+        }
+    }
+
+    let limited_frame = run_frame(&mut limited).concat();
+    let unlimited_frame = run_frame(&mut unlimited).concat();
+    let differences: Vec<(usize, usize)> = (0..limited_frame.len())
+        .filter(|&i| limited_frame[i] != unlimited_frame[i])
+        .map(|i| (i % 256, i / 256))
+        .collect();
+    // Only the two sprites past the limit, at X 72 and 81, are drawn differently
+    assert!(!differences.is_empty());
+    assert!(differences
+        .iter()
+        .all(|&(x, y)| (72..90).contains(&x) && (101..109).contains(&y)));
+
+    // The game sees the same thing either way, including the sprite overflow flag
+    assert_eq!(game_state(&limited), game_state(&unlimited));
+    assert_eq!(game_state(&limited).2 & 0x20, 0x20);
+    for _ in 0..3 {
+        run_frame(&mut limited);
+        run_frame(&mut unlimited);
+        assert_eq!(game_state(&limited), game_state(&unlimited));
+    }
+}
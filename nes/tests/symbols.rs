@@ -0,0 +1,123 @@
+mod common;
+
+use common::load_nestest_automated;
+use nes::symbols::{SourceLine, SymbolTable};
+use nes::trace::{TraceFormat, TraceLogger};
+
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+#[test]
+fn symbols_fceux_labels() {
+    let mut symbols = SymbolTable::new();
+    let prg = "$C5F5#Main#Entry point\n$C72D#TestFlags#\n  continued comment\n";
+    symbols.load_fceux_nl(Cursor::new(prg), Some(0)).unwrap();
+    let ram = "$0010/2#Pointer#\n";
+    symbols.load_fceux_nl(Cursor::new(ram), None).unwrap();
+
+    let mut nes = load_nestest_automated();
+    nes.set_symbols(Some(symbols));
+    assert_eq!(nes.label_at(0xC72D), Some("TestFlags"));
+    assert_eq!(nes.label_at(0x0010), Some("Pointer"));
+    assert_eq!(nes.address_of_label("Main"), Some(0xC5F5));
+    assert_eq!(nes.address_of_label("Nothing"), None);
+
+    // C5FD  20 2D C7  JSR $C72D
+    assert_eq!(nes.disassemble(0xC5FD).unwrap().operand, "TestFlags");
+    // C5F9  86 10     STX $10
+    assert_eq!(nes.disassemble(0xC5F9).unwrap().operand, "Pointer");
+    // C5F5  A2 00     LDX #$00 has an immediate operand, not an address
+    assert_eq!(nes.disassemble(0xC5F5).unwrap().operand, "#$00");
+}
+
+#[test]
+fn symbols_in_traces() {
+    let mut symbols = SymbolTable::new();
+    symbols.add_label(Some(0), 0xC5F5, "Main");
+
+    // Traces keep their addresses unless the logger asks for labels
+    for labels in [false, true] {
+        let mut nes = load_nestest_automated();
+        nes.set_symbols(Some(symbols.clone()));
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let lines_clone = lines.clone();
+        let mut logger = TraceLogger::to_callback(
+            move |line| lines_clone.borrow_mut().push(line.to_string()),
+            TraceFormat::Nestest,
+        );
+        logger.labels = labels;
+        nes.set_trace_logger(Some(logger));
+        nes.step_instruction();
+
+        // C000  4C F5 C5  JMP $C5F5
+        let operand = if labels { "JMP Main" } else { "JMP $C5F5" };
+        assert!(lines.borrow()[0].contains(operand), "{}", lines.borrow()[0]);
+    }
+}
+
+#[test]
+fn symbols_ca65_lines() {
+    let dbg = [
+        "version\tmajor=2,minor=0",
+        "file\tid=0,name=\"main.s\",size=100,mtime=0x5F000000,mod=0",
+        "seg\tid=0,name=\"HEADER\",start=0x000000,size=0x0010,addrsize=absolute,type=ro,oname=\"nestest.nes\",ooffs=0",
+        "seg\tid=1,name=\"CODE\",start=0x00C000,size=0x4000,addrsize=absolute,type=ro,oname=\"nestest.nes\",ooffs=16",
+        "span\tid=0,seg=1,start=0x5F5,size=8",
+        "span\tid=1,seg=1,start=0x5FD,size=3",
+        "span\tid=2,seg=1,start=0x600,size=3",
+        "line\tid=0,file=0,line=10,span=0",
+        "line\tid=1,file=0,line=11,span=1",
+        "line\tid=2,file=0,line=12,span=2",
+        "sym\tid=0,name=\"TestFlags\",addrsize=absolute,scope=0,def=0,val=0xC72D,seg=1,type=lab",
+        "sym\tid=1,name=\"COUNT\",addrsize=zeropage,scope=0,def=0,val=0x10,type=equ",
+        // A cheap local and another label at the same address don't replace the first name
+        "sym\tid=2,name=\"@loop\",addrsize=absolute,scope=0,def=0,parent=3,val=0xC5F5,seg=1,type=lab",
+        "sym\tid=3,name=\"Main\",addrsize=absolute,scope=0,def=0,val=0xC5F5,seg=1,type=lab",
+        "sym\tid=4,name=\"Alias\",addrsize=absolute,scope=0,def=0,val=0xC5F5,seg=1,type=lab",
+    ]
+    .join("\n");
+    let mut symbols = SymbolTable::new();
+    symbols.load_ca65_dbg(Cursor::new(dbg)).unwrap();
+    assert_eq!(symbols.label(Some(0), 0xC72D), Some("TestFlags"));
+    assert_eq!(symbols.address_of("COUNT"), None); // Constants aren't labels
+    assert_eq!(symbols.label(Some(0), 0xC5F5), Some("Main"));
+    assert_eq!(symbols.address_of("Alias"), Some((Some(0), 0xC5F5)));
+    assert_eq!(symbols.address_of("@loop"), Some((Some(0), 0xC5F5)));
+
+    // Offsets that overflow are skipped rather than trusted
+    let bad_dbg = [
+        "seg\tid=0,name=\"CODE\",start=0xFFFFFFFF,size=0x10,addrsize=absolute,type=ro,oname=\"a.nes\",ooffs=0xFFFFFFFF",
+        "span\tid=0,seg=0,start=16,size=3",
+        "file\tid=0,name=\"main.s\",size=100,mtime=0x5F000000,mod=0",
+        "line\tid=0,file=0,line=10,span=0",
+        "sym\tid=0,name=\"Far\",addrsize=absolute,scope=0,def=0,val=0x0F,seg=0,type=lab",
+    ]
+    .join("\n");
+    let mut bad_symbols = SymbolTable::new();
+    bad_symbols.load_ca65_dbg(Cursor::new(bad_dbg)).unwrap();
+    assert_eq!(bad_symbols.address_of("Far"), None);
+    assert!(!bad_symbols.has_source_lines());
+
+    let mut nes = load_nestest_automated();
+    nes.set_symbols(Some(symbols));
+
+    // C000  4C F5 C5  JMP $C5F5
+    nes.step_line();
+    assert_eq!(nes.cpu_registers().pc, 0xC5F5);
+    assert_eq!(
+        nes.source_line_at(0xC5F5),
+        Some(&SourceLine {
+            file: "main.s".to_string(),
+            line: 10,
+        })
+    );
+
+    nes.step_line_over();
+    assert_eq!(nes.cpu_registers().pc, 0xC5FD);
+
+    // Stepping a line into a subroutine without line info runs until it returns
+    nes.step_line();
+    assert_eq!(nes.cpu_registers().pc, 0xC600);
+    assert_eq!(nes.source_line_at(0xC600).unwrap().line, 12);
+}
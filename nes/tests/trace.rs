@@ -0,0 +1,157 @@
+mod common;
+
+use common::load_nestest_automated;
+use nes::trace::{TraceArm, TraceFormat, TraceLogger, TraceRecord};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Run nestest in its automated mode with a logger, returning the lines it logged
+fn trace(format: TraceFormat, arm: TraceArm, frame_limit: Option<u64>, frames: u64) -> Vec<String> {
+    let mut nes = load_nestest_automated();
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let lines_clone = lines.clone();
+    let mut logger = TraceLogger::to_callback(
+        move |line| lines_clone.borrow_mut().push(line.to_string()),
+        format,
+    );
+    logger.arm = arm;
+    logger.frame_limit = frame_limit;
+    nes.set_trace_logger(Some(logger));
+    while nes.frame() < frames {
+        nes.tick();
+    }
+    nes.set_trace_logger(None);
+
+    let lines = lines.borrow().clone();
+    lines
+}
+
+#[test]
+fn trace_formats() {
+    let lines = trace(TraceFormat::Nestest, TraceArm::Always, None, 1);
+    assert_eq!(
+        &lines[..2],
+        [
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  0 CYC:7",
+            "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  9 CYC:10",
+        ]
+    );
+
+    let lines = trace(TraceFormat::Mesen, TraceArm::Always, None, 1);
+    assert_eq!(
+        &lines[..2],
+        [
+            "C000 $4C $F5 $C5 JMP $C5F5                      A:00 X:00 Y:00 P:24 SP:FD CYC:0   SL:0   FC:0 CPU Cycle:7 PRG:$00",
+            "C5F5 $A2 $00     LDX #$00                       A:00 X:00 Y:00 P:24 SP:FD CYC:9   SL:0   FC:0 CPU Cycle:10 PRG:$00",
+        ]
+    );
+
+    let lines = trace(TraceFormat::Fceux, TraceArm::Always, None, 1);
+    assert_eq!(
+        &lines[..2],
+        [
+            "f0      c7          A:00 X:00 Y:00 S:FD P:nvUbdIzc  $00:C000:4C F5 C5  JMP $C5F5",
+            "f0      c10         A:00 X:00 Y:00 S:FD P:nvUbdIzc  $00:C5F5:A2 00     LDX #$00",
+        ]
+    );
+}
+
+#[test]
+fn trace_armed_at_address() {
+    // C72D is the first group of tests, which nestest calls once it has set up
+    let lines = trace(
+        TraceFormat::Nestest,
+        TraceArm::Address(0xC72D, 0xC72F),
+        None,
+        1,
+    );
+    assert!(lines[0].starts_with("C72D  EA        NOP"));
+    // Once armed, the trace keeps going wherever the PC goes
+    assert!(lines.iter().any(|line| !line.starts_with("C72")));
+}
+
+#[test]
+fn trace_armed_at_frame() {
+    let lines = trace(TraceFormat::Fceux, TraceArm::Frame(2), None, 4);
+    assert!(lines[0].starts_with("f2 "));
+    assert!(lines.iter().any(|line| line.starts_with("f3 ")));
+}
+
+#[test]
+fn trace_frame_limit() {
+    let lines = trace(TraceFormat::Fceux, TraceArm::Frame(1), Some(2), 5);
+    assert!(lines[0].starts_with("f1 "));
+    assert!(lines.last().unwrap().starts_with("f2 "));
+}
+
+#[test]
+fn trace_arm_from_str() {
+    assert_eq!("pc:C000".parse(), Ok(TraceArm::Address(0xC000, 0xC000)));
+    assert_eq!(
+        "pc:$C000-$C0FF".parse(),
+        Ok(TraceArm::Address(0xC000, 0xC0FF))
+    );
+    assert_eq!("frame:60".parse(), Ok(TraceArm::Frame(60)));
+    assert!("pc:C0FF-C000".parse::<TraceArm>().is_err());
+    assert!("C000".parse::<TraceArm>().is_err());
+}
+
+fn record(pc: u16, p: u8, cycles: Option<u64>) -> TraceRecord {
+    TraceRecord {
+        pc,
+        a: 0x01,
+        x: 0x02,
+        y: 0x03,
+        p,
+        s: 0xFD,
+        cycles,
+    }
+}
+
+#[test]
+fn parse_nestest_record() {
+    assert_eq!(
+        TraceRecord::parse(
+            "C000  4C F5 C5  JMP $C5F5                       A:01 X:02 Y:03 P:24 SP:FD PPU:  0, 21 CYC:7"
+        ),
+        Some(record(0xC000, 0x24, Some(7)))
+    );
+    // Older nestest logs use CYC for the PPU dot, beside the scanline
+    assert_eq!(
+        TraceRecord::parse(
+            "C000  4C F5 C5  JMP $C5F5                       A:01 X:02 Y:03 P:24 SP:FD CYC:  0 SL:241"
+        ),
+        Some(record(0xC000, 0x24, None))
+    );
+}
+
+#[test]
+fn parse_mesen_record() {
+    // The CPU cycle is labeled "CPU Cycle", while CYC is the PPU dot
+    assert_eq!(
+        TraceRecord::parse(
+            "C000 $4C $F5 $C5 JMP $C5F5                      A:01 X:02 Y:03 P:24 SP:FD CYC:21  SL:0   FC:0 CPU Cycle:7 PRG:$00"
+        ),
+        Some(record(0xC000, 0x24, Some(7)))
+    );
+}
+
+#[test]
+fn parse_fceux_record() {
+    // Flags are letters, and the PC may have a bank in front of it
+    assert_eq!(
+        TraceRecord::parse(
+            "f1      c7          A:01 X:02 Y:03 S:FD P:nvUbdIzc  $07:C000:4C F5 C5  JMP $C5F5"
+        ),
+        Some(record(0xC000, 0x24, Some(7)))
+    );
+    assert_eq!(
+        TraceRecord::parse(
+            "$C000:4C F5 C5  JMP $C5F5                A:01 X:02 Y:03 S:FD P:NVubdizC"
+        ),
+        Some(record(0xC000, 0xC1, None))
+    );
+    assert_eq!(TraceRecord::parse("FCEUX 2.6.4 - Trace Log File"), None);
+}
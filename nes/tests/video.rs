@@ -0,0 +1,384 @@
+mod common;
+
+use common::{load_nestest, run_frame};
+use nes::video::{
+    render_frame, AspectRatio, CrtFilter, CrtSettings, Layout, NtscFilter, NtscSetup, Overscan,
+    Palette, PaletteSettings, PixelFormat, Rect, Scaler, NTSC_HEIGHT, NTSC_WIDTH,
+};
+use nes::{pixel_to_rgb, Layers, NES};
+
+#[test]
+fn emphasis_and_greyscale() {
+    let mut nes = load_nestest();
+    for _ in 0..3 {
+        run_frame(&mut nes);
+    }
+    let plain = run_frame(&mut nes);
+    assert!(plain.iter().flatten().all(|&pixel| pixel < 0x40));
+    assert!(plain.iter().flatten().any(|&pixel| pixel & 0x0F != 0));
+
+    // Greyscale with red and blue emphasis
+    nes.write_cpu_memory(0x2001, 0b1010_1111);
+    let framebuffer = run_frame(&mut nes);
+    for &pixel in framebuffer.iter().flatten() {
+        assert_eq!(pixel >> 6, 0b101);
+        assert_eq!(pixel & 0x0F, 0);
+    }
+
+    let colors: Vec<u32> = (0..64).map(|_| 0x808080).collect();
+    assert_eq!(pixel_to_rgb(0x20, &colors), 0x808080);
+    // Emphasizing red darkens green and blue, and emphasizing all three darkens everything
+    assert_eq!(pixel_to_rgb(0x20 | (0b001 << 6), &colors), 0x805F5F);
+    assert_eq!(pixel_to_rgb(0x20 | (0b101 << 6), &colors), 0x5F5F5F);
+    assert_eq!(pixel_to_rgb(0x20 | (0b111 << 6), &colors), 0x5F5F5F);
+    // But not black, in columns $xE and $xF
+    assert_eq!(pixel_to_rgb(0x0F | (0b111 << 6), &colors), 0x808080);
+}
+
+#[test]
+fn ntsc_filter() {
+    let close = |a: u32, b: u32| {
+        (0..3).all(|i| (((a >> (8 * i)) & 0xFF) as i32 - ((b >> (8 * i)) & 0xFF) as i32).abs() <= 4)
+    };
+    let mut out = vec![0; NTSC_WIDTH * NTSC_HEIGHT];
+    let mut other = out.clone();
+
+    // A solid color decodes to the color of the usual palette
+    let mut red = [[0x16; 256]; 240];
+    let mut filter = NtscFilter::new(NtscSetup::composite());
+    filter.render(&red, 0, &mut out);
+    assert!(
+        close(out[100 * NTSC_WIDTH + 300], 0xB53120),
+        "{:06X}",
+        out[100 * NTSC_WIDTH + 300]
+    );
+    // And emphasis changes it
+    red[100][140] |= 0b100 << 6;
+    filter.render(&red, 0, &mut other);
+    assert_ne!(out[100 * NTSC_WIDTH + 328], other[100 * NTSC_WIDTH + 328]);
+
+    // Thin stripes of white on black make colored artifacts that crawl from frame to frame
+    let mut stripes = [[0x0F; 256]; 240];
+    for line in stripes.iter_mut() {
+        for x in (0..256).step_by(2) {
+            line[x] = 0x30;
+        }
+    }
+    let is_grey = |c: u32| c >> 16 == (c >> 8) & 0xFF && (c >> 8) & 0xFF == c & 0xFF;
+    filter.render(&stripes, 0, &mut out);
+    filter.render(&stripes, 1, &mut other);
+    assert!(out.iter().any(|&c| !is_grey(c)));
+    assert_ne!(out, other);
+
+    // S-Video keeps luma apart from chroma, and RGB is as clean as the palette
+    for setup in &[NtscSetup::svideo(), NtscSetup::rgb()] {
+        let mut filter = NtscFilter::new(*setup);
+        filter.render(&stripes, 0, &mut out);
+        filter.render(&stripes, 1, &mut other);
+        assert!(out.iter().all(|&c| is_grey(c)));
+        assert_eq!(out, other);
+    }
+}
+
+#[test]
+fn burst_phase() {
+    let phases = |nes: &mut NES| -> Vec<usize> {
+        (0..10)
+            .map(|_| {
+                run_frame(nes);
+                nes.burst_phase()
+            })
+            .collect()
+    };
+
+    // Each frame moves the subcarrier on by a third of a cycle, but once nestest turns on
+    // rendering, every other frame is a dot short and moves it by two thirds
+    let mut nes = load_nestest();
+    assert_eq!(phases(&mut nes), [0, 1, 2, 0, 2, 0, 2, 0, 2, 0]);
+
+    // Its automated mode leaves rendering off
+    let mut nes = load_nestest();
+    let mut registers = nes.cpu_registers();
+    registers.pc = 0xC000;
+    nes.set_cpu_registers(registers);
+    assert_eq!(phases(&mut nes), [0, 1, 2, 0, 1, 2, 0, 1, 2, 0]);
+}
+
+#[test]
+fn palettes() {
+    let close = |a: u32, b: u32| {
+        (0..3).all(|i| (((a >> (8 * i)) & 0xFF) as i32 - ((b >> (8 * i)) & 0xFF) as i32).abs() <= 4)
+    };
+    let channel = |color: u32, i: u32| (color >> (16 - 8 * i)) & 0xFF;
+
+    let palette = Palette::new();
+    assert_eq!(palette.colors().len(), 64);
+    assert_eq!(palette.rgb(0x16), 0xB53120);
+    assert_eq!(
+        palette.rgb(0x20 | (0b001 << 6)),
+        pixel_to_rgb(0x20 | (0b001 << 6), palette.colors())
+    );
+    assert_eq!(NES::new().palette(), &palette);
+
+    // 64 colors get emphasis the same way, and 512 are used as they are
+    let bytes: Vec<u8> = (0..192).map(|i| i as u8).collect();
+    let loaded = Palette::load(&bytes[..]).unwrap();
+    assert_eq!(loaded.rgb(1), 0x030405);
+    assert_eq!(
+        loaded.rgb(0x30 | (0b010 << 6)),
+        pixel_to_rgb(0x30 | (0b010 << 6), loaded.colors())
+    );
+    let mut bytes = loaded.to_pal();
+    assert_eq!(bytes.len(), 1536);
+    bytes[0x41 * 3] = 0xAB;
+    let loaded = Palette::load(&bytes[..]).unwrap();
+    assert_eq!(loaded.rgb(0x41) >> 16, 0xAB);
+    assert_eq!(loaded.rgb(0x41 | 0x200), loaded.rgb(0x41));
+    assert!(Palette::load(&bytes[..100]).is_err());
+
+    // Decoding the signal gives about the usual colors, and emphasis tints them
+    let generated = Palette::generate(PaletteSettings::default());
+    assert!(
+        close(generated.rgb(0x16), 0xB53120),
+        "{:06X}",
+        generated.rgb(0x16)
+    );
+    let white = generated.rgb(0x30);
+    let red_white = generated.rgb(0x30 | (0b001 << 6));
+    assert!(channel(red_white, 0) > channel(red_white, 1));
+    assert!(channel(red_white, 1) < channel(white, 1));
+
+    let settings: PaletteSettings = "saturation=0,gamma=2".parse().unwrap();
+    assert_eq!(settings.hue, 0.0);
+    let grey = Palette::generate(settings);
+    for &color in grey.colors() {
+        assert!(
+            (0..3).all(|i| channel(color, i) == channel(color, 0)),
+            "{:06X}",
+            color
+        );
+    }
+    let dark_grey = generated.rgb(0x00);
+    assert!(channel(grey.rgb(0x00), 1) > channel(dark_grey, 1));
+    let bright = Palette::generate("brightness=0.2".parse().unwrap());
+    assert!(channel(bright.rgb(0x00), 1) > channel(dark_grey, 1));
+
+    assert!("tint=1".parse::<PaletteSettings>().is_err());
+    assert!("gamma=0".parse::<PaletteSettings>().is_err());
+    assert!("hue".parse::<PaletteSettings>().is_err());
+}
+
+#[test]
+fn pixel_formats() {
+    let mut framebuffer = [[0x0F; 256]; 240];
+    framebuffer[0][1] = 0x16;
+    framebuffer[239][255] = 0x16 | (0b110 << 6);
+    let palette = Palette::new();
+    let emphasized = palette.rgb(framebuffer[239][255]);
+
+    let mut out = vec![0; 256 * 240 * 4];
+    render_frame(
+        &framebuffer,
+        &palette,
+        PixelFormat::Rgba8,
+        &mut out,
+        256 * 4,
+    );
+    assert_eq!(out[4..8], [0xB5, 0x31, 0x20, 0xFF]);
+    assert_eq!(out[..4], [0x00, 0x00, 0x00, 0xFF]);
+    let last = out.len() - 4;
+    assert_eq!(
+        out[last..],
+        [
+            (emphasized >> 16) as u8,
+            (emphasized >> 8) as u8,
+            emphasized as u8,
+            0xFF
+        ]
+    );
+
+    render_frame(
+        &framebuffer,
+        &palette,
+        PixelFormat::Argb8888,
+        &mut out,
+        256 * 4,
+    );
+    assert_eq!(out[4..8], 0xFFB5_3120u32.to_ne_bytes());
+
+    // Lines can be padded, which is left alone
+    let pitch = 256 * 2 + 10;
+    let mut out = vec![0xAA; pitch * 239 + 256 * 2];
+    render_frame(&framebuffer, &palette, PixelFormat::Rgb565, &mut out, pitch);
+    assert_eq!(
+        out[2..4],
+        ((0b10110 << 11) | (0b001100 << 5) | 0b00100u16).to_ne_bytes()
+    );
+    assert_eq!(out[256 * 2..pitch], [0xAA; 10]);
+    assert_eq!(out[pitch..pitch + 2], [0, 0]);
+
+    // The NES draws its last frame in its palette
+    let mut nes = load_nestest();
+    nes.set_palette(Palette::generate(PaletteSettings::default()));
+    for _ in 0..10 {
+        run_frame(&mut nes); // Past the blank frames before the menu
+    }
+    let framebuffer = run_frame(&mut nes);
+    let mut out = vec![0; 256 * 240 * 3];
+    nes.render_frame(PixelFormat::Rgb8, &mut out, 256 * 3);
+    let pixel = framebuffer[120][128];
+    let color = nes.palette().rgb(pixel);
+    let i = (120 * 256 + 128) * 3;
+    assert_eq!(
+        out[i..i + 3],
+        [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+    );
+
+    // Partway through the next frame, the finished one is still what's drawn
+    nes.set_layers(Layers {
+        background: false,
+        sprites: false,
+        ..Layers::default()
+    });
+    while nes.scanline() != 200 {
+        nes.tick();
+    }
+    let mut midframe = vec![0; 256 * 240 * 3];
+    nes.render_frame(PixelFormat::Rgb8, &mut midframe, 256 * 3);
+    assert_eq!(midframe, out);
+}
+
+#[test]
+fn scalers() {
+    let names = [
+        "scale2x", "scale3x", "blend2x", "blend3x", "xbrz2", "xbrz3", "xbrz4", "xbrz5", "xbrz6",
+    ];
+    // A white triangle over black, whose diagonal edge the scalers should smooth
+    let (width, height) = (16, 12);
+    let triangle: Vec<u32> = (0..width * height)
+        .map(|i| {
+            if i % width > i / width {
+                0xFFFFFF
+            } else {
+                0x000000
+            }
+        })
+        .collect();
+    for name in names.iter() {
+        let scaler: Scaler = name.parse().unwrap();
+        assert_eq!(&scaler.to_string(), name);
+        let factor = scaler.factor();
+        let mut out = vec![0x123456; width * height * factor * factor];
+        scaler.scale(&triangle, width, height, &mut out);
+
+        // Away from the edge, each pixel just gets bigger
+        let out_width = width * factor;
+        assert_eq!(out[(height * factor - 1) * out_width], 0x000000, "{}", name);
+        assert_eq!(out[out_width - 1], 0xFFFFFF, "{}", name);
+        // But the staircase along the edge is smoothed
+        let nearest: Vec<u32> = (0..out.len())
+            .map(|i| triangle[(i / out_width / factor) * width + (i % out_width) / factor])
+            .collect();
+        assert_ne!(out, nearest, "{}", name);
+    }
+    assert!("xbrz7".parse::<Scaler>().is_err());
+
+    // Scale2x rounds off the corner of a pixel with matching neighbors above and beside it
+    let mut image = vec![0xFFFFFF; 9];
+    image[1] = 0;
+    image[3] = 0;
+    let mut out = vec![0; 36];
+    Scaler::Scale2x.scale(&image, 3, 3, &mut out);
+    assert_eq!(out[2 * 6 + 2], 0);
+    assert_eq!(out[2 * 6 + 3], 0xFFFFFF);
+    assert_eq!(out[3 * 6 + 2], 0xFFFFFF);
+
+    // Blending only makes colors in between the two
+    let mut out = vec![0; width * height * 16];
+    Scaler::Xbrz(4).scale(&triangle, width, height, &mut out);
+    assert!(out.iter().any(|&c| c != 0 && c != 0xFFFFFF));
+    assert!(out
+        .iter()
+        .all(|&c| c >> 16 == (c >> 8) & 0xFF && c >> 16 == c & 0xFF));
+}
+
+#[test]
+fn crt_filter() {
+    let (width, height) = (16, 10);
+    let (out_width, out_height) = (48, 30);
+    let frame: Vec<u32> = (0..width * height)
+        .map(|i| if i % width < 8 { 0xFFFFFF } else { 0x000000 })
+        .collect();
+    let mut out = vec![0; out_width * out_height];
+    let channel = |color: u32, i: u32| (color >> (16 - 8 * i)) & 0xFF;
+
+    // With everything off, it's just the frame scaled up
+    let settings: CrtSettings = "scanlines=0,mask=0,blur=0,bloom=0".parse().unwrap();
+    let mut crt = CrtFilter::new(settings);
+    crt.render(&frame, width, height, &mut out, out_width, out_height);
+    for (i, &color) in out.iter().enumerate() {
+        let (x, y) = (i % out_width, i / out_width);
+        assert_eq!(color, frame[(y / 3) * width + x / 3]);
+    }
+
+    crt.set_settings(CrtSettings::default());
+    crt.render(&frame, width, height, &mut out, out_width, out_height);
+    // Scanlines are darker at their edges than in the middle
+    let pixel = |x: usize, y: usize| out[y * out_width + x];
+    assert!(channel(pixel(3, 12), 0) < channel(pixel(3, 13), 0));
+    // Each column of the mask favors one of red, green and blue
+    assert!(channel(pixel(3, 12), 0) > channel(pixel(3, 12), 1));
+    assert!(channel(pixel(4, 12), 1) > channel(pixel(4, 12), 0));
+    assert!(channel(pixel(5, 12), 2) > channel(pixel(5, 12), 1));
+    // Blur and bloom spill light from the white half into the black half
+    assert_ne!(pixel(24, 13), 0);
+    assert_eq!(pixel(47, 13), 0);
+
+    // A minimized window has nothing to draw into
+    crt.render(&frame, width, height, &mut [], 0, 0);
+    crt.render(&[], 0, 0, &mut out, out_width, out_height);
+    crt.render(&[], 0, height, &mut out, out_width, out_height);
+    crt.render(&frame, width, height, &mut [], out_width, 0);
+
+    assert!("mask=2".parse::<CrtSettings>().is_err());
+    assert!("glow=0.5".parse::<CrtSettings>().is_err());
+}
+
+#[test]
+fn layouts() {
+    let rect = |x, y, width, height| Rect {
+        x,
+        y,
+        width,
+        height,
+    };
+
+    // By default, the whole frame is shown with square pixels, as large as fits
+    let mut layout = Layout::default();
+    assert_eq!(layout.size(3), (768, 720));
+    assert_eq!(layout.viewport(768, 720), rect(0, 0, 768, 720));
+    assert_eq!(layout.viewport(1000, 720), rect(116, 0, 768, 720));
+    assert_eq!(layout.viewport(512, 1000), rect(0, 260, 512, 480));
+
+    // Overscan is cropped from the frame at any size
+    layout.overscan = "ntsc".parse().unwrap();
+    assert_eq!(layout.crop(256, 240), rect(0, 8, 256, 224));
+    assert_eq!(layout.crop(768, 720), rect(0, 24, 768, 672));
+    layout.overscan = "top=4,left=8,right=16".parse().unwrap();
+    assert_eq!(layout.crop(512, 480), rect(16, 8, 464, 472));
+    assert_eq!("pal".parse::<Overscan>(), Ok(Overscan::PAL));
+    assert!("top=2.5".parse::<Overscan>().is_err());
+    assert!("left=200,right=56".parse::<Overscan>().is_err());
+    assert!("middle=1".parse::<Overscan>().is_err());
+
+    layout.overscan = Overscan::NTSC;
+    layout.aspect_ratio = AspectRatio::Pixel8x7;
+    assert_eq!(layout.size(1), (293, 224));
+    layout.aspect_ratio = "4:3".parse().unwrap();
+    assert_eq!(layout.size(3), (896, 672));
+    assert_eq!(layout.viewport(1000, 1000), rect(0, 125, 1000, 750));
+
+    // Integer scaling leaves bars rather than scale lines unevenly
+    layout.integer_scaling = true;
+    assert_eq!(layout.viewport(1000, 1000), rect(52, 164, 896, 672));
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use common::load_nestest;
+use nes::video::Palette;
+use nes::NES;
+
+use std::fs::{self, File};
+use std::process;
+
+// Distinct colors, so that every color index can be told apart in a rendered image
+fn colors() -> Vec<u32> {
+    (0..64).map(|i| 0x010101 * (i + 1)).collect()
+}
+
+#[test]
+fn nametable_viewer() {
+    let mut nes = load_nestest();
+    while nes.frame() < 3 {
+        nes.tick();
+    }
+    let image = nes.render_nametables(&colors());
+    assert_eq!((image.width, image.height), (512, 480));
+
+    // nestest doesn't scroll, so the viewport is the first nametable
+    for &(x, y) in &[(0, 0), (255, 0), (0, 239), (255, 239), (128, 239)] {
+        assert_eq!(image.pixel(x, y), 0xFFFFFF);
+    }
+    assert_ne!(image.pixel(256, 0), 0xFFFFFF);
+
+    // nestest has vertical mirroring, so its nametables repeat left to right
+    let quadrant = |left: usize, top: usize| -> Vec<u32> {
+        (1..239)
+            .flat_map(|y| (1..255).map(move |x| (x, y)))
+            .map(|(x, y)| image.pixel(left + x, top + y))
+            .collect()
+    };
+    assert_eq!(quadrant(0, 0), quadrant(256, 0));
+    assert_ne!(quadrant(0, 0), quadrant(0, 240));
+}
+
+#[test]
+fn pattern_table_viewer() {
+    let mut nes = load_nestest();
+    while nes.frame() < 3 {
+        nes.tick();
+    }
+    let colors = colors();
+    let tables = nes.render_pattern_tables(0, &colors);
+    assert_eq!((tables.width, tables.height), (256, 128));
+
+    // nestest's 8 KB of CHR ROM is never banked, so it's the same as the pattern tables
+    assert_eq!(nes.chr_bank_count(), 2);
+    for bank in 0..2 {
+        let image = nes.render_chr_bank(bank, 0, &colors).unwrap();
+        for y in 0..128 {
+            for x in 0..128 {
+                assert_eq!(image.pixel(x, y), tables.pixel(bank * 128 + x, y));
+            }
+        }
+    }
+    assert!(nes.render_chr_bank(2, 0, &colors).is_none());
+
+    // Tiles are drawn in whichever palette is chosen
+    for i in 1..4 {
+        nes.write_ppu_memory(0x3F04 + i, 0x10 + i as u8);
+    }
+    let other = nes.render_pattern_tables(1, &colors);
+    assert_ne!(tables, other);
+}
+
+#[test]
+fn chr_ram_viewer() {
+    // An NROM cartridge with 16 KB of NOPs and CHR RAM rather than ROM
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 0];
+    rom.resize(0x10, 0);
+    rom.resize(0x10 + 0x4000, 0xEA);
+    let mut path = std::env::temp_dir();
+    path.push(format!("kind-nes-chr-ram-{}.nes", process::id()));
+    fs::write(&path, rom).unwrap();
+    let mut nes = NES::new();
+    nes.load_rom(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // What's written to CHR RAM shows up in its banks
+    for addr in 0x0000..0x0010 {
+        nes.write_ppu_memory(addr, 0xFF);
+    }
+    nes.write_ppu_memory(0x3F03, 0x20);
+    let colors = colors();
+    let tables = nes.render_pattern_tables(0, &colors);
+    assert_eq!(nes.chr_bank_count(), 2);
+    let image = nes.render_chr_bank(0, 0, &colors).unwrap();
+    assert_ne!(image.pixel(0, 0), image.pixel(8, 0));
+    for y in 0..128 {
+        for x in 0..128 {
+            assert_eq!(image.pixel(x, y), tables.pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn sprite_viewer() {
+    let mut nes = load_nestest();
+    while nes.frame() < 3 {
+        nes.tick();
+    }
+    // Emphasize red, and keep the frame it's drawn in
+    nes.write_cpu_memory(0x2001, 0x3E);
+    let frame = loop {
+        nes.tick();
+        if let Some(frame) = nes.get_new_frame() {
+            break frame;
+        }
+    };
+    assert!(frame[200][200] >> 6 == 0b001, "{:03X}", frame[200][200]);
+
+    // Nine sprites in a row on the same scanlines, with the last flipped and behind the background
+    nes.write_cpu_memory(0x2003, 0);
+    for i in 0..64 {
+        let (y, attr) = match i {
+            0..=7 => (50, 0x01),
+            8 => (50, 0xE2),
+            _ => (0xF0, 0x00),
+        };
+        for &data in &[y, 0x41, attr, (10 * i) as u8] {
+            nes.write_cpu_memory(0x2004, data);
+        }
+    }
+    // And one hanging off the right edge of the picture
+    nes.write_cpu_memory(0x2003, 4 * 9);
+    for &data in &[100, 0x41, 0x00, 252] {
+        nes.write_cpu_memory(0x2004, data);
+    }
+
+    let sprites = nes.sprites();
+    assert_eq!(sprites.len(), 64);
+    assert!(sprites[..8].iter().all(|sprite| !sprite.dropped));
+    let ninth = sprites[8];
+    assert!(ninth.dropped);
+    assert_eq!(
+        (ninth.x, ninth.y, ninth.tile, ninth.palette),
+        (80, 50, 0x41, 2)
+    );
+    assert!(ninth.flip_horizontal && ninth.flip_vertical && ninth.behind_background);
+    assert_eq!(ninth.height, 8);
+    assert_eq!(
+        ninth.to_string(),
+        "#8 at (80, 50): tile $41, palette 2, flipped horizontally, flipped vertically, behind background, dropped"
+    );
+
+    // A palette with every emphasized color given, rather than worked out from the 64 colors
+    let pal: Vec<u8> = (0..512u32)
+        .flat_map(|pixel| (0x010000 + 0x0203 * pixel).to_be_bytes()[1..].to_vec())
+        .collect();
+    let palette = Palette::load(&pal[..]).unwrap();
+    let image = nes.render_sprite_view(&palette);
+    assert_eq!((image.width, image.height), (392, 240));
+    assert_eq!(image.pixel(200, 200), palette.rgb(frame[200][200]));
+    // Sprites on screen are boxed a line below their Y, with dropped ones in red
+    assert_eq!(image.pixel(10, 51), 0x50FF50);
+    assert_eq!(image.pixel(80, 51), 0xFF5050);
+    assert_eq!(nes.sprite_in_view(84, 55), Some(ninth));
+    assert_eq!(nes.sprite_in_view(84, 80), None);
+    // Boxes are cut off at the edge of the picture, rather than going into the gap beside it
+    assert_eq!(image.pixel(255, 101), 0x50FF50);
+    assert_eq!(image.pixel(259, 101), 0x000000);
+    assert_eq!(nes.sprite_in_view(258, 104), None);
+    // And in the grid beside the picture, 8 to a row
+    assert_eq!(nes.sprite_in_view(264 + 4, 24 + 4), Some(ninth));
+}
@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct BackgroundData {
     pub latch: BackgroundLatches,
     pub shift: BackgroundShifts,
@@ -21,6 +22,7 @@ impl BackgroundData {
     }
 }
 
+#[derive(Clone)]
 pub struct BackgroundLatches {
     pub nt_byte: u8,
     pub attr_byte: u8,
@@ -28,6 +30,7 @@ pub struct BackgroundLatches {
     pub patt_hi: u8,
 }
 
+#[derive(Clone)]
 pub struct BackgroundShifts {
     pub attr_shift: [u8; 2],
     pub attr_latch: [bool; 2],
@@ -0,0 +1,33 @@
+// Each framebuffer pixel is a color index from palette RAM in its low 6 bits,
+// and the PPUMASK emphasis bits (red, green, then blue) in bits 6-8
+// https://wiki.nesdev.com/w/index.php/PPU_palettes#Color_emphasis
+pub const COLOR_INDEX_MASK: u16 = 0x3F;
+pub const EMPHASIS_SHIFT: u16 = 6;
+
+// Emphasizing one color darkens the other two by about this much
+// https://wiki.nesdev.com/w/index.php/NTSC_video#Color_Tint_Bits
+const ATTENUATION: f32 = 0.746;
+
+/// Convert a framebuffer pixel to 0xRRGGBB, given the RGB of the 64 colors,
+/// darkening the channels of any colors that aren't emphasized
+pub fn pixel_to_rgb(pixel: u16, colors: &[u32]) -> u32 {
+    let index = pixel & COLOR_INDEX_MASK;
+    let color = colors[index as usize];
+    let emphasis = (pixel >> EMPHASIS_SHIFT) & 0b111;
+    // Columns $xE and $xF are black, which emphasis can't darken
+    if emphasis == 0 || index & 0x0E == 0x0E {
+        return color;
+    }
+
+    let mut rgb = 0;
+    for channel in 0..3 {
+        // Red is emphasized by bit 0, and is the highest byte of the color
+        let shift = 16 - 8 * channel;
+        let mut value = (color >> shift) & 0xFF;
+        if emphasis & !(1 << channel) != 0 {
+            value = (value as f32 * ATTENUATION) as u32;
+        }
+        rgb |= value << shift;
+    }
+    rgb
+}
@@ -2,20 +2,81 @@
 extern crate bitflags;
 
 mod background_data;
+mod color;
 mod registers;
 mod scan;
 mod sprite_data;
+mod viewer;
+
+pub use color::{pixel_to_rgb, COLOR_INDEX_MASK, EMPHASIS_SHIFT};
+pub use viewer::{
+    render_pattern_table, RgbImage, SpriteInfo, NAMETABLE_VIEW_HEIGHT, NAMETABLE_VIEW_WIDTH,
+    PATTERN_TABLE_VIEW_SIZE, SPRITE_VIEW_HEIGHT, SPRITE_VIEW_WIDTH,
+};
 
 use background_data::BackgroundData;
-use memory::Memory;
+use memory::{BusObserver, Memory};
 use registers::*;
 use scan::Scan;
-use sprite_data::SpriteData;
 use sprite_data::SpriteEvalState;
+use sprite_data::{SpriteData, SpriteRegisters};
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+/// Everything about the PPU that changes as it runs, for save states.
+/// VRAM is outside of the PPU, so it isn't included.
+#[derive(Clone)]
+pub struct PPUState {
+    registers: PPURegisters,
+    scan: Scan,
+    bg_data: BackgroundData,
+    spr_data: SpriteData,
+    oam: [u8; 0x100],
+    oam2: [u8; 0x20],
+    dma_request: Option<u8>,
+    framebuffer: [[u16; 256]; 240],
+    nmi: bool,
+    frame_ready: bool,
+    bus_addr: u16,
+    data_read: Option<u16>,
+}
+
+/// Watches the address on the PPU's bus on every dot, as cartridge mappers do to follow rendering
+pub trait PPUBusObserver {
+    /// The address on the bus during a dot, and whether the PPU read from it
+    fn on_dot(&mut self, addr: u16, read: bool);
+}
+
+/// Which parts of the picture are drawn, for debugging and screenshots. Hidden parts are only
+/// left out of the output, so sprite 0 hits and sprite overflow happen exactly as usual.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layers {
+    pub background: bool,
+    pub sprites: bool,
+    pub palettes: [bool; 8], // Background palettes 0-3, then sprite palettes 0-3
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            background: true,
+            sprites: true,
+            palettes: [true; 8],
+        }
+    }
+}
+
+impl Layers {
+    fn shows_background(&self, palette: u8) -> bool {
+        self.background && self.palettes[palette as usize]
+    }
+
+    fn shows_sprite(&self, palette: u8) -> bool {
+        self.sprites && self.palettes[4 + palette as usize]
+    }
+}
+
 pub struct PPU {
     registers: PPURegisters,
     scan: Scan,
@@ -26,9 +87,14 @@ pub struct PPU {
     oam2: [u8; 0x20],
     dma_option: Option<Rc<RefCell<dyn Memory>>>,
     dma_request: Option<u8>,
-    pub framebuffer: [[u8; 256]; 240],
+    bus_observer: Option<Rc<RefCell<dyn PPUBusObserver>>>,
+    bus_addr: u16,                      // The address on the bus during the last dot
+    data_read: Option<u16>,             // The address of a read through PPUDATA, until the next dot
+    pub framebuffer: [[u16; 256]; 240], // Color indices with emphasis, as in the color module
     pub nmi: bool,
     pub frame_ready: bool,
+    sprite_limit: bool, // Whether to draw only the 8 sprites per scanline that hardware can
+    layers: Layers,
 }
 
 impl PPU {
@@ -43,32 +109,113 @@ impl PPU {
             oam2: [0u8; 0x20],
             dma_option: None,
             dma_request: None,
+            bus_observer: None,
+            bus_addr: 0,
+            data_read: None,
             framebuffer: [[0; 256]; 240],
             nmi: false,
             frame_ready: false,
+            sprite_limit: true,
+            layers: Layers::default(),
         }
     }
 
+    /// Draw every sprite on a scanline rather than only the first 8, which removes flicker.
+    /// Sprite evaluation is unchanged, so the game sees sprite overflow and sprite 0 hits
+    /// exactly as it would with the limit.
+    pub fn set_sprite_limit(&mut self, sprite_limit: bool) {
+        self.sprite_limit = sprite_limit;
+    }
+
+    /// Hide the background, sprites or palettes from the picture, without changing emulation
+    pub fn set_layers(&mut self, layers: Layers) {
+        self.layers = layers;
+    }
+
+    pub fn layers(&self) -> Layers {
+        self.layers
+    }
+
     pub fn set_dma(&mut self, dma: Rc<RefCell<dyn Memory>>) {
         self.dma_option = Some(dma);
     }
 
+    /// Show the address on the bus to an observer on every dot, including every rendering fetch
+    pub fn set_bus_observer(&mut self, observer: Rc<RefCell<dyn PPUBusObserver>>) {
+        self.bus_observer = Some(observer);
+    }
+
+    /// Watch the CPU's accesses to VRAM through PPUDATA, but not rendering fetches
+    pub fn add_vram_observer(&mut self, observer: Rc<RefCell<dyn BusObserver>>) {
+        self.memory.add_observer(observer);
+    }
+
+    pub fn remove_vram_observer(&mut self, observer: &Rc<RefCell<dyn BusObserver>>) {
+        self.memory.remove_observer(observer);
+    }
+
+    /// Read PPU memory directly, without side effects
+    pub fn peek_vram(&self, addr: u16) -> u8 {
+        self.memory.peek(addr & 0x3FFF)
+    }
+
+    /// Write PPU memory directly, without going through PPUADDR and PPUDATA
+    pub fn write_vram(&mut self, addr: u16, data: u8) {
+        self.memory.write(addr & 0x3FFF, data);
+    }
+
     pub fn reset(&mut self) {
         self.registers.reset();
         self.scan = Scan::new();
         self.bg_data = BackgroundData::new();
         self.spr_data = SpriteData::new();
         self.dma_request = None;
+        self.bus_addr = 0;
+        self.data_read = None;
         self.framebuffer = [[0; 256]; 240];
         self.nmi = false;
         self.frame_ready = false;
     }
 
+    pub fn save_state(&self) -> PPUState {
+        PPUState {
+            registers: self.registers.clone(),
+            scan: self.scan.clone(),
+            bg_data: self.bg_data.clone(),
+            spr_data: self.spr_data.clone(),
+            oam: self.oam,
+            oam2: self.oam2,
+            dma_request: self.dma_request,
+            framebuffer: self.framebuffer,
+            nmi: self.nmi,
+            frame_ready: self.frame_ready,
+            bus_addr: self.bus_addr,
+            data_read: self.data_read,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &PPUState) {
+        self.registers = state.registers.clone();
+        self.scan = state.scan.clone();
+        self.bg_data = state.bg_data.clone();
+        self.spr_data = state.spr_data.clone();
+        self.oam = state.oam;
+        self.oam2 = state.oam2;
+        self.dma_request = state.dma_request;
+        self.framebuffer = state.framebuffer;
+        self.nmi = state.nmi;
+        self.frame_ready = state.frame_ready;
+        self.bus_addr = state.bus_addr;
+        self.data_read = state.data_read;
+    }
+
     pub fn tick(&mut self) {
         if let Some(data) = self.dma_request {
             self.dma_request = None;
             self.run_oam_dma(data);
         }
+        let (bus_addr, bus_read) = self.bus_access();
+        self.bus_addr = bus_addr;
 
         // https://wiki.nesdev.com/w/images/d/d1/Ntsc_timing.png
         // Background operations happend on visible lines and pre-render line
@@ -88,6 +235,13 @@ impl PPU {
 
             if self.scan.cycle == 257 {
                 self.spr_data.spr_nums = self.spr_data.spr_nums_next;
+                self.spr_data.extra.clear();
+                if !self.sprite_limit
+                    && self.scan.on_visible_line()
+                    && self.registers.ppumask.is_rendering()
+                {
+                    self.fetch_extra_sprites();
+                }
             }
 
             if self.scan.on_spr_fetch_cycle() && self.registers.ppumask.is_rendering() {
@@ -115,39 +269,45 @@ impl PPU {
                 || (321 <= self.scan.cycle && self.scan.cycle <= 336)
             {
                 // BG registers are also shifted on 321-336, allowing the first two tiles in
-                let (mut pixel_on, mut color) = self.get_bg_pixel();
+                let (mut pixel_on, mut color, bg_palette) = self.get_bg_pixel();
                 if 1 <= self.scan.cycle && self.scan.cycle <= 256 {
-                    let (spr_pixel_on, spr_color, priority, spr_zero) = self.get_spr_pixel();
-                    if spr_pixel_on {
-                        if pixel_on
-                            && spr_zero
-                            && self.registers.ppumask.contains(MaskRegister::BACK_ENABLE)
-                            && self.registers.ppumask.contains(MaskRegister::SPRITE_ENABLE)
-                            && (self.scan.cycle != 256)
-                            && !((1 <= self.scan.cycle && self.scan.cycle <= 8)
-                                && (!self.registers.ppumask.contains(MaskRegister::BACK_LEFT_COL)
-                                    || !self
-                                        .registers
-                                        .ppumask
-                                        .contains(MaskRegister::SPRITE_LEFT_COL)))
-                        {
-                            self.registers
-                                .ppustatus
-                                .insert(StatusRegister::SPRITE_ZERO_HIT);
-                        }
-
-                        if !pixel_on || priority {
-                            pixel_on = true;
-                            color = spr_color;
-                        }
+                    let (spr_pixel_on, spr_color, priority, spr_zero, spr_palette) =
+                        self.get_spr_pixel();
+                    if spr_pixel_on
+                        && pixel_on
+                        && spr_zero
+                        && self.registers.ppumask.contains(MaskRegister::BACK_ENABLE)
+                        && self.registers.ppumask.contains(MaskRegister::SPRITE_ENABLE)
+                        && (self.scan.cycle != 256)
+                        && !((1 <= self.scan.cycle && self.scan.cycle <= 8)
+                            && (!self.registers.ppumask.contains(MaskRegister::BACK_LEFT_COL)
+                                || !self
+                                    .registers
+                                    .ppumask
+                                    .contains(MaskRegister::SPRITE_LEFT_COL)))
+                    {
+                        self.registers
+                            .ppustatus
+                            .insert(StatusRegister::SPRITE_ZERO_HIT);
+                    }
+
+                    // Hidden layers are left out only now that sprite 0 hits are found
+                    pixel_on &= self.layers.shows_background(bg_palette);
+                    if spr_pixel_on
+                        && self.layers.shows_sprite(spr_palette)
+                        && (!pixel_on || priority)
+                    {
+                        pixel_on = true;
+                        color = spr_color;
                     }
                     let x = (self.scan.cycle - 1) as usize;
                     let y = self.scan.line as usize;
-                    self.framebuffer[y][x] = if pixel_on {
+                    let color = if pixel_on {
                         color
                     } else {
-                        self.memory.read(0x3F00)
-                    }
+                        self.memory.fetch(0x3F00)
+                    };
+                    self.framebuffer[y][x] = self.output_pixel(color);
                 }
             }
         }
@@ -177,15 +337,84 @@ impl PPU {
             }
         }
 
+        if let Some(observer) = &self.bus_observer {
+            observer.borrow_mut().on_dot(bus_addr, bus_read);
+        }
+
         self.scan.increment(self.registers.ppumask.is_rendering());
     }
 
+    pub fn scanline(&self) -> u16 {
+        self.scan.line
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.scan.cycle
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.scan.total_frames
+    }
+
+    /// Where the color subcarrier started this frame, from 0 to 2 in thirds of a cycle
+    pub fn burst_phase(&self) -> u8 {
+        self.scan.burst_phase
+    }
+
     pub fn cpu_cycle(&mut self) {
         for _ in 0..3 {
             self.tick();
         }
     }
 
+    /// The address the PPU puts on its bus this dot, and whether it reads from it
+    fn bus_access(&mut self) -> (u16, bool) {
+        if let Some(addr) = self.data_read.take() {
+            return (addr, true);
+        }
+        let fetching = self.registers.ppumask.is_rendering()
+            && (self.scan.on_visible_line() || self.scan.on_prerender_line());
+        if !fetching {
+            // The VRAM address is left on the bus, so writing PPUADDR can clock mappers
+            return (self.registers.curr_addr.raw & 0x3FFF, false);
+        }
+
+        // https://wiki.nesdev.com/w/index.php/PPU_rendering
+        // Each fetch puts its address on the bus for two dots and reads on the second
+        let cycle = self.scan.cycle;
+        let read = cycle & 1 == 0;
+        let addr = match (cycle, (cycle.wrapping_sub(1)) % 8) {
+            (0, _) => return (self.bus_addr, false), // Idle
+            (257..=320, 0..=3) | (337..=340, _) => self.nt_addr(), // Garbage and unused fetches
+            (257..=320, 4..=5) => self.spr_patt_addr((cycle - 257) / 8),
+            (257..=320, _) => self.spr_patt_addr((cycle - 257) / 8).wrapping_add(8),
+            (_, 0..=1) => self.nt_addr(),
+            (_, 2..=3) => self.attr_addr(),
+            (_, 4..=5) => self.bg_patt_addr(),
+            (_, _) => self.bg_patt_addr() + 8,
+        };
+        (addr, read)
+    }
+
+    // https://wiki.nesdev.com/w/index.php/PPU_scrolling#Tile_and_attribute_fetching
+    fn nt_addr(&self) -> u16 {
+        0x2000 | (self.registers.curr_addr.raw & 0x0FFF)
+    }
+
+    fn attr_addr(&self) -> u16 {
+        0x23C0
+            | (self.registers.curr_addr.raw & 0x0C00) // Nametable select
+            | ((self.registers.curr_addr.raw >> 4) & 0x38) // High 3 coarse Y => attr table row
+            | ((self.registers.curr_addr.raw >> 2) & 0x07) // High 3 coarse X => attr table col
+    }
+
+    // https://wiki.nesdev.com/w/index.php/PPU_pattern_tables
+    fn bg_patt_addr(&self) -> u16 {
+        self.registers.ppuctrl.get_patt_base() // PPUCTRL selects left or right half of table
+            | ((self.bg_data.latch.nt_byte as u16) << 4) // NT byte is 4 bits of row, 4 bits of col
+            | self.registers.curr_addr.get(vram_addr::FINE_Y) // "the row number within a tile"
+    }
+
     fn bg_fetch(&mut self, cycles_into_tile: u16) {
         // https://wiki.nesdev.com/w/index.php/PPU_rendering#Cycles_1-256
         match cycles_into_tile {
@@ -198,19 +427,12 @@ impl PPU {
                 self.bg_data.shift.attr_latch[0] = (self.bg_data.latch.attr_byte & 0b01) == 0b01;
                 self.bg_data.shift.attr_latch[1] = (self.bg_data.latch.attr_byte & 0b10) == 0b10;
 
-                // https://wiki.nesdev.com/w/index.php/PPU_scrolling#Tile_and_attribute_fetching
                 // Read tile data from a nametable
-                let nt_addr = 0x2000 | (self.registers.curr_addr.raw & 0x0FFF);
-                self.bg_data.latch.nt_byte = self.memory.read(nt_addr);
+                self.bg_data.latch.nt_byte = self.memory.fetch(self.nt_addr());
             }
             2 => {
-                // https://wiki.nesdev.com/w/index.php/PPU_scrolling#Tile_and_attribute_fetching
                 // Read attribute data from the nametable's attribute table
-                let attr_addr = 0x23C0
-                    | (self.registers.curr_addr.raw & 0x0C00) // Nametable select
-                    | ((self.registers.curr_addr.raw >> 4) & 0x38) // High 3 coarse Y => attr table row
-                    | ((self.registers.curr_addr.raw >> 2) & 0x07); // High 3 coarse X => attr table col
-                self.bg_data.latch.attr_byte = self.memory.read(attr_addr);
+                self.bg_data.latch.attr_byte = self.memory.fetch(self.attr_addr());
 
                 // https://wiki.nesdev.com/w/index.php/PPU_attribute_tables
                 // Move the correct bit pair to the low end of the latch
@@ -222,21 +444,12 @@ impl PPU {
                 }
             }
             4 => {
-                // https://wiki.nesdev.com/w/index.php/PPU_pattern_tables
                 // Read pattern data from the lower bit plane of the pattern table
-                let patt_addr = self.registers.ppuctrl.get_patt_base() // PPUCTRL selects left or right half of table
-                    | ((self.bg_data.latch.nt_byte as u16) << 4) // NT byte is 4 bits of row, 4 bits of col
-                    | self.registers.curr_addr.get(vram_addr::FINE_Y); // "the row number within a tile"
-
-                self.bg_data.latch.patt_lo = self.memory.read(patt_addr + 0);
+                self.bg_data.latch.patt_lo = self.memory.fetch(self.bg_patt_addr());
             }
             6 => {
                 // Read pattern data from the upper bit plane of the pattern table
-                let patt_addr = self.registers.ppuctrl.get_patt_base() // PPUCTRL selects left or right half of table
-                    | ((self.bg_data.latch.nt_byte as u16) << 4) // NT byte is 4 bits of row, 4 bits of col
-                    | self.registers.curr_addr.get(vram_addr::FINE_Y); // "the row number within a tile"
-
-                self.bg_data.latch.patt_hi = self.memory.read(patt_addr + 8);
+                self.bg_data.latch.patt_hi = self.memory.fetch(self.bg_patt_addr() + 8);
             }
             7 => {
                 if self.registers.ppumask.is_rendering() {
@@ -271,60 +484,12 @@ impl PPU {
             }
             4 => {
                 // Pattern table tile low
-                let mut y = self
-                    .scan
-                    .line
-                    .wrapping_sub(self.oam2[4 * (spr_num as usize) + 0] as u16);
-                let mut any_good = false;
-                for i in 1..4 {
-                    if self.oam2[4 * (spr_num as usize) + i] != 0xFF {
-                        any_good = true;
-                        break;
-                    }
-                }
-                if !any_good {
-                    self.spr_data.registers[spr_num as usize].is_dummy = true;
-                    y = 0;
-                } else {
-                    self.spr_data.registers[spr_num as usize].is_dummy = false;
-                }
-
-                let mut tile_index = self.oam2[4 * (spr_num as usize) + 1] as u16;
-                let base = if self
-                    .registers
-                    .ppuctrl
-                    .contains(ControlRegister::SPRITE_HEIGHT)
-                {
-                    0x1000 * (tile_index & 1)
-                } else {
-                    self.registers.ppuctrl.get_sprite_patt_base()
-                };
-
-                let flip_v = self.oam2[4 * (spr_num as usize) + 2] >> 7 == 1;
-                if flip_v {
-                    y = self
-                        .registers
-                        .ppuctrl
-                        .get_sprite_height()
-                        .wrapping_sub(1)
-                        .wrapping_sub(y);
-                }
-                if self
-                    .registers
-                    .ppuctrl
-                    .contains(ControlRegister::SPRITE_HEIGHT)
-                {
-                    tile_index &= 0b1111_1110;
-                    if y > 7 {
-                        tile_index += 1;
-                        y -= 8;
-                    }
-                }
-                let patt_addr = base | (tile_index << 4) | y;
+                self.spr_data.registers[spr_num as usize].is_dummy = self.is_dummy_sprite(spr_num);
+                let patt_addr = self.spr_patt_addr(spr_num);
                 self.spr_data.registers[spr_num as usize].patt_shift[0] =
-                    self.memory.read(patt_addr + 0);
+                    self.memory.fetch(patt_addr + 0);
                 self.spr_data.registers[spr_num as usize].patt_shift[1] =
-                    self.memory.read(patt_addr.wrapping_add(8));
+                    self.memory.fetch(patt_addr.wrapping_add(8));
             }
             6 => {
                 // Pattern table tile high (fetched alongside low to avoid repeating work)
@@ -333,6 +498,93 @@ impl PPU {
         }
     }
 
+    /// Whether sprite evaluation left a slot of secondary OAM empty
+    fn is_dummy_sprite(&self, spr_num: u16) -> bool {
+        let entry = &self.oam2[4 * spr_num as usize..4 * spr_num as usize + 4];
+        entry[1..].iter().all(|&byte| byte == 0xFF)
+    }
+
+    /// The address of the row of a sprite's pattern that's fetched for the next line,
+    /// where an empty slot fetches a row of tile $FF
+    fn spr_patt_addr(&self, spr_num: u16) -> u16 {
+        let entry = &self.oam2[4 * spr_num as usize..4 * spr_num as usize + 4];
+        let y = if self.is_dummy_sprite(spr_num) {
+            0
+        } else {
+            self.scan.line.wrapping_sub(entry[0] as u16)
+        };
+        self.sprite_patt_addr(y, entry[1], entry[2])
+    }
+
+    /// The address of a row of a sprite's pattern, counted from the top of the unflipped sprite
+    fn sprite_patt_addr(&self, row: u16, tile: u8, attr: u8) -> u16 {
+        let mut y = row;
+        let mut tile_index = tile as u16;
+        let base = if self
+            .registers
+            .ppuctrl
+            .contains(ControlRegister::SPRITE_HEIGHT)
+        {
+            0x1000 * (tile_index & 1)
+        } else {
+            self.registers.ppuctrl.get_sprite_patt_base()
+        };
+
+        let flip_v = attr >> 7 == 1;
+        if flip_v {
+            y = self
+                .registers
+                .ppuctrl
+                .get_sprite_height()
+                .wrapping_sub(1)
+                .wrapping_sub(y);
+        }
+        if self
+            .registers
+            .ppuctrl
+            .contains(ControlRegister::SPRITE_HEIGHT)
+        {
+            tile_index &= 0b1111_1110;
+            if y > 7 {
+                tile_index += 1;
+                y -= 8;
+            }
+        }
+        base | (tile_index << 4) | y
+    }
+
+    /// Find the sprites on the next line that evaluation had no room for, and fetch their
+    /// patterns without side effects, since the hardware never reads them
+    fn fetch_extra_sprites(&mut self) {
+        let line = self.scan.line;
+        let height = self.registers.ppuctrl.get_sprite_height();
+        let mut found = 0;
+        for spr_num in 0..64 {
+            let entry = &self.oam[4 * spr_num..4 * spr_num + 4];
+            let y = entry[0] as u16;
+            if !(y <= line && line < y.wrapping_add(height)) {
+                continue;
+            }
+            found += 1;
+            if found <= 8 {
+                continue;
+            }
+
+            let patt_addr = self.sprite_patt_addr(line - y, entry[1], entry[2]);
+            let sprite_registers = SpriteRegisters {
+                num: spr_num as u16,
+                patt_shift: [
+                    self.memory.peek(patt_addr),
+                    self.memory.peek(patt_addr.wrapping_add(8)),
+                ],
+                attr_latch: entry[2],
+                x_counter: entry[3],
+                is_dummy: false,
+            };
+            self.spr_data.extra.push(sprite_registers);
+        }
+    }
+
     fn spr_eval(&mut self, odd_cycle: bool) {
         if odd_cycle {
             self.spr_data.oam_byte =
@@ -427,7 +679,20 @@ impl PPU {
         }
     }
 
-    fn get_bg_pixel(&mut self) -> (bool, u8) {
+    /// Apply greyscale and emphasis to a color index as the PPU outputs it
+    /// https://wiki.nesdev.com/w/index.php/PPU_registers#Color_control
+    fn output_pixel(&self, color: u8) -> u16 {
+        let mask = self.registers.ppumask;
+        let color = if mask.contains(MaskRegister::GRAYSCALE) {
+            color & 0x30 // Only the grey column is left
+        } else {
+            color
+        };
+        let emphasis = (mask.bits() >> 5) as u16;
+        (color as u16 & COLOR_INDEX_MASK) | (emphasis << EMPHASIS_SHIFT)
+    }
+
+    fn get_bg_pixel(&mut self) -> (bool, u8, u8) {
         // https://wiki.nesdev.com/w/index.php/PPU_rendering#Preface
         let nth_bit = |val: u16, n: u8| (val & (1 << n)) >> n;
 
@@ -449,7 +714,7 @@ impl PPU {
             || (!self.registers.ppumask.contains(MaskRegister::BACK_LEFT_COL)
                 && (self.scan.cycle - 1 < 8))
         {
-            return (false, 0x00);
+            return (false, 0x00, 0);
         }
 
         // https://wiki.nesdev.com/w/index.php/PPU_palettes#Memory_Map
@@ -458,30 +723,22 @@ impl PPU {
             | patt_pair; // "Pixel value from tile data"
 
         // TODO: Make a struct for this
-        // (pixel_on, color)
-        (patt_pair != 0, self.memory.read(color_index))
+        // (pixel_on, color, palette)
+        (
+            patt_pair != 0,
+            self.memory.fetch(color_index),
+            attr_pair as u8,
+        )
     }
 
-    fn get_spr_pixel(&mut self) -> (bool, u8, bool, bool) {
+    fn get_spr_pixel(&mut self) -> (bool, u8, bool, bool, u8) {
         // https://wiki.nesdev.com/w/index.php/PPU_rendering#Preface
         let mut pixel_option = None;
         for sprite_registers in &mut self.spr_data.registers {
-            if sprite_registers.x_counter != 0 || sprite_registers.is_dummy {
-                continue;
-            }
-
-            let flip_h = ((sprite_registers.attr_latch >> 6) & 1) == 1;
-            let offset = if flip_h { 0 } else { 7 };
-            let patt_pair = (((sprite_registers.patt_shift[1] >> offset) & 1) << 1)
-                | ((sprite_registers.patt_shift[0] >> offset) & 1);
-
-            if flip_h {
-                sprite_registers.patt_shift[1] >>= 1;
-                sprite_registers.patt_shift[0] >>= 1;
-            } else {
-                sprite_registers.patt_shift[1] <<= 1;
-                sprite_registers.patt_shift[0] <<= 1;
-            }
+            let patt_pair = match sprite_registers.shift_pixel() {
+                Some(patt_pair) => patt_pair,
+                None => continue,
+            };
 
             if patt_pair != 0 && pixel_option.is_none() {
                 let priority = ((sprite_registers.attr_latch >> 5) & 1) == 0;
@@ -490,14 +747,35 @@ impl PPU {
                         | (patt_pair as u16); // "Pixel value from tile data"
                 pixel_option = Some((
                     true,
-                    self.memory.read(color_index),
+                    self.memory.fetch(color_index),
                     priority,
                     sprite_registers.num == 0,
+                    sprite_registers.attr_latch & 0b11,
                 ));
             }
         }
 
-        for sprite_registers in &mut self.spr_data.registers {
+        // Sprites past the limit are behind the others, since they're later in OAM
+        for sprite_registers in &mut self.spr_data.extra {
+            if let Some(patt_pair) = sprite_registers.shift_pixel() {
+                if patt_pair != 0 && pixel_option.is_none() {
+                    let priority = ((sprite_registers.attr_latch >> 5) & 1) == 0;
+                    let color_index = 0x3f10
+                        | (((sprite_registers.attr_latch & 0b11) as u16) << 2)
+                        | (patt_pair as u16);
+                    let palette = sprite_registers.attr_latch & 0b11;
+                    let color = self.memory.peek(color_index);
+                    pixel_option = Some((true, color, priority, false, palette));
+                }
+            }
+        }
+
+        let all_sprites = self
+            .spr_data
+            .registers
+            .iter_mut()
+            .chain(self.spr_data.extra.iter_mut());
+        for sprite_registers in all_sprites {
             if sprite_registers.x_counter > 0 {
                 sprite_registers.x_counter -= 1;
             }
@@ -514,8 +792,8 @@ impl PPU {
         }
 
         // TODO: Make a struct for this
-        // (pixel_on, color, priority, is_sprite_zero)
-        pixel_option.unwrap_or((false, 0x00, false, false))
+        // (pixel_on, color, priority, is_sprite_zero, palette)
+        pixel_option.unwrap_or((false, 0x00, false, false, 0))
     }
 
     fn run_oam_dma(&mut self, data: u8) {
@@ -586,6 +864,7 @@ impl Memory for PPU {
             register_addrs::PPUDATA => {
                 let old_ppudata = self.registers.ppudata;
                 self.registers.ppudata = self.memory.read(self.registers.curr_addr.raw);
+                self.data_read = Some(self.registers.curr_addr.raw & 0x3FFF);
 
                 let old_addr = self.registers.curr_addr.raw;
                 self.registers.curr_addr.raw += self.registers.ppuctrl.get_vram_increment();
@@ -11,6 +11,7 @@ pub mod register_addrs {
     pub const OAMDMA: u16 = 0x4014;
 }
 
+#[derive(Clone)]
 pub struct PPURegisters {
     pub ppuctrl: ControlRegister,
     pub ppumask: MaskRegister,
@@ -153,6 +154,7 @@ impl StatusRegister {
     }
 }
 
+#[derive(Clone)]
 pub struct AddressRegister {
     pub raw: u16,
 }
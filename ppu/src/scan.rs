@@ -1,8 +1,10 @@
+#[derive(Clone)]
 pub struct Scan {
     pub line: u16,
     pub cycle: u16,
     pub total_cycles: u64,
     pub total_frames: u64,
+    pub burst_phase: u8, // Where the color subcarrier starts in this frame, in thirds of a cycle
     odd_frame: bool,
 }
 
@@ -13,6 +15,7 @@ impl Scan {
             cycle: 0,
             total_cycles: 0,
             total_frames: 0,
+            burst_phase: 0,
             odd_frame: false,
         }
     }
@@ -28,6 +31,10 @@ impl Scan {
         // but in emulation, we can just skip (340,261) entirely and continue as usual
         if self.odd_frame && self.line == 261 && self.cycle == 340 && is_rendering {
             self.cycle += 1;
+            // https://wiki.nesdev.com/w/index.php/NTSC_video
+            // A frame of 89342 dots moves the subcarrier on by a third of a cycle,
+            // and a frame a dot shorter moves it by two thirds
+            self.burst_phase = (self.burst_phase + 1) % 3;
         }
 
         if self.cycle > 340 {
@@ -38,6 +45,7 @@ impl Scan {
                 self.line = 0;
                 self.odd_frame = !self.odd_frame;
                 self.total_frames += 1;
+                self.burst_phase = (self.burst_phase + 1) % 3;
             }
         }
     }
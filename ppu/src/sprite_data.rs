@@ -1,4 +1,5 @@
 // https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation
+#[derive(Clone)]
 pub struct SpriteData {
     pub registers: [SpriteRegisters; 8],
     pub eval_state: SpriteEvalState,
@@ -8,6 +9,7 @@ pub struct SpriteData {
     pub byte_num: u8,           // "byte m (0-3)"
     pub oam_byte: u8,           // Filled on odd cycles
     pub oam2_index: u8,         // Number of sprites found on this line.
+    pub extra: Vec<SpriteRegisters>, // Sprites past the first 8 on a line, drawn without the limit
 }
 
 impl SpriteData {
@@ -21,6 +23,7 @@ impl SpriteData {
             byte_num: 0,
             oam_byte: 0,
             oam2_index: 0,
+            extra: Vec::new(),
         }
     }
 
@@ -54,9 +57,31 @@ impl SpriteRegisters {
             is_dummy: false,
         }
     }
+
+    /// Shift out the pattern of the sprite's pixel at this dot, if it has reached the sprite
+    pub fn shift_pixel(&mut self) -> Option<u8> {
+        if self.x_counter != 0 || self.is_dummy {
+            return None;
+        }
+
+        let flip_h = ((self.attr_latch >> 6) & 1) == 1;
+        let offset = if flip_h { 0 } else { 7 };
+        let patt_pair =
+            (((self.patt_shift[1] >> offset) & 1) << 1) | ((self.patt_shift[0] >> offset) & 1);
+
+        if flip_h {
+            self.patt_shift[1] >>= 1;
+            self.patt_shift[0] >>= 1;
+        } else {
+            self.patt_shift[1] <<= 1;
+            self.patt_shift[0] <<= 1;
+        }
+        Some(patt_pair)
+    }
 }
 
 // https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation#Details Cycles 65-256: Sprite evaluation
+#[derive(Clone, Copy)]
 pub enum SpriteEvalState {
     CopyY,                // Step 1
     CopyRemaining(usize), // Step 1a (with index m to be copied)
@@ -0,0 +1,400 @@
+use crate::registers::vram_addr;
+use crate::PPU;
+
+use std::fmt;
+
+pub const NAMETABLE_VIEW_WIDTH: usize = 512;
+pub const NAMETABLE_VIEW_HEIGHT: usize = 480;
+// A pattern table is 16x16 tiles
+pub const PATTERN_TABLE_VIEW_SIZE: usize = 128;
+
+// The sprite view is the picture, with each sprite's bounding box,
+// beside a grid of every sprite in OAM, 8 to a row
+pub const SPRITE_VIEW_WIDTH: usize = SPRITE_GRID_LEFT + 8 * SPRITE_CELL_WIDTH;
+pub const SPRITE_VIEW_HEIGHT: usize = 240;
+const SPRITE_GRID_LEFT: usize = 256 + 8;
+const SPRITE_CELL_WIDTH: usize = 16;
+const SPRITE_CELL_HEIGHT: usize = 24;
+
+const VIEWPORT_COLOR: u32 = 0xFFFFFF;
+const SPRITE_COLOR: u32 = 0x50FF50;
+const DROPPED_SPRITE_COLOR: u32 = 0xFF5050;
+const SPRITE_CELL_COLOR: u32 = 0x202020;
+
+/// An image made for a debug view, with 3 bytes of RGB per pixel, in rows from the top
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    /// The color of a pixel, as 0xRRGGBB
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        let i = (y * self.width + x) * 3;
+        let (r, g, b) = (self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]);
+        ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        let i = (y * self.width + x) * 3;
+        self.pixels[i] = (color >> 16) as u8;
+        self.pixels[i + 1] = (color >> 8) as u8;
+        self.pixels[i + 2] = color as u8;
+    }
+
+    /// Copy another image into this one, with its top left corner at (left, top)
+    pub fn draw_image(&mut self, image: &RgbImage, left: usize, top: usize) {
+        for y in 0..image.height {
+            let src = y * image.width * 3;
+            let dst = ((top + y) * self.width + left) * 3;
+            self.pixels[dst..dst + image.width * 3]
+                .copy_from_slice(&image.pixels[src..src + image.width * 3]);
+        }
+    }
+
+    /// Draw the outline of a rectangle, clipped to the image
+    pub fn rect(&mut self, left: usize, top: usize, width: usize, height: usize, color: u32) {
+        let (right, bottom) = (left + width - 1, top + height - 1);
+        for y in top..=bottom.min(self.height - 1) {
+            for x in left..=right.min(self.width - 1) {
+                if x == left || x == right || y == top || y == bottom {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, left: usize, top: usize, width: usize, height: usize, color: u32) {
+        for y in top..(top + height).min(self.height) {
+            for x in left..(left + width).min(self.width) {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// A sprite's entry in OAM, decoded
+/// https://wiki.nesdev.com/w/index.php/PPU_OAM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteInfo {
+    pub index: u8,
+    pub x: u8,
+    pub y: u8, // One less than the first scanline the sprite is on
+    pub tile: u8,
+    pub palette: u8, // Of the 4 sprite palettes
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub behind_background: bool,
+    pub height: u8,    // 8 or 16, for all sprites, from PPUCTRL
+    pub dropped: bool, // Whether the 8 sprites per scanline limit hid it on any scanline
+}
+
+impl fmt::Display for SpriteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} at ({}, {}): tile ${:02X}, palette {}",
+            self.index, self.x, self.y, self.tile, self.palette
+        )?;
+        if self.flip_horizontal {
+            write!(f, ", flipped horizontally")?;
+        }
+        if self.flip_vertical {
+            write!(f, ", flipped vertically")?;
+        }
+        if self.behind_background {
+            write!(f, ", behind background")?;
+        }
+        if self.dropped {
+            write!(f, ", dropped")?;
+        }
+        Ok(())
+    }
+}
+
+/// Draw a 4 KB pattern table, like those at $0000 and $1000, as 16x16 tiles,
+/// with the color indices of a palette and the RGB of the 64 colors, as 0xRRGGBB
+/// https://wiki.nesdev.com/w/index.php/PPU_pattern_tables
+pub fn render_pattern_table(data: &[u8], palette: &[u8; 4], colors: &[u32]) -> RgbImage {
+    let mut image = RgbImage::new(PATTERN_TABLE_VIEW_SIZE, PATTERN_TABLE_VIEW_SIZE);
+    for (tile, tile_data) in data.chunks_exact(16).take(256).enumerate() {
+        let (left, top) = ((tile % 16) * 8, (tile / 16) * 8);
+        draw_tile(
+            &mut image, left, top, tile_data, palette, colors, false, false,
+        );
+    }
+    image
+}
+
+/// Draw a tile from its 16 bytes of pattern data, optionally flipped, as sprites can be
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_tile(
+    image: &mut RgbImage,
+    left: usize,
+    top: usize,
+    tile_data: &[u8],
+    palette: &[u8; 4],
+    colors: &[u32],
+    flip_horizontal: bool,
+    flip_vertical: bool,
+) {
+    for row in 0..8 {
+        let (patt_lo, patt_hi) = (tile_data[row], tile_data[row + 8]);
+        for col in 0..8 {
+            let bit = 7 - col;
+            let pixel = (((patt_hi >> bit) & 1) << 1) | ((patt_lo >> bit) & 1);
+            let color = colors[(palette[pixel as usize] & 0x3F) as usize];
+            let x = if flip_horizontal { 7 - col } else { col };
+            let y = if flip_vertical { 7 - row } else { row };
+            image.set_pixel(left + x, top + y, color);
+        }
+    }
+}
+
+impl PPU {
+    /// Draw the four logical nametables, as the current mirroring arranges them, in a 2x2 grid,
+    /// with the background pattern table and palettes in use now, and outline the 256x240
+    /// viewport that rendering starts from (the scroll in the "t" register).
+    /// Colors are the RGB values of the 64 NES colors, as 0xRRGGBB.
+    /// https://wiki.nesdev.com/w/index.php/PPU_nametables
+    pub fn render_nametables(&self, colors: &[u32]) -> RgbImage {
+        let mut image = RgbImage::new(NAMETABLE_VIEW_WIDTH, NAMETABLE_VIEW_HEIGHT);
+        let palettes: Vec<[u8; 4]> = (0..4).map(|palette| self.palette(palette)).collect();
+        let patt_base = self.registers.ppuctrl.get_patt_base();
+
+        for nametable in 0..4 {
+            let base = 0x2000 + 0x400 * nametable as u16;
+            let (left, top) = ((nametable % 2) * 256, (nametable / 2) * 240);
+            for tile_y in 0..30 {
+                for tile_x in 0..32 {
+                    let tile = self.memory.peek(base + tile_y * 32 + tile_x) as u16;
+                    let tile_data = self.peek_tile(patt_base + tile * 16);
+
+                    // https://wiki.nesdev.com/w/index.php/PPU_attribute_tables
+                    let attr = self
+                        .memory
+                        .peek(base + 0x3C0 + (tile_y / 4) * 8 + tile_x / 4);
+                    let shift = ((tile_y % 4) / 2) * 4 + ((tile_x % 4) / 2) * 2;
+                    let palette = &palettes[((attr >> shift) & 0b11) as usize];
+
+                    let (x, y) = (left + tile_x as usize * 8, top + tile_y as usize * 8);
+                    draw_tile(&mut image, x, y, &tile_data, palette, colors, false, false);
+                }
+            }
+        }
+
+        // https://wiki.nesdev.com/w/index.php/PPU_scrolling
+        let temp_addr = &self.registers.temp_addr;
+        let nametable = temp_addr.get(vram_addr::NAMETABLE_SEL) as usize;
+        let scroll_x = (nametable % 2) * 256
+            + temp_addr.get(vram_addr::COARSE_X) as usize * 8
+            + self.registers.fine_x as usize;
+        let scroll_y = (nametable / 2) * 240
+            + temp_addr.get(vram_addr::COARSE_Y) as usize * 8
+            + temp_addr.get(vram_addr::FINE_Y) as usize;
+        let mut outline = |x: usize, y: usize| {
+            image.set_pixel(
+                (scroll_x + x) % NAMETABLE_VIEW_WIDTH,
+                (scroll_y + y) % NAMETABLE_VIEW_HEIGHT,
+                VIEWPORT_COLOR,
+            );
+        };
+        for x in 0..256 {
+            outline(x, 0);
+            outline(x, 239);
+        }
+        for y in 0..240 {
+            outline(0, y);
+            outline(255, y);
+        }
+
+        image
+    }
+
+    /// Draw both pattern tables as the mapper has banked them in, side by side,
+    /// with one of the 4 background (0-3) or 4 sprite (4-7) palettes
+    pub fn render_pattern_tables(&self, palette: u8, colors: &[u32]) -> RgbImage {
+        let palette = self.palette(palette);
+        let mut image = RgbImage::new(PATTERN_TABLE_VIEW_SIZE * 2, PATTERN_TABLE_VIEW_SIZE);
+        for table in 0..2 {
+            let data: Vec<u8> = (0..0x1000)
+                .map(|addr| self.memory.peek(0x1000 * table + addr))
+                .collect();
+            let table_image = render_pattern_table(&data, &palette, colors);
+            image.draw_image(&table_image, PATTERN_TABLE_VIEW_SIZE * table as usize, 0);
+        }
+        image
+    }
+
+    /// Decode all 64 sprites in OAM, and work out which ones sprite evaluation drops
+    /// because they're after 8 others on a scanline
+    /// https://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation
+    pub fn sprites(&self) -> Vec<SpriteInfo> {
+        let height = self.registers.ppuctrl.get_sprite_height();
+        let mut sprites: Vec<SpriteInfo> = self
+            .oam
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, entry)| SpriteInfo {
+                index: index as u8,
+                x: entry[3],
+                y: entry[0],
+                tile: entry[1],
+                palette: entry[2] & 0b11,
+                flip_horizontal: (entry[2] >> 6) & 1 == 1,
+                flip_vertical: (entry[2] >> 7) & 1 == 1,
+                behind_background: (entry[2] >> 5) & 1 == 1,
+                height: height as u8,
+                dropped: false,
+            })
+            .collect();
+
+        for line in 0..240 {
+            let mut in_range = 0;
+            for sprite in sprites.iter_mut() {
+                let y = sprite.y as u16;
+                if y <= line && line < y + height {
+                    in_range += 1;
+                    if in_range > 8 {
+                        sprite.dropped = true;
+                    }
+                }
+            }
+        }
+        sprites
+    }
+
+    /// Draw a sprite as it appears on screen, 8 pixels wide and 8 or 16 tall
+    pub fn render_sprite(&self, sprite: &SpriteInfo, colors: &[u32]) -> RgbImage {
+        let mut image = RgbImage::new(8, sprite.height as usize);
+        let palette = self.palette(4 + sprite.palette);
+        // https://wiki.nesdev.com/w/index.php/PPU_OAM#Byte_1
+        let tiles = if sprite.height == 16 {
+            let base = 0x1000 * (sprite.tile as u16 & 1) + 16 * (sprite.tile as u16 & 0xFE);
+            vec![base, base + 16]
+        } else {
+            vec![self.registers.ppuctrl.get_sprite_patt_base() + 16 * sprite.tile as u16]
+        };
+        for (half, &addr) in tiles.iter().enumerate() {
+            // Flipping an 8x16 sprite vertically swaps its halves too
+            let top = if sprite.flip_vertical {
+                8 * (tiles.len() - 1 - half)
+            } else {
+                8 * half
+            };
+            let tile_data = self.peek_tile(addr);
+            draw_tile(
+                &mut image,
+                0,
+                top,
+                &tile_data,
+                &palette,
+                colors,
+                sprite.flip_horizontal,
+                sprite.flip_vertical,
+            );
+        }
+        image
+    }
+
+    /// Draw the last picture with every sprite's bounding box, beside a grid of all 64 sprites,
+    /// where those dropped on some scanline are outlined in red rather than green.
+    /// Sprites are drawn in the 64 colors, and the picture's pixels, emphasis and all, by pixel_rgb.
+    pub fn render_sprite_view(&self, colors: &[u32], pixel_rgb: impl Fn(u16) -> u32) -> RgbImage {
+        let mut image = RgbImage::new(SPRITE_VIEW_WIDTH, SPRITE_VIEW_HEIGHT);
+        // Boxes are drawn on a picture of their own, so that they're clipped to it
+        let mut picture = RgbImage::new(256, 240);
+        for (y, row) in self.framebuffer.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                picture.set_pixel(x, y, pixel_rgb(pixel));
+            }
+        }
+
+        // Draw the boxes of earlier sprites, which are in front, last
+        let sprites = self.sprites();
+        for sprite in sprites.iter().rev() {
+            let color = if sprite.dropped {
+                DROPPED_SPRITE_COLOR
+            } else {
+                SPRITE_COLOR
+            };
+            let top = sprite.y as usize + 1;
+            if top < 240 {
+                picture.rect(sprite.x as usize, top, 8, sprite.height as usize, color);
+            }
+
+            let (left, top) = sprite_cell(sprite.index);
+            image.fill_rect(
+                left,
+                top,
+                SPRITE_CELL_WIDTH,
+                SPRITE_CELL_HEIGHT,
+                SPRITE_CELL_COLOR,
+            );
+            if sprite.dropped {
+                image.rect(left, top, SPRITE_CELL_WIDTH, SPRITE_CELL_HEIGHT, color);
+            }
+            image.draw_image(&self.render_sprite(sprite, colors), left + 4, top + 4);
+        }
+        image.draw_image(&picture, 0, 0);
+        image
+    }
+
+    /// The sprite at a point in the sprite view, either in the grid or on the picture
+    pub fn sprite_in_view(&self, x: usize, y: usize) -> Option<SpriteInfo> {
+        let sprites = self.sprites();
+        if x >= SPRITE_GRID_LEFT {
+            let index = ((y / SPRITE_CELL_HEIGHT) * 8) + (x - SPRITE_GRID_LEFT) / SPRITE_CELL_WIDTH;
+            return sprites.get(index).copied();
+        } else if x >= 256 {
+            return None; // Between the picture and the grid
+        }
+        sprites.into_iter().find(|sprite| {
+            let (left, top) = (sprite.x as usize, sprite.y as usize + 1);
+            (left..left + 8).contains(&x) && (top..top + sprite.height as usize).contains(&y)
+        })
+    }
+
+    /// The color indices of one of the 4 background (0-3) or 4 sprite (4-7) palettes,
+    /// where color 0 is always the shared backdrop color
+    /// https://wiki.nesdev.com/w/index.php/PPU_palettes
+    pub fn palette(&self, palette: u8) -> [u8; 4] {
+        let mut colors = [0; 4];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let addr = if i == 0 {
+                0x3F00
+            } else {
+                0x3F00 + 4 * (palette as u16 % 8) + i as u16
+            };
+            *color = self.memory.peek(addr);
+        }
+        colors
+    }
+
+    fn peek_tile(&self, addr: u16) -> [u8; 16] {
+        let mut tile_data = [0; 16];
+        for (i, byte) in tile_data.iter_mut().enumerate() {
+            *byte = self.memory.peek(addr + i as u16);
+        }
+        tile_data
+    }
+}
+
+/// The top left corner of a sprite's cell in the sprite view's grid
+fn sprite_cell(index: u8) -> (usize, usize) {
+    let (col, row) = (index as usize % 8, index as usize / 8);
+    (
+        SPRITE_GRID_LEFT + col * SPRITE_CELL_WIDTH,
+        row * SPRITE_CELL_HEIGHT,
+    )
+}
@@ -1,4 +1,15 @@
-use nes::NES;
+mod viewer;
+
+pub use viewer::{Viewer, ViewerWindow};
+
+use nes::event_log::{Event, DOTS_PER_SCANLINE, SCANLINES_PER_FRAME};
+use nes::gdb::GdbServer;
+use nes::script::Script;
+use nes::video::{
+    letterbox, CrtFilter, CrtSettings, Layout, NtscFilter, PixelFormat, Rect, Scaler, NTSC_HEIGHT,
+    NTSC_WIDTH,
+};
+use nes::{Layers, NES};
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -7,34 +18,159 @@ use std::time;
 
 use sdl2::audio::AudioSpecDesired;
 use sdl2::controller::{Axis, Button};
-use sdl2::event::Event as SDL_Event;
-use sdl2::keyboard::Scancode;
+use sdl2::event::{Event as SDL_Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::pixels::PixelFormatEnum;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, WindowCanvas};
 use sdl2::video::Window;
 use sdl2::Sdl;
 
-const COLORS: &'static [i32] = &[
-    0x666666, 0x002A88, 0x1412A7, 0x3B00A4, 0x5C007E, 0x6E0040, 0x6C0600, 0x561D00, 0x333500,
-    0x0B4800, 0x005200, 0x004F08, 0x00404D, 0x000000, 0x000000, 0x000000, 0xADADAD, 0x155FD9,
-    0x4240FF, 0x7527FE, 0xA01ACC, 0xB71E7B, 0xB53120, 0x994E00, 0x6B6D00, 0x388700, 0x0C9300,
-    0x008F32, 0x007C8D, 0x000000, 0x000000, 0x000000, 0xFFFEFF, 0x64B0FF, 0x9290FF, 0xC676FF,
-    0xF36AFF, 0xFE6ECC, 0xFE8170, 0xEA9E22, 0xBCBE00, 0x88D800, 0x5CE430, 0x45E082, 0x48CDDE,
-    0x4F4F4F, 0x000000, 0x000000, 0xFFFEFF, 0xC0DFFF, 0xD3D2FF, 0xE8C8FF, 0xFBC2FF, 0xFEC4EA,
-    0xFECCC5, 0xF7D8A5, 0xE4E594, 0xCFEF96, 0xBDF4AB, 0xB3F3CC, 0xB5EBF2, 0xB8B8B8, 0x000000,
-    0x000000,
-];
-
 const SAMPLE_RATE: usize = 96000;
 const DESIRED_AUDIO_DELAY_MS: usize = 60;
 const DELAY_SAMPLES: usize =
     (SAMPLE_RATE as f32 * (DESIRED_AUDIO_DELAY_MS as f32 / 1000.)) as usize;
 const AUDIO_BUFF_THRESHOLD: usize = std::mem::size_of::<f32>() * DELAY_SAMPLES;
 
+const BLANKING_COLOR: [u8; 3] = [0x20, 0x20, 0x20];
+
+// What F2 cycles through, where None is the plain picture scaled up by the GPU
+const SCALERS: [Option<Scaler>; 10] = [
+    None,
+    Some(Scaler::Scale2x),
+    Some(Scaler::Scale3x),
+    Some(Scaler::Blend2x),
+    Some(Scaler::Blend3x),
+    Some(Scaler::Xbrz(2)),
+    Some(Scaler::Xbrz(3)),
+    Some(Scaler::Xbrz(4)),
+    Some(Scaler::Xbrz(5)),
+    Some(Scaler::Xbrz(6)),
+];
+
+// What 1 to 8 hide and show: background palettes 0-3, then sprite palettes 0-3
+const PALETTE_KEYS: [Keycode; 8] = [
+    Keycode::Num1,
+    Keycode::Num2,
+    Keycode::Num3,
+    Keycode::Num4,
+    Keycode::Num5,
+    Keycode::Num6,
+    Keycode::Num7,
+    Keycode::Num8,
+];
+
+/// Toggle the part of the picture that a hotkey hides and shows, if it's one of them:
+/// F5 for the background, F6 for sprites, or a palette
+fn toggle_layer(mut layers: Layers, keycode: Keycode) -> Option<Layers> {
+    if keycode == Keycode::F5 {
+        layers.background = !layers.background;
+    } else if keycode == Keycode::F6 {
+        layers.sprites = !layers.sprites;
+    } else {
+        let i = PALETTE_KEYS.iter().position(|&key| key == keycode)?;
+        layers.palettes[i] = !layers.palettes[i];
+    }
+    Some(layers)
+}
+
+/// Name the parts of the picture that are hidden, for the window title
+fn describe_layers(layers: Layers) -> String {
+    let mut hidden = Vec::new();
+    if !layers.background {
+        hidden.push("background".to_string());
+    }
+    if !layers.sprites {
+        hidden.push("sprites".to_string());
+    }
+    for (i, &shown) in layers.palettes.iter().enumerate() {
+        if !shown {
+            let kind = if i < 4 { "BG" } else { "sprite" };
+            hidden.push(format!("{} palette {}", kind, i % 4));
+        }
+    }
+    if hidden.is_empty() {
+        "all layers shown".to_string()
+    } else {
+        format!("hiding {}", hidden.join(", "))
+    }
+}
+
+/// Unpack an area of an RGB24 picture, `width` pixels wide, into 0xRRGGBB colors
+fn unpack_rgb24(buff: &[u8], width: usize, area: Rect, pixels: &mut Vec<u32>) {
+    pixels.clear();
+    for y in area.y..area.y + area.height {
+        let start = (y * width + area.x) * 3;
+        pixels.extend(
+            buff[start..start + area.width * 3]
+                .chunks_exact(3)
+                .map(|rgb| ((rgb[0] as u32) << 16) | ((rgb[1] as u32) << 8) | rgb[2] as u32),
+        );
+    }
+}
+
+fn to_sdl_rect(rect: Rect) -> sdl2::rect::Rect {
+    sdl2::rect::Rect::new(
+        rect.x as i32,
+        rect.y as i32,
+        rect.width as u32,
+        rect.height as u32,
+    )
+}
+
+/// Copy a picture, or the area of it that's shown, into the viewport
+fn copy_picture(canvas: &mut WindowCanvas, texture: &Texture, area: Option<Rect>, viewport: Rect) {
+    canvas
+        .copy(texture, area.map(to_sdl_rect), to_sdl_rect(viewport))
+        .unwrap();
+}
+
+/// Draw a frame and the register accesses made during it on a grid of every PPU dot,
+/// where the picture starts at dot 1 of scanline 0, into an RGB24 buffer
+fn draw_event_viewer(screen_buff: &[u8], events: &[Event], grid_buff: &mut [u8]) {
+    for line in 0..SCANLINES_PER_FRAME {
+        for dot in 0..DOTS_PER_SCANLINE {
+            let grid_i = (line * DOTS_PER_SCANLINE + dot) * 3;
+            let color = if line < 240 && (1..=256).contains(&dot) {
+                let pixel_i = (line * 256 + dot - 1) * 3;
+                [
+                    screen_buff[pixel_i],
+                    screen_buff[pixel_i + 1],
+                    screen_buff[pixel_i + 2],
+                ]
+            } else {
+                BLANKING_COLOR
+            };
+            grid_buff[grid_i..grid_i + 3].copy_from_slice(&color);
+        }
+    }
+
+    // Each event is a 3x3 square, so that it can be seen over the picture
+    for event in events {
+        let c = event.kind.color();
+        let color = [(c >> 16) as u8, ((c >> 8) & 0xFF) as u8, (c & 0xFF) as u8];
+        let (dot, line) = (event.dot as usize, event.scanline as usize);
+        for y in line.saturating_sub(1)..(line + 2).min(SCANLINES_PER_FRAME) {
+            for x in dot.saturating_sub(1)..(dot + 2).min(DOTS_PER_SCANLINE) {
+                let grid_i = (y * DOTS_PER_SCANLINE + x) * 3;
+                grid_buff[grid_i..grid_i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
 pub struct SDLUI {
     sdl_context: Sdl,
     canvas: WindowCanvas,
     nes: Rc<RefCell<NES>>,
+    gdb_server: Option<GdbServer>,
+    script: Option<Script>,
+    event_viewer: bool,
+    viewers: Vec<ViewerWindow>,
+    ntsc_filter: Option<NtscFilter>,
+    scaler: Option<Scaler>,
+    crt_filter: CrtFilter,
+    crt: bool,
+    layout: Layout,
 }
 
 impl SDLUI {
@@ -43,6 +179,145 @@ impl SDLUI {
             sdl_context,
             canvas: window.into_canvas().build().unwrap(),
             nes,
+            gdb_server: None,
+            script: None,
+            event_viewer: false,
+            viewers: Vec::new(),
+            ntsc_filter: None,
+            scaler: None,
+            crt_filter: CrtFilter::new(CrtSettings::default()),
+            crt: false,
+            layout: Layout::default(),
+        }
+    }
+
+    /// Serve remote debuggers while the emulator runs
+    pub fn set_gdb_server(&mut self, gdb_server: Option<GdbServer>) {
+        self.gdb_server = gdb_server;
+    }
+
+    /// Show the event viewer, from the NES's event log, in place of the picture
+    pub fn set_event_viewer(&mut self, event_viewer: bool) {
+        self.event_viewer = event_viewer;
+    }
+
+    /// Open a viewer in a window of its own, which closes without quitting
+    pub fn add_viewer(&mut self, viewer: ViewerWindow) {
+        self.viewers.push(viewer);
+    }
+
+    /// Show the picture through an NTSC filter, or plainly if None
+    pub fn set_ntsc_filter(&mut self, ntsc_filter: Option<NtscFilter>) {
+        self.ntsc_filter = ntsc_filter;
+    }
+
+    /// Upscale the picture with a pixel art scaler, or just make the pixels bigger if None.
+    /// The CRT look, NTSC filter and event viewer take priority.
+    pub fn set_scaler(&mut self, scaler: Option<Scaler>) {
+        self.scaler = scaler;
+    }
+
+    /// Draw the picture like a CRT, with these settings, or plainly if None
+    pub fn set_crt(&mut self, settings: Option<CrtSettings>) {
+        if let Some(settings) = settings {
+            self.crt_filter.set_settings(settings);
+        }
+        self.crt = settings.is_some();
+    }
+
+    /// Crop the picture and fit it in the window this way
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// Run a script, which drives the NES while it runs
+    pub fn set_script(&mut self, script: Option<Script>) {
+        self.script = script;
+    }
+
+    fn tick(&mut self) {
+        match &mut self.script {
+            Some(script) => {
+                if let Err(err) = script.tick() {
+                    println!("script error: {}", err);
+                    self.script = None;
+                }
+            }
+            None => self.nes.borrow_mut().tick(),
+        }
+    }
+
+    /// Handle an SDL event, returning whether the emulator should quit
+    fn handle_event(&mut self, event: SDL_Event) -> bool {
+        match event {
+            SDL_Event::Quit { .. } => true,
+            SDL_Event::Window {
+                window_id,
+                win_event: WindowEvent::Close,
+                ..
+            } => {
+                self.viewers
+                    .retain(|viewer| viewer.window_id() != window_id);
+                window_id == self.canvas.window().id()
+            }
+            SDL_Event::KeyDown {
+                window_id,
+                keycode: Some(keycode),
+                ..
+            } => {
+                if window_id == self.canvas.window().id() {
+                    self.handle_key(keycode);
+                }
+                for viewer in &mut self.viewers {
+                    if viewer.window_id() == window_id {
+                        viewer.handle_key(keycode);
+                    }
+                }
+                false
+            }
+            SDL_Event::MouseMotion {
+                window_id, x, y, ..
+            } => {
+                for viewer in &mut self.viewers {
+                    if viewer.window_id() == window_id {
+                        viewer.handle_mouse_motion(x, y);
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Handle a hotkey pressed in the main window
+    fn handle_key(&mut self, keycode: Keycode) {
+        let layers = self.nes.borrow().layers();
+        if keycode == Keycode::F2 {
+            let i = SCALERS.iter().position(|&scaler| scaler == self.scaler);
+            self.scaler = SCALERS[i.map_or(0, |i| (i + 1) % SCALERS.len())];
+            let name = self
+                .scaler
+                .map_or("nearest neighbor".to_string(), |scaler| scaler.to_string());
+            self.canvas
+                .window_mut()
+                .set_title(&format!("KindNES | {}", name))
+                .unwrap();
+        } else if keycode == Keycode::F3 {
+            self.crt = !self.crt;
+        } else if let Some(layers) = toggle_layer(layers, keycode) {
+            self.nes.borrow_mut().set_layers(layers);
+            self.canvas
+                .window_mut()
+                .set_title(&format!("KindNES | {}", describe_layers(layers)))
+                .unwrap();
+        }
+    }
+
+    fn poll_gdb_server(&mut self) {
+        if let Some(gdb_server) = &mut self.gdb_server {
+            if let Err(err) = gdb_server.poll(&mut self.nes.borrow_mut()) {
+                println!("GDB server error: {}", err);
+            }
         }
     }
 
@@ -59,7 +334,6 @@ impl SDLUI {
             game_controller_subsystem.open(id).ok()
         });
 
-        self.canvas.set_scale(3.0, 3.0).unwrap();
         self.canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
         let mut event_pump = self.sdl_context.event_pump().unwrap();
 
@@ -70,6 +344,39 @@ impl SDLUI {
 
         let mut screen_buff = [0u8; 256 * 240 * 3];
 
+        let mut ntsc_texture = creator
+            .create_texture_target(
+                PixelFormatEnum::RGB24,
+                NTSC_WIDTH as u32,
+                NTSC_HEIGHT as u32,
+            )
+            .unwrap();
+        let mut ntsc_pixels = vec![0u32; NTSC_WIDTH * NTSC_HEIGHT];
+        let mut ntsc_buff = vec![0u8; NTSC_WIDTH * NTSC_HEIGHT * 3];
+
+        // The scaled picture, whose texture is remade when the scale changes
+        let mut scaled_texture = None;
+        let mut scaled_factor = 0;
+        let mut frame_pixels = Vec::new();
+        let mut scaled_pixels = Vec::new();
+        let mut scaled_buff = Vec::new();
+
+        // The CRT look, drawn at the size of the viewport
+        let mut crt_texture = None;
+        let mut crt_size = (0, 0);
+        let mut crt_source = Vec::new();
+        let mut crt_pixels = Vec::new();
+        let mut crt_buff = Vec::new();
+
+        let mut event_texture = creator
+            .create_texture_target(
+                PixelFormatEnum::RGB24,
+                DOTS_PER_SCANLINE as u32,
+                SCANLINES_PER_FRAME as u32,
+            )
+            .unwrap();
+        let mut grid_buff = vec![0u8; DOTS_PER_SCANLINE * SCANLINES_PER_FRAME * 3];
+
         let audio_subsystem = self.sdl_context.audio().unwrap();
         let desired_spec = AudioSpecDesired {
             freq: Some(SAMPLE_RATE as i32),
@@ -108,59 +415,72 @@ impl SDLUI {
         'main_loop: loop {
             if !self.nes.borrow().has_cartridge() {
                 for event in event_pump.poll_iter() {
-                    match event {
-                        SDL_Event::Quit { .. } => break 'main_loop,
-                        _ => {}
+                    if self.handle_event(event) {
+                        break 'main_loop;
                     }
                 }
                 continue;
             }
 
             if self.nes.borrow().paused {
+                self.poll_gdb_server();
                 for event in event_pump.poll_iter() {
-                    match event {
-                        SDL_Event::Quit { .. } => break 'main_loop,
-                        _ => {}
+                    if self.handle_event(event) {
+                        break 'main_loop;
                     }
                 }
 
                 // Draw the game screen below a gray tint and a pause icon (two parallel lines)
-                self.canvas.copy(&texture, None, None).unwrap();
+                let (width, height) = self.canvas.output_size().unwrap();
+                let (width, height) = (width as usize, height as usize);
+                let viewport = self.layout.viewport(width, height);
+                self.canvas.set_draw_color(sdl2::pixels::Color::BLACK);
+                self.canvas.clear();
+                if self.event_viewer {
+                    let viewport = letterbox(
+                        DOTS_PER_SCANLINE as f32,
+                        SCANLINES_PER_FRAME,
+                        false,
+                        width,
+                        height,
+                    );
+                    copy_picture(&mut self.canvas, &event_texture, None, viewport);
+                } else if let (true, Some(crt_texture)) = (self.crt, &crt_texture) {
+                    copy_picture(&mut self.canvas, crt_texture, None, viewport);
+                } else if self.ntsc_filter.is_some() {
+                    let area = self.layout.crop(NTSC_WIDTH, NTSC_HEIGHT);
+                    copy_picture(&mut self.canvas, &ntsc_texture, Some(area), viewport);
+                } else if let (Some(_), Some(scaled_texture)) = (self.scaler, &scaled_texture) {
+                    let area = self.layout.crop(256 * scaled_factor, 240 * scaled_factor);
+                    copy_picture(&mut self.canvas, scaled_texture, Some(area), viewport);
+                } else {
+                    let area = self.layout.crop(256, 240);
+                    copy_picture(&mut self.canvas, &texture, Some(area), viewport);
+                }
                 self.canvas
                     .set_draw_color(sdl2::pixels::Color::RGBA(50, 50, 50, 215));
-                let canvas_size = self.canvas.output_size().unwrap();
                 self.canvas
-                    .fill_rect(sdl2::rect::Rect::new(0, 0, canvas_size.0, canvas_size.1))
+                    .fill_rect(sdl2::rect::Rect::new(0, 0, width as u32, height as u32))
                     .unwrap();
                 self.canvas
                     .set_draw_color(sdl2::pixels::Color::RGB(225, 25, 25));
+                let (center_x, center_y) = ((width / 2) as i32, (height / 2) as i32);
                 self.canvas
-                    .fill_rect(sdl2::rect::Rect::new(
-                        ((canvas_size.0 / 6) - 13) as i32,
-                        ((canvas_size.1 / 6) - 15) as i32,
-                        10,
-                        30,
-                    ))
+                    .fill_rect(sdl2::rect::Rect::new(center_x - 39, center_y - 45, 30, 90))
                     .unwrap();
                 self.canvas
-                    .fill_rect(sdl2::rect::Rect::new(
-                        ((canvas_size.0 / 6) + 3) as i32,
-                        ((canvas_size.1 / 6) - 15) as i32,
-                        10,
-                        30,
-                    ))
+                    .fill_rect(sdl2::rect::Rect::new(center_x + 9, center_y - 45, 30, 90))
                     .unwrap();
                 self.canvas.present();
                 continue;
             }
 
-            self.nes.borrow_mut().tick();
+            self.tick();
 
             if self.nes.borrow().get_shift_strobe() || cycle_interrupt_timer == 0 {
                 for event in event_pump.poll_iter() {
-                    match event {
-                        SDL_Event::Quit { .. } => break 'main_loop,
-                        _ => {}
+                    if self.handle_event(event) {
+                        break 'main_loop;
                     }
                 }
                 const DEAD_ZONE: i16 = 10_000;
@@ -188,6 +508,10 @@ impl SDLUI {
                     controller_byte <<= 1;
                     controller_byte |= bit;
                 }
+                // Input set by the script replaces the player's
+                if let Some(input) = self.script.as_ref().and_then(|script| script.input()) {
+                    controller_byte = input;
+                }
                 self.nes
                     .borrow_mut()
                     .try_fill_controller_shift(controller_byte);
@@ -221,23 +545,153 @@ impl SDLUI {
                     now = time::Instant::now();
                 }
                 frame_count += 1;
+                self.poll_gdb_server();
 
-                let mut pixel_i = 0;
-                for y in 0..240 {
-                    for x in 0..256 {
-                        let color = framebuffer[y][x];
-                        let c = COLORS[(color as usize) % 64];
-                        let (r, g, b) =
-                            ((c >> 16) as u8, ((c >> 8) & 0xFF) as u8, (c & 0xFF) as u8);
-                        screen_buff[pixel_i + 0] = r;
-                        screen_buff[pixel_i + 1] = g;
-                        screen_buff[pixel_i + 2] = b;
-                        pixel_i += 3;
+                self.nes
+                    .borrow()
+                    .render_frame(PixelFormat::Rgb8, &mut screen_buff, 256 * 3);
+                if let Some(script) = &self.script {
+                    let overlay = script.overlay();
+                    for y in 0..240 {
+                        for x in 0..256 {
+                            if let Some(c) = overlay.pixel(x, y) {
+                                let pixel_i = (y * 256 + x) * 3;
+                                screen_buff[pixel_i] = (c >> 16) as u8;
+                                screen_buff[pixel_i + 1] = ((c >> 8) & 0xFF) as u8;
+                                screen_buff[pixel_i + 2] = (c & 0xFF) as u8;
+                            }
+                        }
                     }
                 }
                 texture.update(None, &screen_buff, 256 * 3).unwrap();
-                self.canvas.copy(&texture, None, None).unwrap();
+                if let Some(ntsc_filter) = &mut self.ntsc_filter {
+                    let burst_phase = self.nes.borrow().burst_phase();
+                    ntsc_filter.render(&framebuffer, burst_phase, &mut ntsc_pixels);
+                    let overlay = self.script.as_ref().map(|script| script.overlay());
+                    for (i, &pixel) in ntsc_pixels.iter().enumerate() {
+                        let (x, y) = (i % NTSC_WIDTH, i / NTSC_WIDTH);
+                        let c = overlay
+                            .as_ref()
+                            .and_then(|overlay| overlay.pixel(x * 256 / NTSC_WIDTH, y))
+                            .unwrap_or(pixel);
+                        ntsc_buff[i * 3] = (c >> 16) as u8;
+                        ntsc_buff[i * 3 + 1] = ((c >> 8) & 0xFF) as u8;
+                        ntsc_buff[i * 3 + 2] = (c & 0xFF) as u8;
+                    }
+                    ntsc_texture
+                        .update(None, &ntsc_buff, NTSC_WIDTH * 3)
+                        .unwrap();
+                }
+                let (window_width, window_height) = self.canvas.output_size().unwrap();
+                let (window_width, window_height) = (window_width as usize, window_height as usize);
+                let viewport = self.layout.viewport(window_width, window_height);
+                if self.crt && viewport.width > 0 && viewport.height > 0 {
+                    // The CRT look goes over the NTSC filter, if it's on
+                    let (source_buff, source_width) = if self.ntsc_filter.is_some() {
+                        (&ntsc_buff[..], NTSC_WIDTH)
+                    } else {
+                        (&screen_buff[..], 256)
+                    };
+                    let area = self.layout.crop(source_width, 240);
+                    unpack_rgb24(source_buff, source_width, area, &mut crt_source);
+                    let (width, height) = (viewport.width as u32, viewport.height as u32);
+                    if (width, height) != crt_size {
+                        crt_texture = Some(
+                            creator
+                                .create_texture_streaming(PixelFormatEnum::ARGB8888, width, height)
+                                .unwrap(),
+                        );
+                        crt_size = (width, height);
+                        crt_pixels = vec![0; (width * height) as usize];
+                        crt_buff = vec![0; (width * height * 4) as usize];
+                    }
+                    let (width, height) = (width as usize, height as usize);
+                    self.crt_filter.render(
+                        &crt_source,
+                        area.width,
+                        area.height,
+                        &mut crt_pixels,
+                        width,
+                        height,
+                    );
+                    for (&color, out) in crt_pixels.iter().zip(crt_buff.chunks_exact_mut(4)) {
+                        PixelFormat::Argb8888.write(color, out);
+                    }
+                    if let Some(crt_texture) = &mut crt_texture {
+                        crt_texture.update(None, &crt_buff, width * 4).unwrap();
+                    }
+                }
+                let scaler = self
+                    .scaler
+                    .filter(|_| !self.crt && self.ntsc_filter.is_none());
+                if let Some(scaler) = scaler {
+                    let frame = Rect {
+                        x: 0,
+                        y: 0,
+                        width: 256,
+                        height: 240,
+                    };
+                    unpack_rgb24(&screen_buff, 256, frame, &mut frame_pixels);
+                    let factor = scaler.factor();
+                    let (width, height) = (256 * factor, 240 * factor);
+                    if factor != scaled_factor {
+                        scaled_texture = Some(
+                            creator
+                                .create_texture_streaming(
+                                    PixelFormatEnum::ARGB8888,
+                                    width as u32,
+                                    height as u32,
+                                )
+                                .unwrap(),
+                        );
+                        scaled_factor = factor;
+                        scaled_pixels = vec![0; width * height];
+                        scaled_buff = vec![0; width * height * 4];
+                    }
+                    scaler.scale(&frame_pixels, 256, 240, &mut scaled_pixels);
+                    for (&color, out) in scaled_pixels.iter().zip(scaled_buff.chunks_exact_mut(4)) {
+                        PixelFormat::Argb8888.write(color, out);
+                    }
+                    if let Some(scaled_texture) = &mut scaled_texture {
+                        scaled_texture
+                            .update(None, &scaled_buff, width * 4)
+                            .unwrap();
+                    }
+                }
+                // Black bars go around the picture where it doesn't fit the window's shape
+                self.canvas.set_draw_color(sdl2::pixels::Color::BLACK);
+                self.canvas.clear();
+                if self.event_viewer {
+                    if let Some(event_log) = self.nes.borrow().event_log() {
+                        draw_event_viewer(&screen_buff, event_log.last_frame(), &mut grid_buff);
+                    }
+                    event_texture
+                        .update(None, &grid_buff, DOTS_PER_SCANLINE * 3)
+                        .unwrap();
+                    let viewport = letterbox(
+                        DOTS_PER_SCANLINE as f32,
+                        SCANLINES_PER_FRAME,
+                        false,
+                        window_width,
+                        window_height,
+                    );
+                    copy_picture(&mut self.canvas, &event_texture, None, viewport);
+                } else if let (true, Some(crt_texture)) = (self.crt, &crt_texture) {
+                    copy_picture(&mut self.canvas, crt_texture, None, viewport);
+                } else if self.ntsc_filter.is_some() {
+                    let area = self.layout.crop(NTSC_WIDTH, NTSC_HEIGHT);
+                    copy_picture(&mut self.canvas, &ntsc_texture, Some(area), viewport);
+                } else if let (Some(_), Some(scaled_texture)) = (self.scaler, &scaled_texture) {
+                    let area = self.layout.crop(256 * scaled_factor, 240 * scaled_factor);
+                    copy_picture(&mut self.canvas, scaled_texture, Some(area), viewport);
+                } else {
+                    let area = self.layout.crop(256, 240);
+                    copy_picture(&mut self.canvas, &texture, Some(area), viewport);
+                }
                 self.canvas.present();
+                for viewer in &mut self.viewers {
+                    viewer.render(&self.nes.borrow());
+                }
 
                 let elapsed = fps_timer.elapsed();
                 if elapsed < time::Duration::from_millis(16) {
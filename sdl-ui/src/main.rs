@@ -1,19 +1,164 @@
+use nes::event_log::{DOTS_PER_SCANLINE, SCANLINES_PER_FRAME};
+use nes::gdb::GdbServer;
+use nes::profiler::{Profiler, ReportFormat};
+use nes::script::Script;
+use nes::symbols::SymbolTable;
+use nes::trace::{TraceArm, TraceFormat, TraceLogger};
+use nes::video::{
+    AspectRatio, CrtSettings, Layout, NtscFilter, NtscSetup, Overscan, Palette, PaletteSettings,
+    Scaler,
+};
 use nes::NES;
+use sdl_ui::{Viewer, ViewerWindow};
 
 use std::cell::RefCell;
 use std::env;
 use std::fs::File;
+use std::path::Path;
 use std::process;
 use std::rc::Rc;
 
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "usage: {} [--trace <log file>] [--trace-format nestest|mesen|fceux] [--trace-from pc:<address>[-<address>]|frame:<n>] [--trace-frames <n>] [--trace-labels] [--cdl <.cdl file>] [--profile <report file>] [--profile-format table|json] [--gdb <port>] [--symbols <.dbg or .nl file> ...] [--script <.rhai file>] [--event-viewer] [--nametables] [--pattern-tables] [--sprites] [--no-sprite-limit] [--ntsc composite|svideo|rgb] [--palette <.pal file>] [--generate-palette <settings>] [--scaler scale2x|scale3x|blend2x|blend3x|xbrz2-6] [--crt] [--crt-settings <settings>] [--overscan ntsc|pal|none|<margins>] [--aspect square|8:7|4:3] [--integer-scaling] <NES ROM file>",
+        program
+    );
+    process::exit(1);
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("usage: {} <NES ROM file>", args[0]);
+
+    let mut rom_path = None;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::Nestest;
+    let mut trace_arm = TraceArm::Always;
+    let mut trace_frames = None;
+    let mut trace_labels = false;
+    let mut cdl_path = None;
+    let mut profile_path = None;
+    let mut profile_format = ReportFormat::Table;
+    let mut gdb_port = None;
+    let mut symbol_paths = Vec::new();
+    let mut script_path = None;
+    let mut event_viewer = false;
+    let mut viewers = Vec::new();
+    let mut sprite_limit = true;
+    let mut ntsc_setup = None;
+    let mut palette_path = None;
+    let mut palette_settings = None;
+    let mut scaler = None;
+    let mut crt_settings = None;
+    let mut layout = Layout::default();
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match &arg[..] {
+            "--trace" => trace_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "--trace-format" => {
+                let format_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                trace_format = format_str.parse().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, format_str);
+                    process::exit(1);
+                });
+            }
+            "--trace-from" => {
+                let arm_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                trace_arm = arm_str.parse().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, arm_str);
+                    process::exit(1);
+                });
+            }
+            "--trace-frames" => {
+                let frames_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                trace_frames = Some(frames_str.parse::<u64>().unwrap_or_else(|_| {
+                    eprintln!("invalid frame count: {}", frames_str);
+                    process::exit(1);
+                }));
+            }
+            "--trace-labels" => trace_labels = true,
+            "--cdl" => cdl_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "--profile" => profile_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "--profile-format" => {
+                let format_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                profile_format = format_str.parse().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, format_str);
+                    process::exit(1);
+                });
+            }
+            "--gdb" => {
+                let port_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                gdb_port = Some(port_str.parse::<u16>().unwrap_or_else(|_| {
+                    eprintln!("invalid port: {}", port_str);
+                    process::exit(1);
+                }));
+            }
+            "--symbols" => {
+                symbol_paths.push(arg_iter.next().unwrap_or_else(|| usage(&args[0])));
+            }
+            "--script" => script_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "--event-viewer" => event_viewer = true,
+            "--nametables" => viewers.push(Viewer::Nametables),
+            "--pattern-tables" => viewers.push(Viewer::PatternTables),
+            "--sprites" => viewers.push(Viewer::Sprites),
+            "--no-sprite-limit" => sprite_limit = false,
+            "--ntsc" => {
+                let setup_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                ntsc_setup = Some(setup_str.parse::<NtscSetup>().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, setup_str);
+                    process::exit(1);
+                }));
+            }
+            "--palette" => palette_path = Some(arg_iter.next().unwrap_or_else(|| usage(&args[0]))),
+            "--generate-palette" => {
+                let settings_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                palette_settings = Some(settings_str.parse::<PaletteSettings>().unwrap_or_else(
+                    |err| {
+                        eprintln!("{}: {}", err, settings_str);
+                        process::exit(1);
+                    },
+                ));
+            }
+            "--scaler" => {
+                let scaler_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                scaler = Some(scaler_str.parse::<Scaler>().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, scaler_str);
+                    process::exit(1);
+                }));
+            }
+            "--crt" => crt_settings = crt_settings.or_else(|| Some(CrtSettings::default())),
+            "--crt-settings" => {
+                let settings_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                crt_settings = Some(settings_str.parse::<CrtSettings>().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, settings_str);
+                    process::exit(1);
+                }));
+            }
+            "--overscan" => {
+                let overscan_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                layout.overscan = overscan_str.parse::<Overscan>().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, overscan_str);
+                    process::exit(1);
+                });
+            }
+            "--aspect" => {
+                let aspect_str = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                layout.aspect_ratio = aspect_str.parse::<AspectRatio>().unwrap_or_else(|err| {
+                    eprintln!("{}: {}", err, aspect_str);
+                    process::exit(1);
+                });
+            }
+            "--integer-scaling" => layout.integer_scaling = true,
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => usage(&args[0]),
+        }
+    }
+    let rom_path = rom_path.unwrap_or_else(|| usage(&args[0]));
+    if palette_path.is_some() && palette_settings.is_some() {
+        eprintln!("--palette and --generate-palette can't be used together");
         process::exit(1);
     }
 
-    let file = File::open(&args[1]).unwrap_or_else(|err| {
+    let file = File::open(rom_path).unwrap_or_else(|err| {
         println!("failed to read file: {}", err);
         process::exit(1);
     });
@@ -24,15 +169,132 @@ fn main() {
         process::exit(1);
     });
 
+    nes.borrow_mut().set_sprite_limit(sprite_limit);
+
+    if let Some(path) = palette_path {
+        let palette = File::open(path)
+            .map_err(|_| "failed to open palette file")
+            .and_then(Palette::load)
+            .unwrap_or_else(|err| {
+                println!("failed to load palette: {}", err);
+                process::exit(1);
+            });
+        nes.borrow_mut().set_palette(palette);
+    } else if let Some(settings) = palette_settings {
+        nes.borrow_mut().set_palette(Palette::generate(settings));
+    }
+
+    if !symbol_paths.is_empty() {
+        let mut symbols = SymbolTable::new();
+        for path in symbol_paths {
+            symbols.load_file(Path::new(path)).unwrap_or_else(|err| {
+                println!("failed to load symbols from {}: {}", path, err);
+                process::exit(1);
+            });
+        }
+        nes.borrow_mut().set_symbols(Some(symbols));
+    }
+
+    if let Some(path) = trace_path {
+        let trace_file = File::create(path).unwrap_or_else(|err| {
+            println!("failed to create trace file: {}", err);
+            process::exit(1);
+        });
+        let mut logger = TraceLogger::to_file(trace_file, trace_format);
+        logger.arm = trace_arm;
+        logger.frame_limit = trace_frames;
+        logger.labels = trace_labels;
+        nes.borrow_mut().set_trace_logger(Some(logger));
+    }
+
+    if let Some(path) = cdl_path {
+        // Keep adding to an existing log, so that coverage builds up over several sessions
+        let result = match File::open(path) {
+            Ok(cdl_file) => nes.borrow_mut().load_code_data_log(cdl_file),
+            Err(_) => nes.borrow_mut().start_code_data_log(),
+        };
+        result.unwrap_or_else(|err| {
+            println!("failed to start code/data logger: {}", err);
+            process::exit(1);
+        });
+    }
+
+    if profile_path.is_some() {
+        nes.borrow_mut().set_profiler(Some(Profiler::new()));
+    }
+
+    let gdb_server = gdb_port.map(|port| {
+        let server = GdbServer::bind(("127.0.0.1", port)).unwrap_or_else(|err| {
+            println!("failed to start GDB server: {}", err);
+            process::exit(1);
+        });
+        println!("waiting for GDB connections on port {}", port);
+        server
+    });
+
+    if event_viewer {
+        nes.borrow_mut().start_event_log();
+    }
+
+    let script = script_path.map(|path| {
+        Script::from_file(nes.clone(), Path::new(path)).unwrap_or_else(|err| {
+            println!("failed to run script: {}", err);
+            process::exit(1);
+        })
+    });
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
+    // The event viewer shows every dot of the frame, including those outside the picture
+    let (width, height) = if event_viewer {
+        (DOTS_PER_SCANLINE * 3, SCANLINES_PER_FRAME * 3)
+    } else {
+        layout.size(3)
+    };
     let window = video_subsystem
-        .window("KindNES", (256.0 * 3.0) as u32, (240.0 * 3.0) as u32)
+        .window("KindNES", width as u32, height as u32)
         .position_centered()
+        .resizable()
         .build()
         .unwrap();
 
-    let mut sdl_ui = sdl_ui::SDLUI::new(sdl_context, window, nes);
+    let mut sdl_ui = sdl_ui::SDLUI::new(sdl_context, window, nes.clone());
+    sdl_ui.set_gdb_server(gdb_server);
+    sdl_ui.set_script(script);
+    sdl_ui.set_event_viewer(event_viewer);
+    sdl_ui.set_ntsc_filter(ntsc_setup.map(NtscFilter::new));
+    sdl_ui.set_scaler(scaler);
+    sdl_ui.set_crt(crt_settings);
+    sdl_ui.set_layout(layout);
+    for viewer in viewers {
+        let (width, height) = viewer.window_size();
+        let window = video_subsystem
+            .window(viewer.title(), width, height)
+            .build()
+            .unwrap();
+        sdl_ui.add_viewer(ViewerWindow::new(viewer, window));
+    }
     sdl_ui.render_loop();
+
+    if let Some(path) = cdl_path {
+        let result =
+            File::create(path).and_then(|cdl_file| nes.borrow().save_code_data_log(cdl_file));
+        if let Err(err) = result {
+            println!("failed to save code/data log: {}", err);
+        }
+    }
+
+    let report = nes.borrow().profiler().map(|profiler| {
+        let mut profile = profiler.session();
+        if let Some(symbols) = nes.borrow().symbols() {
+            profile.label(symbols);
+        }
+        profile.format(profile_format)
+    });
+    if let (Some(path), Some(report)) = (profile_path, report) {
+        if let Err(err) = std::fs::write(path, report) {
+            println!("failed to save profile: {}", err);
+        }
+    }
 }
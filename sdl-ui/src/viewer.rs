@@ -0,0 +1,141 @@
+use nes::{
+    RgbImage, NAMETABLE_VIEW_HEIGHT, NAMETABLE_VIEW_WIDTH, NES, PATTERN_TABLE_VIEW_SIZE as TABLE,
+    SPRITE_VIEW_HEIGHT, SPRITE_VIEW_WIDTH,
+};
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::WindowCanvas;
+use sdl2::video::Window;
+
+/// What a viewer window shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Viewer {
+    Nametables,
+    PatternTables, // Above two banks of CHR ROM, if the cartridge has any
+    Sprites,
+}
+
+impl Viewer {
+    pub fn title(self) -> &'static str {
+        match self {
+            Viewer::Nametables => "Nametables",
+            Viewer::PatternTables => "Pattern tables | P: palette, PgUp/PgDn: CHR ROM bank",
+            Viewer::Sprites => "Sprites | Point at one to see its attributes",
+        }
+    }
+
+    /// The size of the window, in pixels
+    pub fn window_size(self) -> (u32, u32) {
+        match self {
+            Viewer::Nametables => (NAMETABLE_VIEW_WIDTH as u32, NAMETABLE_VIEW_HEIGHT as u32),
+            Viewer::PatternTables => (4 * TABLE as u32, 4 * TABLE as u32),
+            Viewer::Sprites => (2 * SPRITE_VIEW_WIDTH as u32, 2 * SPRITE_VIEW_HEIGHT as u32),
+        }
+    }
+}
+
+/// A second window beside the game, which shows some of the PPU's state after every frame
+pub struct ViewerWindow {
+    viewer: Viewer,
+    canvas: WindowCanvas,
+    palette: u8,     // The palette that tiles are drawn in, from 0 to 7
+    chr_bank: usize, // The first of the two 4 KB banks of CHR ROM shown
+    mouse: Option<(i32, i32)>,
+}
+
+impl ViewerWindow {
+    pub fn new(viewer: Viewer, window: Window) -> Self {
+        Self {
+            viewer,
+            canvas: window.into_canvas().build().unwrap(),
+            palette: 0,
+            chr_bank: 0,
+            mouse: None,
+        }
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub(crate) fn handle_key(&mut self, keycode: Keycode) {
+        match keycode {
+            Keycode::P => self.palette = (self.palette + 1) % 8,
+            Keycode::PageUp => self.chr_bank = self.chr_bank.saturating_sub(2),
+            Keycode::PageDown => self.chr_bank += 2,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn handle_mouse_motion(&mut self, x: i32, y: i32) {
+        self.mouse = Some((x, y));
+    }
+
+    pub(crate) fn render(&mut self, nes: &NES) {
+        let image = match self.viewer {
+            Viewer::Nametables => nes.render_nametables(nes.palette().colors()),
+            Viewer::PatternTables => self.render_pattern_tables(nes),
+            Viewer::Sprites => {
+                self.show_hovered_sprite(nes);
+                nes.render_sprite_view(nes.palette())
+            }
+        };
+        self.present(&image);
+    }
+
+    /// Title the window with the attributes of the sprite under the mouse
+    fn show_hovered_sprite(&mut self, nes: &NES) {
+        let (width, height) = self.canvas.window().size();
+        let sprite = self.mouse.and_then(|(x, y)| {
+            let x = x.max(0) as usize * SPRITE_VIEW_WIDTH / width as usize;
+            let y = y.max(0) as usize * SPRITE_VIEW_HEIGHT / height as usize;
+            nes.sprite_in_view(x, y)
+        });
+        let title = match sprite {
+            Some(sprite) => format!("Sprite {}", sprite),
+            None => self.viewer.title().to_string(),
+        };
+        if self.canvas.window().title() != title {
+            self.canvas.window_mut().set_title(&title).unwrap();
+        }
+    }
+
+    fn render_pattern_tables(&mut self, nes: &NES) -> RgbImage {
+        let mut image = RgbImage::new(2 * TABLE, 2 * TABLE);
+        image.draw_image(
+            &nes.render_pattern_tables(self.palette, nes.palette().colors()),
+            0,
+            0,
+        );
+
+        let bank_count = nes.chr_bank_count();
+        if self.chr_bank >= bank_count {
+            self.chr_bank = bank_count.saturating_sub(1) & !1;
+        }
+        for i in 0..2 {
+            if let Some(bank) =
+                nes.render_chr_bank(self.chr_bank + i, self.palette, nes.palette().colors())
+            {
+                image.draw_image(&bank, i * TABLE, TABLE);
+            }
+        }
+        image
+    }
+
+    fn present(&mut self, image: &RgbImage) {
+        let creator = self.canvas.texture_creator();
+        let mut texture = creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                image.width as u32,
+                image.height as u32,
+            )
+            .unwrap();
+        texture
+            .update(None, &image.pixels, image.width * 3)
+            .unwrap();
+        self.canvas.copy(&texture, None, None).unwrap();
+        self.canvas.present();
+    }
+}
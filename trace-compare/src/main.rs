@@ -0,0 +1,76 @@
+use nes::trace::{compare_with_reference, TraceComparison};
+use nes::NES;
+
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "usage: {} [--context <lines>] [--cycles] [--pc <hex address>] <NES ROM file> <reference log>",
+        program
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut comparison = TraceComparison::new(5);
+    let mut start_pc = None;
+    let mut paths = Vec::new();
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
+        match &arg[..] {
+            "--context" => {
+                let lines = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                comparison.context_len = lines.parse().unwrap_or_else(|_| usage(&args[0]));
+            }
+            "--cycles" => comparison.compare_cycles = true,
+            "--pc" => {
+                let pc = arg_iter.next().unwrap_or_else(|| usage(&args[0]));
+                let pc = pc.trim_start_matches('$');
+                start_pc = Some(u16::from_str_radix(pc, 16).unwrap_or_else(|_| usage(&args[0])));
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        usage(&args[0]);
+    }
+
+    let rom_file = File::open(paths[0]).unwrap_or_else(|err| {
+        eprintln!("failed to read ROM: {}", err);
+        process::exit(2);
+    });
+    let reference_file = File::open(paths[1]).unwrap_or_else(|err| {
+        eprintln!("failed to read reference log: {}", err);
+        process::exit(2);
+    });
+
+    let mut nes = NES::new();
+    nes.load_rom(rom_file).unwrap_or_else(|err| {
+        eprintln!("failed to load ROM: {}", err);
+        process::exit(2);
+    });
+
+    // Some test ROMs, like nestest, have an automated mode that starts somewhere other than the reset vector
+    if let Some(pc) = start_pc {
+        let mut registers = nes.cpu_registers();
+        registers.pc = pc;
+        nes.set_cpu_registers(registers);
+    }
+
+    match compare_with_reference(&mut nes, BufReader::new(reference_file), comparison) {
+        Ok(None) => println!("traces match"),
+        Ok(Some(divergence)) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("failed to compare traces: {}", err);
+            process::exit(2);
+        }
+    }
+}
@@ -95,7 +95,11 @@ impl GameWindow {
 
     fn pause(&self) {
         let paused = self.nes.borrow().paused;
-        self.nes.borrow_mut().paused = !paused;
+        if paused {
+            self.nes.borrow_mut().resume();
+        } else {
+            self.nes.borrow_mut().paused = true;
+        }
         self.pause_item.set_checked(!paused);
     }
 }